@@ -0,0 +1,34 @@
+# 指定したディレクトリにある DRAT 形式の証明 (<テスト用のデータ名>.drat) を
+# 指定した solver の --check-drat で検査する
+# 証明はこのソルバーの --drat=<file> や minisat 等の DRAT を出力するソルバーで作っておく
+
+import sys
+import pathlib
+import subprocess
+
+try:
+    checker = sys.argv[1]
+except:
+    print("正しく検査に使う solver を指定してください")
+    exit(-1)
+
+try:
+    test_data_dir = pathlib.Path(sys.argv[2])
+    proof_path = pathlib.Path(sys.argv[3])
+except:
+    print("正しくテスト用のデータと証明のあるディレクトリを指定してください")
+    exit(-1)
+
+verified = "s VERIFIED"
+
+for test_data_path in sorted(test_data_dir.glob("*.cnf")):
+    proof_file = pathlib.Path.joinpath(proof_path, f"{test_data_path.name}.drat")
+    if not proof_file.exists():
+        continue
+
+    result = subprocess.run(["./"+checker, "--check-drat", test_data_path, proof_file], capture_output=True, text=True)
+    if verified not in result.stdout:
+        print(f"invalid proof at data:{test_data_path} proof:{proof_file}")
+        print(result.stdout)
+
+print("complete all proof check done")
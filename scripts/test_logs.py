@@ -1,9 +1,11 @@
 # 指定した2つのログファイルディレクトリのログファイルに記載されている
 # 実行結果が一致していることを確認する
+# テスト用のデータと verify-model を指定すると, SAT の割り当てが全ての節を充足するかも検査する
 
 import sys
 import re
 import pathlib
+import subprocess
 
 try:
     sample_log_path = pathlib.Path(sys.argv[1])
@@ -12,6 +14,10 @@ except:
     print("正しくログファイルのあるディレクトリを指定してください")
     exit(-1)
 
+# 省略可能, 例: test ./target/release/verify-model
+test_data_dir = pathlib.Path(sys.argv[3]) if len(sys.argv) > 3 else None
+verifier = sys.argv[4] if len(sys.argv) > 4 else None
+
 # 出力結果の検証
 unsat = "UNSATISFIABLE"
 sat = "SATISFIABLE"
@@ -32,6 +38,17 @@ for sample_log, my_log in zip(sample_logs, my_logs):
     try:
         if unsat in sample_log_data and unsat in my_log_data:
             continue
+        elif sat in sample_log_data and sat in my_log_data and verifier is not None:
+            # <データ名>.log のデータで割り当てを検査する
+            test_data_path = pathlib.Path.joinpath(test_data_dir, my_log.stem)
+            if not test_data_path.exists():
+                print(f"test data not found: {test_data_path}")
+                raise Exception
+            result = subprocess.run([verifier, test_data_path, my_log], capture_output=True, text=True)
+            if "s VERIFIED" in result.stdout:
+                continue
+            else:
+                raise Exception
         elif sat in sample_log_data and sat in my_log_data:
             sample_assigns = assigns_pattern.match(sample_log_data)[1].strip()
             my_assigns = assigns_pattern.match(my_log_data)[1].strip()
@@ -0,0 +1,217 @@
+use log::info;
+
+use crate::bigint::BigUint;
+use crate::random::Random;
+use crate::solver::{Clauses, Solver};
+
+/// XOR 制約, vars の変数の排他的論理和が parity に等しい
+pub type Xor = (Vec<usize>, bool);
+
+/// ランダムな XOR 制約で解空間を分割して, モデル数を近似する (ApproxMC, Chakraborty, Meel, Vardi 2013/2016)
+/// 確率 1 - delta 以上で, 返す値は真のモデル数の (1 + epsilon) 倍以内に入る
+pub struct ApproxCounter {
+    size_vars: usize,
+    clauses: Clauses,
+    sampling: Vec<usize>,       // 数える変数, モデル数はこの変数に射影したもの
+    rng: Random,
+
+    pub epsilon: f64,           // 許容する誤差
+    pub delta: f64,             // 誤差が epsilon を超える確率の上限
+
+    // ログ等
+    pub solver_calls: usize,
+}
+
+impl ApproxCounter {
+    pub fn new(size_vars: usize, clauses: &Clauses, seed: u64) -> Self {
+        ApproxCounter {
+            size_vars,
+            clauses: clauses.clone(),
+            sampling: (0..size_vars).collect(),
+            rng: Random::new(seed),
+
+            epsilon: 0.8,
+            delta: 0.2,
+
+            solver_calls: 0,
+        }
+    }
+
+    /// 数える変数を vars にする (独立な変数の集合を与えると速い)
+    pub fn set_sampling_set(&mut self, vars: &[usize]) {
+        self.sampling = vars.to_vec();
+    }
+
+    /// 1つの区画で数える解の数の上限
+    fn threshold(&self) -> usize {
+        let eps = self.epsilon;
+        (1.0 + 9.84 * (1.0 + eps / (1.0 + eps)) * (1.0 + 1.0 / eps).powi(2)).ceil() as usize
+    }
+
+    /// 推定を繰り返す回数, 推定値の中央値を答える
+    fn iterations(&self) -> usize {
+        (17.0 * (3.0 / self.delta).log2()).ceil() as usize
+    }
+
+    /// 論理式だけの Solver, 区画を分ける XOR 制約は推定の途中で1つずつ加える
+    /// # Returns
+    /// * `None` - 論理式が UNSAT
+    fn base_solver(&self) -> Option<Solver> {
+        let mut solver = Solver::new();
+        // 区画の解は少ないので, 局所探索より列挙を続けた方が速い
+        solver.use_rephase = false;
+        for clause in &self.clauses {
+            if !solver.add_clause(&mut clause.clone()) {
+                return None;
+            }
+        }
+        // 節に現れない変数も数えるので, 変数の数を揃えておく
+        while solver.size_vars() < self.size_vars {
+            solver.new_var();
+        }
+        Some(solver)
+    }
+
+    /// solver の sampling に射影した解を, 前に数えた解 found と合わせて limit 個まで数える
+    /// found の解は models が禁止する節を加えて列挙済みなので, 今の区画に入るものだけを残してから呼ぶ
+    fn bounded_count(&mut self, solver: &mut Solver, found: &mut Vec<Vec<Option<bool>>>, limit: usize) -> usize {
+        self.solver_calls += 1;
+        let rest = limit.saturating_sub(found.len());
+        found.extend(solver.models(Some(&self.sampling)).take(rest));
+        found.len()
+    }
+
+    /// sampling の各変数を確率 1/2 で含む, ランダムな XOR 制約
+    fn random_xor(&mut self) -> Xor {
+        let vars = self.sampling.iter().copied().filter(|_| self.rng.gen_bool(0.5)).collect();
+        (vars, self.rng.gen_bool(0.5))
+    }
+
+    /// 1回の推定, 区画の解が threshold 個未満になるまで XOR を1つずつ加え, 区画の解の数 * 区画の数を返す
+    /// 1つの Solver に XOR 制約を加えていき, 前の区画で見つけた解のうち新しい XOR 制約を満たすものは数え直さない
+    /// # Returns
+    /// * `Some((推定値, XOR の数))`
+    /// * `None` - XOR を増やしても区画の解が threshold 個未満にならなかった
+    fn estimate(&mut self, threshold: usize) -> Option<(BigUint, usize)> {
+        let mut solver = match self.base_solver() {
+            Some(solver) => solver,
+            None => return Some((BigUint::zero(), 0)),
+        };
+        let mut found: Vec<Vec<Option<bool>>> = Vec::new();
+        for m in 1..=self.sampling.len() {
+            let (vars, parity) = self.random_xor();
+            found.retain(|model| vars.iter().fold(false, |acc, &var| acc ^ (model[var] == Some(true))) == parity);
+            // 根で矛盾したら, 区画の解は found に残ったものだけ
+            solver.add_xor(&vars, parity);
+            let count = self.bounded_count(&mut solver, &mut found, threshold);
+            if count < threshold {
+                return Some((&BigUint::from_u64(count as u64) * &BigUint::pow2(m), m));
+            }
+        }
+        None
+    }
+
+    /// 射影したモデル数の近似値
+    pub fn count(&mut self) -> BigUint {
+        let threshold = self.threshold();
+        // 解が少なければ厳密に数える
+        let mut solver = match self.base_solver() {
+            Some(solver) => solver,
+            None => return BigUint::zero(),
+        };
+        let count = self.bounded_count(&mut solver, &mut Vec::new(), threshold);
+        if count < threshold {
+            info!("approxmc: {} solutions (exact)", count);
+            return BigUint::from_u64(count as u64);
+        }
+
+        let iterations = self.iterations();
+        info!("approxmc: threshold {}, {} iterations", threshold, iterations);
+        let mut estimates = Vec::new();
+        for i in 0..iterations {
+            if let Some((estimate, xors)) = self.estimate(threshold) {
+                info!("approxmc: iteration {}: {} xors, estimate {}", i, xors, estimate);
+                estimates.push(estimate);
+            }
+        }
+        if estimates.is_empty() {
+            return BigUint::zero();
+        }
+        estimates.sort_unstable();
+        estimates[estimates.len() / 2].clone()
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{brute_force, is_xor_model, random_clauses};
+
+    #[test]
+    fn small_count_is_exact() {
+        // 解が threshold 個未満なら, XOR で分割せずに厳密に数える
+        let mut rng = Random::new(3);
+        for seed in 0..20 {
+            let clauses = random_clauses(&mut rng, 6, 12, 3);
+            let mut counter = ApproxCounter::new(6, &clauses, seed);
+            assert!(brute_force(6, &clauses, &[]).len() < counter.threshold());
+            let expected = BigUint::from_u64(brute_force(6, &clauses, &[]).len() as u64);
+            assert_eq!(counter.count(), expected);
+        }
+    }
+
+    #[test]
+    fn bounded_count_keeps_solutions_of_previous_cells() {
+        // XOR 制約を1つずつ加えても, 前に数えた解を合わせて各区画の解の数を正しく数える
+        let mut rng = Random::new(5);
+        for seed in 0..20 {
+            let clauses = random_clauses(&mut rng, 8, 6, 3);
+            let mut counter = ApproxCounter::new(8, &clauses, seed);
+            let mut solver = counter.base_solver().unwrap();
+            let mut found = Vec::new();
+            let mut xors: Vec<Xor> = Vec::new();
+            for _ in 0..4 {
+                let expected = brute_force(8, &clauses, &xors).len().min(20);
+                found.retain(|model: &Vec<Option<bool>>| is_xor_model(&xors, model));
+                assert_eq!(counter.bounded_count(&mut solver, &mut found, 20), expected);
+                let (vars, parity) = counter.random_xor();
+                solver.add_xor(&vars, parity);
+                xors.push((vars, parity));
+            }
+        }
+    }
+
+    #[test]
+    fn large_count_is_within_tolerance() {
+        // 解が threshold 個以上あれば分割して推定し, 真の値の (1 + epsilon) 倍以内に入る
+        let mut rng = Random::new(7);
+        for seed in 0..3 {
+            let clauses = random_clauses(&mut rng, 12, 10, 3);
+            let exact = brute_force(12, &clauses, &[]).len() as f64;
+            let mut counter = ApproxCounter::new(12, &clauses, seed);
+            assert!(exact >= counter.threshold() as f64);
+            let estimate = counter.count().to_f64();
+            assert!(estimate <= exact * (1.0 + counter.epsilon), "{} vs {}", estimate, exact);
+            assert!(estimate >= exact / (1.0 + counter.epsilon), "{} vs {}", estimate, exact);
+        }
+    }
+
+    #[test]
+    fn count_projected_models() {
+        // 射影したモデル数を推定する, 射影しない変数の値は区別しない
+        let mut rng = Random::new(11);
+        let clauses = random_clauses(&mut rng, 12, 8, 3);
+        let sampling: Vec<usize> = (0..9).collect();
+        let mut projected: Vec<u64> = brute_force(12, &clauses, &[]).into_iter().map(|bits| bits & 0x1ff).collect();
+        projected.sort_unstable();
+        projected.dedup();
+        let exact = projected.len() as f64;
+
+        let mut counter = ApproxCounter::new(12, &clauses, 1);
+        counter.set_sampling_set(&sampling);
+        let estimate = counter.count().to_f64();
+        assert!(estimate <= exact * (1.0 + counter.epsilon), "{} vs {}", estimate, exact);
+        assert!(estimate >= exact / (1.0 + counter.epsilon), "{} vs {}", estimate, exact);
+    }
+}
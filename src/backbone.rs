@@ -0,0 +1,93 @@
+use log::info;
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+impl Solver {
+    /// 全ての解で真になるリテラル (backbone) を求める
+    /// 最初の解で真のリテラルを候補にして, 候補を chunk_size 個ずつ取り出し
+    /// 「そのどれかが偽になる」節を活性化リテラル付きで加えて仮定の下で解く
+    /// UNSAT なら取り出した候補は全て backbone なので単位節にし, SAT ならその解で偽になった候補を除く
+    /// projection が Some ならその変数だけを調べる, 前処理で除去されないように変数を freeze するので最初に解く前に呼ぶこと
+    /// # Returns
+    /// * `Some(backbone)` - backbone のリテラル, 変数の順
+    /// * `None` - UNSAT, または interrupt により打ち切った
+    pub fn backbone(&mut self, projection: Option<&[usize]>, chunk_size: usize) -> Option<Vec<Literal>> {
+        let vars: Vec<usize> = match projection {
+            Some(vars) => vars.to_vec(),
+            None => (0..self.size_vars()).collect(),
+        };
+        for &var in &vars {
+            let lit = Literal::Pos(var);
+            self.searcher.update_size_vars(&vec![lit]);
+            self.register_lits(&[lit]);
+            self.freeze(var);
+        }
+        if self.solve() != Some(true) {
+            return None;
+        }
+
+        // 割り当てのない変数は偽として扱う (write_model と同じ)
+        let mut candidates: Vec<Literal> = vars.iter()
+            .map(|&var| if self.model[var] == Some(true) { Literal::Pos(var) } else { Literal::Neg(var) })
+            .collect();
+        let mut backbone = Vec::new();
+        let mut calls = 1;
+        let chunk_size = chunk_size.max(1);
+        while !candidates.is_empty() {
+            let chunk: Vec<Literal> = candidates[candidates.len().saturating_sub(chunk_size)..].to_vec();
+            let activation = Literal::Pos(self.new_var());
+            let mut clause: Clause = chunk.iter().map(|lit| lit.not()).collect();
+            clause.push(activation.not());
+            self.add_clause(&mut clause);
+            let result = self.solve_with_assumptions(&[activation]);
+            calls += 1;
+            // 以後は節を無効にする
+            self.add_clause(&mut vec![activation.not()]);
+
+            match result {
+                Some(true) => {
+                    let model = &self.model;
+                    candidates.retain(|lit| model[lit.var()].unwrap_or(false) == lit.is_pos());
+                },
+                Some(false) => {
+                    candidates.truncate(candidates.len() - chunk.len());
+                    for lit in chunk {
+                        self.add_clause(&mut vec![lit]);
+                        backbone.push(lit);
+                    }
+                },
+                None => return None,
+            }
+        }
+        info!("backbone: {} of {} variables, {} solver calls", backbone.len(), vars.len(), calls);
+
+        backbone.sort_unstable_by_key(|lit| lit.var());
+        Some(backbone)
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backbone_of_small_formula() {
+        // x1 ∧ (¬x1 ∨ ¬x2) ∧ (x3 ∨ x4) の backbone は x1, ¬x2
+        for chunk_size in [1, 2, 8] {
+            let mut solver = Solver::new();
+            for clause in [vec![1], vec![-1, -2], vec![3, 4]] {
+                let mut clause: Clause = clause.into_iter().map(Literal::from_dimacs).collect();
+                solver.add_clause(&mut clause);
+            }
+            assert_eq!(solver.backbone(None, chunk_size), Some(vec![Literal::Pos(0), Literal::Neg(1)]));
+        }
+
+        let mut solver = Solver::new();
+        solver.add_clause(&mut vec![Literal::Pos(0)]);
+        solver.add_clause(&mut vec![Literal::Neg(0)]);
+        assert_eq!(solver.backbone(None, 1), None);
+    }
+}
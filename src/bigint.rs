@@ -0,0 +1,415 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+/// 任意精度の非負整数, モデル数の計算に使う
+/// 外部クレートに頼らず, 2^32 進数で下の桁から持つ
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    digits: Vec<u32>,   // 末尾に 0 の桁を持たない, 0 は空
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { digits: Vec::new() }
+    }
+
+    pub fn one() -> Self {
+        BigUint::from_u64(1)
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        let mut n = BigUint {
+            digits: vec![value as u32, (value >> 32) as u32],
+        };
+        n.trim();
+        n
+    }
+
+    /// 2^exp
+    pub fn pow2(exp: usize) -> Self {
+        let mut digits = vec![0; exp / 32 + 1];
+        digits[exp / 32] = 1 << (exp % 32);
+        BigUint { digits }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn trim(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    /// self を 2^exp 倍する
+    pub fn shl(&self, exp: usize) -> Self {
+        if self.is_zero() {
+            return BigUint::zero();
+        }
+        let (words, bits) = (exp / 32, exp % 32);
+        let mut digits = vec![0; words];
+        let mut carry = 0u32;
+        for &d in &self.digits {
+            if bits == 0 {
+                digits.push(d);
+            } else {
+                digits.push((d << bits) | carry);
+                carry = d >> (32 - bits);
+            }
+        }
+        digits.push(carry);
+        let mut n = BigUint { digits };
+        n.trim();
+        n
+    }
+
+    /// self を 2^exp で割った商
+    pub fn shr(&self, exp: usize) -> Self {
+        let (words, bits) = (exp / 32, exp % 32);
+        if words >= self.digits.len() {
+            return BigUint::zero();
+        }
+        let mut digits = Vec::with_capacity(self.digits.len() - words);
+        for i in words..self.digits.len() {
+            let high = self.digits.get(i + 1).copied().unwrap_or(0);
+            if bits == 0 {
+                digits.push(self.digits[i]);
+            } else {
+                digits.push((self.digits[i] >> bits) | (high << (32 - bits)));
+            }
+        }
+        let mut n = BigUint { digits };
+        n.trim();
+        n
+    }
+
+    /// 2進数での桁数
+    pub fn bits(&self) -> usize {
+        match self.digits.last() {
+            Some(&d) => self.digits.len() * 32 - d.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    /// 商と余り, 2^32 進数の筆算で1桁ずつ商を求める (Knuth, TAOCP Vol.2 4.3.1 Algorithm D)
+    pub fn div_rem(&self, divisor: &BigUint) -> (Self, Self) {
+        assert!(!divisor.is_zero(), "division by zero");
+        if self < divisor {
+            return (BigUint::zero(), self.clone());
+        }
+        if divisor.digits.len() == 1 {
+            let (quotient, rem) = self.div_rem_small(divisor.digits[0]);
+            return (quotient, BigUint::from_u64(rem as u64));
+        }
+
+        // 割る数の最上位の桁の最上位ビットが 1 になるようにずらすと, 仮の商の誤差が 2 以下になる
+        let shift = divisor.digits.last().unwrap().leading_zeros() as usize;
+        let v = divisor.shl(shift).digits;
+        let mut u = self.shl(shift).digits;
+        u.resize(self.digits.len() + 1, 0);
+        let n = v.len();
+        let m = self.digits.len() - n;
+        let base = 1u64 << 32;
+        let mut quotient = vec![0u32; m + 1];
+        for j in (0..=m).rev() {
+            // 上の2桁を割る数の最上位の桁で割って仮の商を求め, 次の桁も見て大きすぎれば減らす
+            let top = ((u[j + n] as u64) << 32) | u[j + n - 1] as u64;
+            let mut qhat = top / v[n - 1] as u64;
+            let mut rhat = top % v[n - 1] as u64;
+            while qhat >= base || qhat * v[n - 2] as u64 > ((rhat << 32) | u[j + n - 2] as u64) {
+                qhat -= 1;
+                rhat += v[n - 1] as u64;
+                if rhat >= base {
+                    break;
+                }
+            }
+
+            // u[j..=j + n] から qhat * v を引く
+            let mut borrow = 0i64;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let product = qhat * v[i] as u64 + carry;
+                carry = product >> 32;
+                let t = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+                u[i + j] = t as u32;
+                borrow = (t < 0) as i64;
+            }
+            let t = u[j + n] as i64 - borrow - carry as i64;
+            u[j + n] = t as u32;
+
+            // 引きすぎたら (確率 2/2^32 程度), 1 減らして v を足し戻す
+            if t < 0 {
+                qhat -= 1;
+                let mut carry = 0u64;
+                for i in 0..n {
+                    let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                    u[i + j] = sum as u32;
+                    carry = sum >> 32;
+                }
+                u[j + n] = u[j + n].wrapping_add(carry as u32);
+            }
+            quotient[j] = qhat as u32;
+        }
+
+        let mut quotient = BigUint { digits: quotient };
+        quotient.trim();
+        u.truncate(n);
+        let mut rem = BigUint { digits: u };
+        rem.trim();
+        (quotient, rem.shr(shift))
+    }
+
+    /// self^exp, 2乗を繰り返して求める
+    pub fn pow(&self, mut exp: u32) -> Self {
+        let mut result = BigUint::one();
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+
+    /// 最大公約数
+    pub fn gcd(&self, other: &BigUint) -> Self {
+        let (mut a, mut b) = (self.clone(), other.clone());
+        while !b.is_zero() {
+            let (_, r) = a.div_rem(&b);
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// 小さい数 divisor で割った商と余り
+    fn div_rem_small(&self, divisor: u32) -> (Self, u32) {
+        let mut digits = vec![0; self.digits.len()];
+        let mut rem = 0u64;
+        for i in (0..self.digits.len()).rev() {
+            let cur = (rem << 32) | self.digits[i] as u64;
+            digits[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        let mut n = BigUint { digits };
+        n.trim();
+        (n, rem as u32)
+    }
+
+    /// 近似値, 大きすぎる場合は inf
+    pub fn to_f64(&self) -> f64 {
+        self.digits.iter().rev().fold(0.0, |acc, &d| acc * 4_294_967_296.0 + d as f64)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.digits.len().cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.digits.len() < other.digits.len() {
+            self.digits.resize(other.digits.len(), 0);
+        }
+        let mut carry = 0u64;
+        for i in 0..self.digits.len() {
+            let sum = self.digits[i] as u64 + other.digits.get(i).copied().unwrap_or(0) as u64 + carry;
+            self.digits[i] = sum as u32;
+            carry = sum >> 32;
+            if carry == 0 && i >= other.digits.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.digits.push(carry as u32);
+        }
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut sum = self.clone();
+        sum += other;
+        sum
+    }
+}
+
+impl Sub<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    /// self >= other であること
+    fn sub(self, other: &BigUint) -> BigUint {
+        assert!(self >= other, "subtraction overflow");
+        let mut digits = self.digits.clone();
+        let mut borrow = 0i64;
+        for (i, digit) in digits.iter_mut().enumerate() {
+            let mut cur = *digit as i64 - other.digits.get(i).copied().unwrap_or(0) as i64 - borrow;
+            borrow = 0;
+            if cur < 0 {
+                cur += 1 << 32;
+                borrow = 1;
+            }
+            *digit = cur as u32;
+        }
+        let mut n = BigUint { digits };
+        n.trim();
+        n
+    }
+}
+
+impl Mul<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, &a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.digits.iter().enumerate() {
+                let cur = digits[i + j] as u64 + a as u64 * b as u64 + carry;
+                digits[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        let mut n = BigUint { digits };
+        n.trim();
+        n
+    }
+}
+
+/// 10 進数の文字列から読む
+impl FromStr for BigUint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(format!("invalid number: {}", s));
+        }
+        let ten = BigUint::from_u64(10);
+        let mut n = BigUint::zero();
+        for c in s.chars() {
+            let digit = c.to_digit(10).ok_or_else(|| format!("invalid number: {}", s))?;
+            n = &(&n * &ten) + &BigUint::from_u64(digit as u64);
+        }
+        Ok(n)
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // 10^9 ごとに区切って下の桁から求める
+        let mut chunks = Vec::new();
+        let mut n = self.clone();
+        while !n.is_zero() {
+            let (q, r) = n.div_rem_small(1_000_000_000);
+            chunks.push(r);
+            n = q;
+        }
+        write!(f, "{}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    #[test]
+    fn display_large_numbers() {
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::pow2(100).to_string(), "1267650600228229401496703205376");
+        let n = BigUint::from_u64(u64::MAX);
+        assert_eq!((&n * &n).to_string(), "340282366920938463426481119284349108225");
+        assert_eq!((&n + &BigUint::one()).to_string(), "18446744073709551616");
+        assert_eq!(BigUint::from_u64(3).shl(64), &BigUint::pow2(65) + &BigUint::pow2(64));
+        let (q, r) = (&n * &n).div_rem(&BigUint::from_u64(1_000_000_007));
+        assert_eq!(&(&q * &BigUint::from_u64(1_000_000_007)) + &r, &n * &n);
+        assert_eq!(r.to_string(), ((u64::MAX as u128 * u64::MAX as u128) % 1_000_000_007).to_string());
+        assert_eq!(BigUint::pow2(100).shr(37), BigUint::pow2(63));
+        assert_eq!(BigUint::from_u64(84).gcd(&BigUint::from_u64(36)), BigUint::from_u64(12));
+        assert_eq!(BigUint::from_u64(10).pow(30).to_string(), format!("1{}", "0".repeat(30)));
+        assert_eq!(BigUint::from_u64(7).pow(0), BigUint::one());
+    }
+
+    fn from_u128(value: u128) -> BigUint {
+        &BigUint::from_u64((value >> 64) as u64).shl(64) + &BigUint::from_u64(value as u64)
+    }
+
+    #[test]
+    fn div_rem_agrees_with_u128() {
+        let mut rng = Random::new(3);
+        let random = |rng: &mut Random| {
+            // 桁数をばらつかせ, 上の桁が 0xffff_ffff や 0 に近い場合も試す
+            let bits = rng.gen_range(129);
+            let value = ((rng.next_u64() as u128) << 64) | rng.next_u64() as u128;
+            let value = if bits == 128 { value } else { value & ((1u128 << bits) - 1) };
+            if rng.gen_bool(0.2) { value | (u128::MAX >> (128 - bits.max(1))) } else { value }
+        };
+        // 仮の商から引きすぎて足し戻す場合 (Hacker's Delight の divmnu の例)
+        let (a, b) = (0x7fff_ffff_8000_0000_0000_0000_0000_0000u128, 0x8000_0000_0000_0000_0000_0001u128);
+        assert_eq!(from_u128(a).div_rem(&from_u128(b)), (from_u128(a / b), from_u128(a % b)));
+        for _ in 0..20000 {
+            let a = random(&mut rng);
+            let b = random(&mut rng).max(1);
+            let (q, r) = from_u128(a).div_rem(&from_u128(b));
+            assert_eq!((q, r), (from_u128(a / b), from_u128(a % b)), "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn div_rem_of_large_numbers() {
+        let mut rng = Random::new(4);
+        for _ in 0..200 {
+            let digits = |rng: &mut Random, len: usize| {
+                let mut n = BigUint { digits: (0..len).map(|_| if rng.gen_bool(0.3) { u32::MAX } else { rng.next_u64() as u32 }).collect() };
+                n.trim();
+                n
+            };
+            let len = 1 + rng.gen_range(12);
+            let extra = rng.gen_range(12);
+            let a = digits(&mut rng, len + extra);
+            let b = digits(&mut rng, len);
+            if b.is_zero() {
+                continue;
+            }
+            let (q, r) = a.div_rem(&b);
+            assert!(r < b);
+            assert_eq!(&(&q * &b) + &r, a);
+        }
+        // 割り切れる場合と, 最大公約数
+        let a = BigUint::from_u64(3).pow(200);
+        let b = BigUint::from_u64(3).pow(120);
+        assert_eq!(a.div_rem(&b), (BigUint::from_u64(3).pow(80), BigUint::zero()));
+        let six = BigUint::from_u64(6).pow(90);
+        assert_eq!(six.gcd(&BigUint::from_u64(10).pow(70)), BigUint::from_u64(2).pow(70));
+    }
+}
@@ -0,0 +1,342 @@
+//! 論理式から節, 変数, リテラルを取り除きながら, 指定したコマンド (述語) が成功し続ける最小の論理式を探す
+//! ソルバーの誤答や panic を再現する小さな入力を作るのに使う
+//! 候補の論理式はファイルに書き出し, コマンドの引数の `{}` をそのパスに置き換えて (なければ末尾に加えて) 実行する
+//! コマンドの終了コードが 0 なら, 候補はまだ問題を再現しているとする
+//! 縮めた後, 変数の番号を 1 から詰めても再現するなら詰めたものを出力する
+//! `--timeout=秒` を付けると時間内に終わらないコマンドを kill し, 再現しないとする
+//! (`--timeout-is-interesting` を付けると再現したとする, 停止しない入力を縮めるのに使う)
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::process::{self, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use three_sat_solver_practice_2_h::dimacs_parser::parse_clauses;
+use three_sat_solver_practice_2_h::dimacs_writer::write_dimacs;
+use three_sat_solver_practice_2_h::literal::Literal;
+use three_sat_solver_practice_2_h::solver::Clauses;
+
+/// 候補の論理式をファイルに書き出して実行するコマンド
+struct CommandPredicate {
+    command: Vec<String>,   // 述語のコマンドと引数
+    candidate: String,      // 候補の論理式を書き出すパス
+    renumber: bool,         // 候補の変数を 1 から詰めて書き出すか, 縮め終わってから試す
+    timeout: Option<Duration>, // コマンドの実行時間の上限, 超えたら kill する
+    timeout_is_interesting: bool, // 時間切れを成功とみなすか
+}
+
+impl CommandPredicate {
+    /// 候補の論理式でコマンドが成功するか
+    fn run(&self, clauses: &Clauses) -> bool {
+        self.write(&self.candidate, clauses);
+
+        let mut args: Vec<String> = self.command[1..].iter().map(|arg| arg.replace("{}", &self.candidate)).collect();
+        if !self.command[1..].iter().any(|arg| arg.contains("{}")) {
+            args.push(self.candidate.clone());
+        }
+        let child = Command::new(&self.command[0])
+            .args(&args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            return false;
+        };
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return status.success(),
+                Ok(None) => {},
+                Err(_) => return false,
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return self.timeout_is_interesting;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn write(&self, path: &str, clauses: &Clauses) {
+        let (size_vars, clauses) = if self.renumber {
+            renumber(clauses)
+        } else {
+            let size_vars = clauses.iter().flatten().map(|lit| lit.var() + 1).max().unwrap_or(0);
+            (size_vars, clauses.clone())
+        };
+        let mut w = BufWriter::new(File::create(path).expect("file creating error"));
+        write_dimacs(&mut w, size_vars, &clauses).expect("file writing error");
+        w.flush().expect("file writing error");
+    }
+}
+
+/// 述語 predicate が成功し続けるように論理式を縮める
+struct Reducer<P: FnMut(&Clauses) -> bool> {
+    predicate: P,
+    tests: usize,           // 述語を実行した回数
+}
+
+impl<P: FnMut(&Clauses) -> bool> Reducer<P> {
+    fn new(predicate: P) -> Self {
+        Reducer {
+            predicate,
+            tests: 0,
+        }
+    }
+
+    /// 候補の論理式で述語が成功するか
+    fn is_interesting(&mut self, clauses: &Clauses) -> bool {
+        self.tests += 1;
+        (self.predicate)(clauses)
+    }
+
+    /// 節, 変数, リテラルを取り除けなくなるまで縮める
+    /// 最後の reduce_clauses で何も取り除けないので, どの節を1つ取り除いても述語は失敗する
+    fn reduce(&mut self, clauses: &mut Clauses) {
+        loop {
+            let before = clauses.clone();
+            self.reduce_clauses(clauses);
+            self.reduce_vars(clauses);
+            self.reduce_lits(clauses);
+            if *clauses == before {
+                break;
+            }
+        }
+    }
+
+    /// 節を取り除く, 半分ずつから始めて1つずつまで, まとめて取り除ける節を探す (ddmin)
+    fn reduce_clauses(&mut self, clauses: &mut Clauses) {
+        let mut chunk = (clauses.len() / 2).max(1);
+        loop {
+            let mut i = 0;
+            while i < clauses.len() {
+                let end = (i + chunk).min(clauses.len());
+                let candidate: Clauses = [&clauses[..i], &clauses[end..]].concat();
+                if self.is_interesting(&candidate) {
+                    *clauses = candidate;
+                } else {
+                    i = end;
+                }
+            }
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+    }
+
+    /// 変数を取り除く, 変数に真か偽を割り当てて, 充足した節と偽のリテラルを除く
+    fn reduce_vars(&mut self, clauses: &mut Clauses) {
+        let mut vars: Vec<usize> = clauses.iter().flatten().map(|lit| lit.var()).collect();
+        vars.sort_unstable();
+        vars.dedup();
+        for var in vars {
+            for lit in [Literal::Pos(var), Literal::Neg(var)] {
+                let candidate: Clauses = clauses.iter()
+                    .filter(|clause| !clause.contains(&lit))
+                    .map(|clause| clause.iter().filter(|&&l| l != lit.not()).copied().collect())
+                    .collect();
+                if self.is_interesting(&candidate) {
+                    *clauses = candidate;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// 節からリテラルを1つずつ取り除く
+    fn reduce_lits(&mut self, clauses: &mut Clauses) {
+        for i in 0..clauses.len() {
+            let mut j = clauses[i].len();
+            while j > 0 {
+                j -= 1;
+                let mut candidate = clauses.clone();
+                candidate[i].remove(j);
+                if self.is_interesting(&candidate) {
+                    *clauses = candidate;
+                }
+            }
+        }
+    }
+}
+
+/// 使われている変数を, 元の順のまま 1 から詰めて番号を付け直す
+fn renumber(clauses: &Clauses) -> (usize, Clauses) {
+    let mut vars: Vec<usize> = clauses.iter().flatten().map(|lit| lit.var()).collect();
+    vars.sort_unstable();
+    vars.dedup();
+    let clauses = clauses.iter()
+        .map(|clause| clause.iter().map(|lit| {
+            let var = vars.binary_search(&lit.var()).unwrap();
+            if lit.is_pos() { Literal::Pos(var) } else { Literal::Neg(var) }
+        }).collect())
+        .collect();
+    (vars.len(), clauses)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let usage = || -> ! {
+        eprintln!("usage: {} [--timeout=SECS] [--timeout-is-interesting] <input.cnf> <output.cnf> -- <command> [args...]", args[0]);
+        eprintln!("       `{{}}` in args is replaced with the candidate file, or it is appended to args");
+        eprintln!("       the candidate is kept while the command exits with 0");
+        eprintln!("       a command running longer than --timeout is killed and the candidate is rejected (kept with --timeout-is-interesting)");
+        process::exit(2);
+    };
+    let Some(separator) = args.iter().position(|arg| arg == "--") else {
+        usage();
+    };
+    let mut timeout = None;
+    let mut timeout_is_interesting = false;
+    let mut files = Vec::new();
+    for arg in &args[1..separator] {
+        if let Some(seconds) = arg.strip_prefix("--timeout=") {
+            match seconds.parse::<f64>().ok().and_then(|seconds| Duration::try_from_secs_f64(seconds).ok()) {
+                Some(duration) => timeout = Some(duration),
+                None => usage(),
+            }
+        } else if arg == "--timeout-is-interesting" {
+            timeout_is_interesting = true;
+        } else if arg.starts_with("--") {
+            usage();
+        } else {
+            files.push(arg);
+        }
+    }
+    if files.len() != 2 || args.len() <= separator + 1 || (timeout_is_interesting && timeout.is_none()) {
+        usage();
+    }
+    let (input, output, command) = (files[0], files[1], args[separator + 1..].to_vec());
+
+    let mut clauses = parse_clauses(&fs::read_to_string(input).expect("file reading error"));
+    let mut command = CommandPredicate {
+        command,
+        candidate: format!("{}.candidate.cnf", output),
+        renumber: false,
+        timeout,
+        timeout_is_interesting,
+    };
+    let (size_vars, size_clauses) = (clauses.iter().flatten().map(|lit| lit.var() + 1).max().unwrap_or(0), clauses.len());
+
+    let mut reducer = Reducer::new(|clauses: &Clauses| command.run(clauses));
+    if !reducer.is_interesting(&clauses) {
+        eprintln!("the command does not succeed on {}", input);
+        let _ = fs::remove_file(&command.candidate);
+        process::exit(1);
+    }
+    reducer.reduce(&mut clauses);
+    let tests = reducer.tests + 1;
+
+    command.renumber = true;
+    if !command.run(&clauses) {
+        command.renumber = false;
+        println!("c variables are not renumbered, the command does not succeed on the renumbered formula");
+    }
+    command.write(output, &clauses);
+    let _ = fs::remove_file(&command.candidate);
+    let (reduced_vars, _) = renumber(&clauses);
+    println!("c variables: {} -> {}", size_vars, reduced_vars);
+    println!("c clauses  : {} -> {}", size_clauses, clauses.len());
+    println!("c tests    : {}", tests);
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use three_sat_solver_practice_2_h::clause::Clause;
+    use three_sat_solver_practice_2_h::random::Random;
+    use three_sat_solver_practice_2_h::solver::Solver;
+
+    fn is_unsat(clauses: &Clauses) -> bool {
+        let mut solver = Solver::new();
+        let ok = clauses.iter().all(|clause| solver.add_clause(&mut clause.clone()));
+        !ok || solver.solve() == Some(false)
+    }
+
+    /// 3 羽の鳩と 2 つの巣の鳩の巣原理 (極小 UNSAT, 変数 0..6) に,
+    /// 全て真で充足する変数 6..16 の節を混ぜる, UNSAT にする節の組は鳩の巣原理の節だけ
+    fn formula_with_core() -> (Clauses, Clauses) {
+        let var = |pigeon: usize, hole: usize| pigeon * 2 + hole;
+        let mut core: Clauses = (0..3).map(|p| vec![Literal::Pos(var(p, 0)), Literal::Pos(var(p, 1))]).collect();
+        for h in 0..2 {
+            for p in 0..3 {
+                for q in p + 1..3 {
+                    core.push(vec![Literal::Neg(var(p, h)), Literal::Neg(var(q, h))]);
+                }
+            }
+        }
+
+        let mut rng = Random::new(1);
+        let mut clauses = Vec::new();
+        for i in 0..40 {
+            let mut clause: Clause = (0..3)
+                .map(|_| {
+                    let var = 6 + rng.gen_range(10);
+                    if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) }
+                })
+                .collect();
+            clause[0] = Literal::Pos(clause[0].var());
+            clauses.push(clause);
+            if i % 4 == 0 && i / 4 < core.len() {
+                clauses.push(core[i / 4].clone());
+            }
+        }
+        (clauses, core)
+    }
+
+    #[test]
+    fn reduce_clauses_to_the_core() {
+        let (mut clauses, core) = formula_with_core();
+        assert!(is_unsat(&clauses));
+        let mut reducer = Reducer::new(is_unsat);
+        reducer.reduce_clauses(&mut clauses);
+        assert_eq!(clauses, core);
+    }
+
+    #[test]
+    fn reduced_formula_is_one_minimal() {
+        let (mut clauses, core) = formula_with_core();
+        let mut reducer = Reducer::new(is_unsat);
+        reducer.reduce(&mut clauses);
+
+        // 失敗を再現し, どの節を1つ取り除いても再現しない
+        assert!(is_unsat(&clauses));
+        assert!(clauses.len() <= core.len());
+        for i in 0..clauses.len() {
+            let mut candidate = clauses.clone();
+            candidate.remove(i);
+            assert!(!is_unsat(&candidate), "{:?} without clause {}", clauses, i);
+        }
+    }
+
+    /// sh -c script をテスト用の候補のパスで実行する述語
+    fn shell(script: &str, timeout: Option<Duration>, timeout_is_interesting: bool) -> CommandPredicate {
+        let candidate = env::temp_dir().join(format!("cnf-reduce-test-{}.cnf", process::id()));
+        CommandPredicate {
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            candidate: candidate.to_string_lossy().into_owned(),
+            renumber: false,
+            timeout,
+            timeout_is_interesting,
+        }
+    }
+
+    #[test]
+    fn command_is_killed_after_timeout() {
+        let clauses = vec![vec![Literal::Pos(0)]];
+        let timeout = Some(Duration::from_millis(100));
+        assert!(shell("exit 0", timeout, false).run(&clauses));
+        assert!(!shell("exit 1", timeout, true).run(&clauses));
+
+        let start = Instant::now();
+        let predicate = shell("sleep 10", timeout, false);
+        assert!(!predicate.run(&clauses));
+        assert!(shell("sleep 10", timeout, true).run(&clauses));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let _ = fs::remove_file(&predicate.candidate);
+    }
+}
@@ -0,0 +1,355 @@
+//! 小さなランダムな論理式を Solver と総当たりで解き, SAT/UNSAT とモデルが正しいかを比べる
+//! 節の長さ k と節の数の比を変え, 単位節, 重複したリテラル, 恒真な節, 同じ節も混ぜる
+//! 途中で解いてから残りの節を加えて解き直し, 一致しなかった (panic を含む) 論理式はファイルに書き出す
+//! 仮定の下でも解き, UNSAT なら failed_assumptions だけで UNSAT になるかも調べる
+//! 後から加える節と仮定の変数は freeze する場合としない場合があり, しなければ前処理で除去した節を戻す処理を通る
+//! 解の列挙 (Solver::models) とモデル数 (Counter) も, 射影する場合を含めて総当たりと比べる
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::panic;
+use std::process;
+
+use three_sat_solver_practice_2_h::bigint::BigUint;
+use three_sat_solver_practice_2_h::clause::Clause;
+use three_sat_solver_practice_2_h::count::Counter;
+use three_sat_solver_practice_2_h::dimacs_writer::write_dimacs;
+use three_sat_solver_practice_2_h::literal::Literal;
+use three_sat_solver_practice_2_h::random::Random;
+use three_sat_solver_practice_2_h::solver::{Clauses, Solver};
+
+/// 1つの論理式と, 解くときの Solver の設定
+struct Case {
+    size_vars: usize,
+    clauses: Clauses,
+    split: usize,           // clauses[..split] で一度解いてから残りを加える
+    assumptions: [Vec<Literal>; 2], // 1回目と2回目に解くときの仮定
+    freeze: bool,           // 後から加える節と仮定の変数を, 最初に解く前に freeze するか
+    projection: Option<Vec<usize>>, // 列挙とモデル数で射影する変数
+    preprocess: bool,
+    vivify: bool,
+    rephase: bool,
+    track_core: bool,
+    random_var_freq: f64,
+    seed: u64,
+}
+
+impl Case {
+    fn generate(rng: &mut Random, max_vars: usize) -> Self {
+        let size_vars = 1 + rng.gen_range(max_vars);
+        let k = 1 + rng.gen_range(4);
+        let ratio = 0.5 + rng.gen_f64() * [2.0, 4.0, 7.0, 12.0][k - 1];
+        let num_clauses = ((size_vars as f64 * ratio) as usize).max(1);
+
+        let mut clauses: Clauses = Vec::new();
+        let random_lit = |rng: &mut Random| {
+            let var = rng.gen_range(size_vars);
+            if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) }
+        };
+        for _ in 0..num_clauses {
+            if !clauses.is_empty() && rng.gen_bool(0.05) {
+                // 同じ節
+                let clause = clauses[rng.gen_range(clauses.len())].clone();
+                clauses.push(clause);
+                continue;
+            }
+            let len = if rng.gen_bool(0.1) { 1 } else { k };
+            let mut clause: Clause = (0..len).map(|_| random_lit(rng)).collect();
+            if rng.gen_bool(0.1) {
+                // 重複したリテラル
+                clause.push(clause[rng.gen_range(clause.len())]);
+            }
+            if rng.gen_bool(0.05) {
+                // 恒真な節
+                clause.push(clause[rng.gen_range(clause.len())].not());
+            }
+            let i = rng.gen_range(clause.len());
+            let last = clause.len() - 1;
+            clause.swap(i, last);
+            clauses.push(clause);
+        }
+
+        let mut assumptions = [Vec::new(), Vec::new()];
+        for assumptions in &mut assumptions {
+            if rng.gen_bool(0.3) {
+                *assumptions = (0..1 + rng.gen_range(3)).map(|_| random_lit(rng)).collect();
+            }
+        }
+
+        let projection = if rng.gen_bool(0.5) {
+            Some((0..size_vars).filter(|_| rng.gen_bool(0.5)).collect())
+        } else {
+            None
+        };
+
+        Case {
+            size_vars,
+            assumptions,
+            freeze: rng.gen_bool(0.5),
+            projection,
+            split: rng.gen_range(clauses.len() + 1),
+            clauses,
+            preprocess: rng.gen_bool(0.5),
+            vivify: rng.gen_bool(0.5),
+            rephase: rng.gen_bool(0.5),
+            track_core: rng.gen_bool(0.2),
+            random_var_freq: if rng.gen_bool(0.5) { 0.0 } else { rng.gen_f64() * 0.5 },
+            seed: rng.next_u64(),
+        }
+    }
+
+    /// 総当たりで clauses を充足する割り当てを全て求める, i 番目のビットが変数 i の値
+    fn brute_force_models(&self, clauses: &[Clause]) -> Vec<u64> {
+        let masks: Vec<(u64, u64)> = clauses.iter()
+            .map(|clause| clause.iter().fold((0, 0), |(pos, neg), lit| {
+                if lit.is_pos() { (pos | 1 << lit.var(), neg) } else { (pos, neg | 1 << lit.var()) }
+            }))
+            .collect();
+        (0..1u64 << self.size_vars)
+            .filter(|bits| masks.iter().all(|&(pos, neg)| bits & pos != 0 || !bits & neg != 0))
+            .collect()
+    }
+
+    /// 総当たりで clauses を充足する割り当てがあるか
+    fn brute_force(&self, clauses: &[Clause]) -> bool {
+        !self.brute_force_models(clauses).is_empty()
+    }
+
+    /// 射影する変数のビット, 射影しなければ全ての変数
+    fn projection_mask(&self) -> u64 {
+        match &self.projection {
+            Some(vars) => vars.iter().fold(0, |mask, &var| mask | 1 << var),
+            None => (1 << self.size_vars) - 1,
+        }
+    }
+
+    /// 総当たりで求めた, 射影したモデル数
+    fn brute_force_count(&self) -> usize {
+        let mask = self.projection_mask();
+        let projected: HashSet<u64> = self.brute_force_models(&self.clauses).into_iter().map(|bits| bits & mask).collect();
+        projected.len()
+    }
+
+    /// 設定を反映した Solver
+    fn solver(&self) -> Solver {
+        let mut solver = Solver::new();
+        solver.use_preprocess = self.preprocess;
+        solver.use_vivify = self.vivify;
+        solver.use_rephase = self.rephase;
+        solver.track_core = self.track_core;
+        solver.random_var_freq = self.random_var_freq;
+        solver.seed = self.seed;
+        // 小さな論理式でもリスタートと節の間の処理が起きるようにする
+        solver.restart_first = 2;
+        solver
+    }
+
+    /// Solver で解いた結果が総当たりと一致するか, 一致しなければ理由を返す
+    fn check(&self) -> Result<(), String> {
+        let mut solver = self.solver();
+        // freeze しなければ, 後から加える節と仮定で除去した節を戻す
+        if self.freeze {
+            for lit in self.clauses[self.split..].iter().chain(&self.assumptions).flatten() {
+                solver.freeze(lit.var());
+            }
+        }
+
+        let mut ok = true;
+        for (i, clause) in self.clauses.iter().enumerate() {
+            if i == self.split {
+                self.compare(&mut solver, i, &self.assumptions[0], ok)?;
+            }
+            ok &= solver.add_clause(&mut clause.clone());
+        }
+        self.compare(&mut solver, self.clauses.len(), &self.assumptions[1], ok)
+    }
+
+    fn compare(&self, solver: &mut Solver, added: usize, assumptions: &[Literal], ok: bool) -> Result<(), String> {
+        let units = |lits: &[Literal]| lits.iter().map(|&lit| vec![lit]).collect::<Clauses>();
+        let clauses: Clauses = [&self.clauses[..added], &units(assumptions)].concat();
+        let expected = self.brute_force(&clauses);
+        let result = if ok { solver.solve_with_assumptions(assumptions) } else { Some(false) };
+        match result {
+            Some(result) if result != expected => {
+                Err(format!("after {} clauses: expected {}, but solver returned {}", added, answer(expected), answer(result)))
+            },
+            Some(true) => {
+                // 割り当てのない変数は偽とする (main の出力と同じ)
+                let model = &solver.model;
+                let value = |lit: &Literal| model.get(lit.var()).copied().flatten().unwrap_or(false) == lit.is_pos();
+                match clauses.iter().position(|clause| !clause.iter().any(value)) {
+                    Some(i) => Err(format!("after {} clauses: clause {} is falsified by the model", added, i + 1)),
+                    None => Ok(()),
+                }
+            },
+            Some(false) if ok => {
+                let failed = solver.failed_assumptions();
+                if !failed.iter().all(|lit| assumptions.contains(lit)) {
+                    Err(format!("after {} clauses: failed assumptions {:?} are not assumed", added, failed))
+                } else if self.brute_force(&[&self.clauses[..added], &units(failed)].concat()) {
+                    Err(format!("after {} clauses: failed assumptions {:?} are satisfiable", added, failed))
+                } else {
+                    Ok(())
+                }
+            },
+            Some(false) => Ok(()),
+            None => Err(format!("after {} clauses: solver returned no answer", added)),
+        }
+    }
+
+    /// Solver::models で列挙した解が総当たりと一致するか
+    /// 各解が全ての節を充足し, 射影した割り当てが重複せず, 数が総当たりと同じかを調べる
+    fn check_models(&self) -> Result<(), String> {
+        let mut solver = self.solver();
+        while solver.size_vars() < self.size_vars {
+            solver.new_var();
+        }
+        let mut ok = true;
+        for clause in &self.clauses {
+            ok &= solver.add_clause(&mut clause.clone());
+        }
+        let expected = self.brute_force_count();
+
+        let mask = self.projection_mask();
+        let mut seen: HashSet<u64> = HashSet::new();
+        let models: Vec<Vec<Option<bool>>> = if ok {
+            solver.models(self.projection.as_deref()).take(expected + 1).collect()
+        } else {
+            Vec::new()
+        };
+        for model in &models {
+            let value = |lit: &Literal| model.get(lit.var()).copied().flatten().unwrap_or(false) == lit.is_pos();
+            if let Some(i) = self.clauses.iter().position(|clause| !clause.iter().any(value)) {
+                return Err(format!("models: clause {} is falsified by a model", i + 1));
+            }
+            let bits = (0..self.size_vars).filter(|&var| model[var] == Some(true)).fold(0, |bits, var| bits | 1 << var);
+            if !seen.insert(bits & mask) {
+                return Err(format!("models: model {:b} is enumerated twice", bits & mask));
+            }
+        }
+        if models.len() != expected {
+            return Err(format!("models: expected {} models, but enumerated {}", expected, models.len()));
+        }
+        Ok(())
+    }
+
+    /// Counter で数えたモデル数が総当たりと一致するか
+    fn check_count(&self) -> Result<(), String> {
+        let mut counter: Counter<BigUint> = Counter::new(self.size_vars);
+        for clause in &self.clauses {
+            counter.add_clause(&mut clause.clone());
+        }
+        if let Some(vars) = &self.projection {
+            counter.set_projection(vars);
+        }
+        let count = counter.count();
+        let expected = BigUint::from_u64(self.brute_force_count() as u64);
+        if count != expected {
+            return Err(format!("count: expected {}, but counted {}", expected, count));
+        }
+        Ok(())
+    }
+
+    /// 論理式と設定を DIMACS 形式で書き出す
+    fn dump(&self, path: &str, message: &str) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        writeln!(w, "c {}", message)?;
+        writeln!(w, "c solve after clause {}, then after all clauses", self.split)?;
+        for assumptions in &self.assumptions {
+            let lits: Vec<String> = assumptions.iter().map(|lit| lit.to_dimacs().to_string()).collect();
+            writeln!(w, "c assumptions: {}", lits.join(" "))?;
+        }
+        if let Some(vars) = &self.projection {
+            let vars: Vec<String> = vars.iter().map(|var| (var + 1).to_string()).collect();
+            writeln!(w, "c projection: {}", vars.join(" "))?;
+        }
+        writeln!(
+            w,
+            "c preprocess={} vivify={} rephase={} track_core={} random_var_freq={} seed={} freeze={}",
+            self.preprocess, self.vivify, self.rephase, self.track_core, self.random_var_freq, self.seed, self.freeze
+        )?;
+        write_dimacs(&mut w, self.size_vars, &self.clauses)?;
+        w.flush()
+    }
+}
+
+fn answer(satisfiable: bool) -> &'static str {
+    if satisfiable { "SAT" } else { "UNSAT" }
+}
+
+/// 比べる機能
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Solve,
+    Models,
+    Count,
+}
+
+impl Mode {
+    fn check(self, case: &Case) -> Result<(), String> {
+        match self {
+            Mode::Solve => case.check(),
+            Mode::Models => case.check_models(),
+            Mode::Count => case.check_count(),
+        }
+    }
+}
+
+fn option_value(options: &HashMap<String, String>, key: &str, default: u64) -> u64 {
+    options.get(key).and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut options: HashMap<String, String> = HashMap::new();
+    for arg in &args[1..] {
+        match arg.strip_prefix("--").and_then(|option| option.split_once('=')) {
+            Some((key, value)) => {
+                options.insert(key.to_string(), value.to_string());
+            },
+            None => {
+                eprintln!("usage: {} [--iterations=N] [--seed=N] [--max-vars=N] [--mode=all|solve|models|count] [--out=<dir>]", args[0]);
+                process::exit(2);
+            },
+        }
+    }
+    let iterations = option_value(&options, "iterations", 10000);
+    let seed = option_value(&options, "seed", 0);
+    // 総当たりするので 20 変数まで
+    let max_vars = option_value(&options, "max-vars", 10).clamp(1, 20) as usize;
+    let out = options.get("out").map_or("fuzz_failures", |out| out.as_str());
+    let modes = match options.get("mode").map(String::as_str) {
+        None | Some("all") => vec![Mode::Solve, Mode::Models, Mode::Count],
+        Some("solve") => vec![Mode::Solve],
+        Some("models") => vec![Mode::Models],
+        Some("count") => vec![Mode::Count],
+        Some(mode) => {
+            eprintln!("unknown mode: {}", mode);
+            process::exit(2);
+        },
+    };
+
+    let mut rng = Random::new(seed);
+    let mut failures = 0;
+    for iteration in 0..iterations {
+        let case = Case::generate(&mut rng, max_vars);
+        // Solver の panic も一致しなかったものとして扱う
+        let result = modes.iter().try_for_each(|mode| {
+            panic::catch_unwind(|| mode.check(&case)).unwrap_or_else(|_| Err("solver panicked".to_string()))
+        });
+        if let Err(message) = result {
+            failures += 1;
+            fs::create_dir_all(out).expect("directory creating error");
+            let path = format!("{}/fuzz-{}-{}.cnf", out, seed, iteration);
+            case.dump(&path, &message).expect("file writing error");
+            println!("c {}: {}", path, message);
+        }
+    }
+
+    println!("c {} iterations, {} failures", iterations, failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
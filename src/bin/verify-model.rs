@@ -0,0 +1,133 @@
+//! solver の出力したモデルが論理式の全ての節を充足するか検査する
+//! モデルは SAT competition の形式 (`v` から始まる行) か,
+//! このソルバーの形式 (`Satisfying solution: x0=1 x1=0 ...`) で読む
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process;
+
+use three_sat_solver_practice_2_h::dimacs_parser::{parse_clause_lines, parse_model, parse_num_vars};
+
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).expect("file reading error")
+}
+
+/// 検査の結果と, 出力する行
+struct Report {
+    verified: bool,
+    lines: Vec<String>,
+}
+
+/// solver_output のモデルが cnf_data の全ての節を充足するか検査する, output_name はメッセージに使う出力の名前
+fn verify(cnf_data: &str, solver_output: &str, output_name: &str) -> Report {
+    let clauses = parse_clause_lines(cnf_data);
+    let size_vars = clauses.iter()
+        .flat_map(|(_, clause)| clause.iter().map(|lit| lit.var() + 1))
+        .chain(parse_num_vars(cnf_data))
+        .max()
+        .unwrap_or(0);
+
+    let model = match parse_model(solver_output, size_vars) {
+        Some(model) => model,
+        None => {
+            return Report {
+                verified: false,
+                lines: vec!["s NOT VERIFIED".to_string(), format!("c no model is found in {}", output_name)],
+            };
+        },
+    };
+
+    // 真のリテラルを含まない節, 割り当てのない変数を含むものは充足していない
+    let unsatisfied: Vec<&(usize, _)> = clauses.iter()
+        .filter(|(_, clause)| !clause.iter().any(|lit| model[lit.var()] == Some(lit.is_pos())))
+        .collect();
+    let Some((line, clause)) = unsatisfied.first() else {
+        return Report {
+            verified: true,
+            lines: vec!["s VERIFIED".to_string(), format!("c all {} clauses are satisfied", clauses.len())],
+        };
+    };
+
+    let lits: Vec<String> = clause.iter().map(|lit| lit.to_dimacs()).chain([0]).map(|lit| lit.to_string()).collect();
+    let unassigned: Vec<String> = clause.iter()
+        .filter(|lit| model[lit.var()].is_none())
+        .map(|lit| (lit.var() + 1).to_string())
+        .collect();
+    let mut lines = vec!["s NOT VERIFIED".to_string()];
+    if unassigned.is_empty() {
+        lines.push(format!("c clause at line {} is falsified: {}", line, lits.join(" ")));
+    } else {
+        lines.push(format!("c clause at line {} is not satisfied: {} (unassigned variables: {})", line, lits.join(" "), unassigned.join(" ")));
+    }
+    lines.push(format!("c {} of {} clauses are not satisfied", unsatisfied.len(), clauses.len()));
+    Report {
+        verified: false,
+        lines,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: {} <input.cnf> <solver output>", args[0]);
+        process::exit(2);
+    }
+
+    let report = verify(&read_file(&args[1]), &read_file(&args[2]), &args[2]);
+
+    // 出力先が先に閉じられても (`| head` など), 終了コードで結果を返す
+    let mut out = io::stdout().lock();
+    for line in &report.lines {
+        match writeln!(out, "{}", line) {
+            Ok(()) => {},
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            Err(e) => panic!("writing error: {}", e),
+        }
+    }
+    process::exit(if report.verified { 0 } else { 1 });
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CNF: &str = "c example\np cnf 3 2\n1 -2 0\n2 3 0\n";
+
+    #[test]
+    fn verify_model_in_competition_format() {
+        let report = verify(CNF, "s SATISFIABLE\nv 1 -2\nv 3 0\n", "out");
+        assert!(report.verified);
+        assert_eq!(report.lines[0], "s VERIFIED");
+    }
+
+    #[test]
+    fn verify_model_in_solver_format() {
+        let report = verify(CNF, "SATISFIABLE\nSatisfying solution: x0=0 x1=0 x2=1 \n", "out");
+        assert!(report.verified);
+    }
+
+    #[test]
+    fn report_falsified_clause_with_line() {
+        let report = verify(CNF, "v -1 2 3 0\n", "out");
+        assert!(!report.verified);
+        assert_eq!(report.lines[0], "s NOT VERIFIED");
+        assert_eq!(report.lines[1], "c clause at line 3 is falsified: 1 -2 0");
+        assert_eq!(report.lines[2], "c 1 of 2 clauses are not satisfied");
+    }
+
+    #[test]
+    fn report_unassigned_variables() {
+        let report = verify(CNF, "v 1 0\n", "out");
+        assert!(!report.verified);
+        assert_eq!(report.lines[1], "c clause at line 4 is not satisfied: 2 3 0 (unassigned variables: 2 3)");
+    }
+
+    #[test]
+    fn report_missing_model() {
+        let report = verify(CNF, "s UNSATISFIABLE\n", "out");
+        assert!(!report.verified);
+        assert_eq!(report.lines[1], "c no model is found in out");
+    }
+}
@@ -11,7 +11,7 @@ pub enum NormalizeError {
 /// * `Ok(Clause)` - トートロジーでない, 重複を削除した, 変数番号で昇順に整列した Clause
 /// * `Err(NormalizeError)` - see NormalizeError
 pub fn normalize_clause(clause: &mut Clause) -> Result<Clause, NormalizeError> {
-    if clause.len() == 0 {
+    if clause.is_empty() {
         return Err(NormalizeError::EmptyClause)
     } else if clause.len() == 1 {
         // 単位節
@@ -36,7 +36,7 @@ pub fn normalize_clause(clause: &mut Clause) -> Result<Clause, NormalizeError> {
         ret_clause.push(*l);
     }
 
-    if ret_clause.len() == 0 {
+    if ret_clause.is_empty() {
         return Err(NormalizeError::EmptyClause)
     }
 
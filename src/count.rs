@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use crate::bigint::BigUint;
+use crate::clause::{Clause, NormalizeError, normalize_clause};
+use crate::literal::Literal;
+use crate::rational::Rational;
+use crate::solver::Clauses;
+
+/// モデル数として数える値
+/// 各モデルの重みは真になるリテラルの重みの積で, その総和を求める
+/// 重みのないモデル数は, 全てのリテラルの重みが 1 の場合になる
+pub trait Weight: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn add(&self, other: &Self) -> Self;
+    fn mul(&self, other: &Self) -> Self;
+}
+
+impl Weight for BigUint {
+    fn zero() -> Self {
+        BigUint::zero()
+    }
+
+    fn one() -> Self {
+        BigUint::one()
+    }
+
+    fn is_zero(&self) -> bool {
+        BigUint::is_zero(self)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// 速いが丸め誤差が出る
+impl Weight for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+
+    fn one() -> Self {
+        1.0
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0.0
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        self * other
+    }
+}
+
+/// 厳密だが遅い
+impl Weight for Rational {
+    fn zero() -> Self {
+        Rational::zero()
+    }
+
+    fn one() -> Self {
+        Rational::one()
+    }
+
+    fn is_zero(&self) -> bool {
+        Rational::is_zero(self)
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Rational::add(self, other)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Rational::mul(self, other)
+    }
+}
+
+/// DPLL によるモデル数の厳密な計算 (#SAT)
+/// 割り当てで論理式が変数を共有しない部分 (component) に分かれたら別々に数えて掛け合わせ,
+/// 数えた component の結果は cache に残して再利用する (sharpSAT などと同じ方針)
+/// W を f64 や Rational にして重みを設定すると, 重み付きモデル数 (WMC) を求める
+pub struct Counter<W: Weight = BigUint> {
+    size_vars: usize,
+    clauses: Clauses,
+    show: Vec<bool>,                    // 数える変数, 射影しない場合は全て true
+    weights: Vec<[W; 2]>,               // weights[var] = [var の重み, ¬var の重み], 既定は 1
+    assigns: Vec<Option<bool>>,
+    trail: Vec<usize>,                  // 割り当てた変数の履歴
+    cache: HashMap<Vec<usize>, W>,      // component (変数と節のインデックス) ごとのモデル数
+    ok: bool,                           // false なら空節を含む
+
+    pub cache_limit: usize,             // cache に残す component の数の上限, 超えたら空にする
+
+    // ログ等
+    pub decisions: usize,
+    pub components: usize,
+    pub cache_hits: usize,
+}
+
+impl<W: Weight> Counter<W> {
+    /// size_vars 個の変数の論理式, 節に現れない変数も数える
+    pub fn new(size_vars: usize) -> Self {
+        Counter {
+            size_vars,
+            clauses: Vec::new(),
+            show: vec![true; size_vars],
+            weights: vec![[W::one(), W::one()]; size_vars],
+            assigns: vec![None; size_vars],
+            trail: Vec::new(),
+            cache: HashMap::new(),
+            ok: true,
+
+            cache_limit: 1_000_000,
+
+            decisions: 0,
+            components: 0,
+            cache_hits: 0,
+        }
+    }
+
+    fn resize(&mut self, size_vars: usize) {
+        if self.size_vars < size_vars {
+            self.size_vars = size_vars;
+            self.show.resize(size_vars, true);
+            self.weights.resize(size_vars, [W::one(), W::one()]);
+            self.assigns.resize(size_vars, None);
+        }
+    }
+
+    pub fn add_clause(&mut self, clause: &mut Clause) {
+        match normalize_clause(clause) {
+            Ok(c) => {
+                self.resize(c[c.len() - 1].var() + 1);
+                self.clauses.push(c);
+            },
+            Err(NormalizeError::TautologyClause) => {},
+            Err(NormalizeError::EmptyClause) => self.ok = false,
+        }
+    }
+
+    /// vars に射影したモデル数を数えるようにする
+    /// vars 上の割り当てのうち, 残りの変数の割り当てで論理式を充足できるものの数になる
+    pub fn set_projection(&mut self, vars: &[usize]) {
+        if let Some(&max_var) = vars.iter().max() {
+            self.resize(max_var + 1);
+        }
+        self.show.fill(false);
+        for &var in vars {
+            self.show[var] = true;
+        }
+    }
+
+    /// lit が真になるモデルの重みに lit の重みを掛ける
+    pub fn set_weight(&mut self, lit: Literal, weight: W) {
+        self.resize(lit.var() + 1);
+        self.weights[lit.var()][if lit.is_pos() { 0 } else { 1 }] = weight;
+    }
+
+    /// 射影で数えない変数の重みは 1 とする
+    fn weight(&self, lit: Literal) -> W {
+        if self.show[lit.var()] {
+            self.weights[lit.var()][if lit.is_pos() { 0 } else { 1 }].clone()
+        } else {
+            W::one()
+        }
+    }
+
+    fn value(&self, lit: &Literal) -> Option<bool> {
+        self.assigns[lit.var()].map(|value| value == lit.is_pos())
+    }
+
+    fn is_satisfied(&self, i: usize) -> bool {
+        self.clauses[i].iter().any(|lit| self.value(lit) == Some(true))
+    }
+
+    fn assign(&mut self, lit: Literal) {
+        self.assigns[lit.var()] = Some(lit.is_pos());
+        self.trail.push(lit.var());
+    }
+
+    fn undo(&mut self, mark: usize) {
+        for &var in &self.trail[mark..] {
+            self.assigns[var] = None;
+        }
+        self.trail.truncate(mark);
+    }
+
+    /// clauses の中で単位伝播する
+    /// # Returns
+    /// * `true` - 矛盾した
+    /// * `false` - 矛盾しなかった
+    fn propagate(&mut self, clauses: &[usize]) -> bool {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &i in clauses {
+                if self.is_satisfied(i) {
+                    continue;
+                }
+                let mut unassigned = self.clauses[i].iter().filter(|lit| self.value(lit).is_none());
+                match (unassigned.next(), unassigned.next()) {
+                    (None, _) => return true,
+                    (Some(&lit), None) => {
+                        self.assign(lit);
+                        changed = true;
+                    },
+                    _ => {},
+                }
+            }
+        }
+        false
+    }
+
+    /// 未充足の節を, 未割り当ての変数を共有するもの同士でまとめる
+    /// # Returns
+    /// * (変数, 節のインデックス) の組の一覧, どちらも昇順
+    fn decompose(&self, clauses: &[usize]) -> Vec<(Vec<usize>, Vec<usize>)> {
+        fn find(parent: &mut HashMap<usize, usize>, var: usize) -> usize {
+            let mut root = var;
+            while parent[&root] != root {
+                root = parent[&root];
+            }
+            let mut cur = var;
+            while parent[&cur] != root {
+                let next = parent[&cur];
+                parent.insert(cur, root);
+                cur = next;
+            }
+            root
+        }
+
+        let mut parent: HashMap<usize, usize> = HashMap::new();
+        for &i in clauses {
+            let mut vars = self.clauses[i].iter()
+                .filter(|lit| self.value(lit).is_none())
+                .map(|lit| lit.var());
+            let first = vars.next().unwrap();
+            parent.entry(first).or_insert(first);
+            let first_root = find(&mut parent, first);
+            for var in vars {
+                parent.entry(var).or_insert(var);
+                let root = find(&mut parent, var);
+                if root != first_root {
+                    parent.insert(root, first_root);
+                }
+            }
+        }
+
+        let mut components: HashMap<usize, (Vec<usize>, Vec<usize>)> = HashMap::new();
+        let vars: Vec<usize> = parent.keys().copied().collect();
+        for var in vars {
+            let root = find(&mut parent, var);
+            components.entry(root).or_default().0.push(var);
+        }
+        for &i in clauses {
+            let var = self.clauses[i].iter().find(|lit| self.value(lit).is_none()).unwrap().var();
+            let root = find(&mut parent, var);
+            components.get_mut(&root).unwrap().1.push(i);
+        }
+        let mut components: Vec<(Vec<usize>, Vec<usize>)> = components.into_values().collect();
+        for (vars, clauses) in components.iter_mut() {
+            vars.sort_unstable();
+            clauses.sort_unstable();
+        }
+        // 数える順番を毎回同じにする
+        components.sort_unstable();
+        components
+    }
+
+    /// vars と clauses からなる部分の, 現在の割り当ての下でのモデル数
+    fn count_formula(&mut self, vars: &[usize], clauses: &[usize]) -> W {
+        let mark = self.trail.len();
+        if self.propagate(clauses) {
+            self.undo(mark);
+            return W::zero();
+        }
+
+        // 伝播で決まった変数の重み
+        let mut result = W::one();
+        for &var in &self.trail[mark..] {
+            let lit = if self.assigns[var] == Some(true) { Literal::Pos(var) } else { Literal::Neg(var) };
+            result = result.mul(&self.weight(lit));
+        }
+
+        let open: Vec<usize> = clauses.iter().copied().filter(|&i| !self.is_satisfied(i)).collect();
+        let components = self.decompose(&open);
+
+        // どの未充足の節にも現れない変数はどちらの真偽値でもよい
+        let mut occurring = vec![false; vars.len()];
+        for (component_vars, _) in &components {
+            for var in component_vars {
+                if let Ok(k) = vars.binary_search(var) {
+                    occurring[k] = true;
+                }
+            }
+        }
+        for (k, &var) in vars.iter().enumerate() {
+            if !occurring[k] && self.assigns[var].is_none() && self.show[var] {
+                let both = self.weight(Literal::Pos(var)).add(&self.weight(Literal::Neg(var)));
+                result = result.mul(&both);
+            }
+        }
+
+        for (component_vars, component_clauses) in components {
+            let count = self.count_cached(component_vars, component_clauses);
+            if count.is_zero() {
+                self.undo(mark);
+                return W::zero();
+            }
+            result = result.mul(&count);
+        }
+        self.undo(mark);
+        result
+    }
+
+    fn count_cached(&mut self, vars: Vec<usize>, clauses: Vec<usize>) -> W {
+        self.components += 1;
+        let mut key = vars.clone();
+        key.push(usize::MAX);
+        key.extend_from_slice(&clauses);
+        if let Some(count) = self.cache.get(&key) {
+            self.cache_hits += 1;
+            return count.clone();
+        }
+
+        let count = self.count_component(&vars, &clauses);
+        if self.cache.len() >= self.cache_limit {
+            self.cache.clear();
+        }
+        self.cache.insert(key, count.clone());
+        count
+    }
+
+    /// 1つの component のモデル数を, 最も多くの節に現れる変数で場合分けして数える
+    /// 数える変数を含まない component は, 充足できるか (1) できないか (0) だけを調べる
+    fn count_component(&mut self, vars: &[usize], clauses: &[usize]) -> W {
+        let projected = vars.iter().any(|&var| self.show[var]);
+        let mut occurs: HashMap<usize, usize> = HashMap::new();
+        for &i in clauses {
+            for lit in &self.clauses[i] {
+                if self.value(lit).is_none() && (!projected || self.show[lit.var()]) {
+                    *occurs.entry(lit.var()).or_default() += 1;
+                }
+            }
+        }
+        let var = occurs.into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .unwrap()
+            .0;
+
+        let mut total = W::zero();
+        for lit in [Literal::Pos(var), Literal::Neg(var)] {
+            self.decisions += 1;
+            let mark = self.trail.len();
+            self.assign(lit);
+            let count = self.count_formula(vars, clauses);
+            self.undo(mark);
+            if !projected && !count.is_zero() {
+                return W::one();
+            }
+            total = total.add(&self.weight(lit).mul(&count));
+        }
+        total
+    }
+
+    /// モデル数を数える, set_projection した場合は射影したモデル数, set_weight した場合は重み付きモデル数
+    pub fn count(&mut self) -> W {
+        if !self.ok {
+            return W::zero();
+        }
+        info!("count: {} vars, {} clauses", self.size_vars, self.clauses.len());
+        let vars: Vec<usize> = (0..self.size_vars).collect();
+        let clauses: Vec<usize> = (0..self.clauses.len()).collect();
+        let count = self.count_formula(&vars, &clauses);
+        info!("count: {} decisions, {} components, {} cache hits", self.decisions, self.components, self.cache_hits);
+        count
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, random_clauses};
+
+    fn counter_from(size_vars: usize, clauses: &Clauses) -> Counter {
+        let mut counter = Counter::new(size_vars);
+        for clause in clauses {
+            counter.add_clause(&mut clause.clone());
+        }
+        counter
+    }
+
+    /// 長さ 1 から 3 の節を混ぜたランダムな論理式
+    fn random_formula(rng: &mut Random, size_vars: usize) -> Clauses {
+        let size_clauses = rng.gen_range(size_vars * 3);
+        (0..size_clauses)
+            .flat_map(|_| {
+                let k = 1 + rng.gen_range(3);
+                random_clauses(rng, size_vars, 1, k)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn count_agrees_with_brute_force() {
+        let mut rng = Random::new(6);
+        for _ in 0..300 {
+            let size_vars = 1 + rng.gen_range(12);
+            let clauses = random_formula(&mut rng, size_vars);
+            let expected = brute_force(size_vars, &clauses, &[]).len() as u64;
+            assert_eq!(counter_from(size_vars, &clauses).count(), BigUint::from_u64(expected), "{:?}", clauses);
+        }
+    }
+
+    #[test]
+    fn count_unused_vars() {
+        // 10 変数のうち節に現れるのは 6 変数まで, 現れない変数の分だけ 2 倍になる
+        let mut rng = Random::new(7);
+        for _ in 0..100 {
+            let clauses = random_formula(&mut rng, 6);
+            let expected = brute_force(10, &clauses, &[]).len() as u64;
+            assert_eq!(counter_from(10, &clauses).count(), BigUint::from_u64(expected), "{:?}", clauses);
+        }
+    }
+
+    #[test]
+    fn count_projected_models() {
+        let mut rng = Random::new(8);
+        for _ in 0..300 {
+            let size_vars = 1 + rng.gen_range(12);
+            let clauses = random_formula(&mut rng, size_vars);
+            let show: Vec<usize> = (0..size_vars).filter(|_| rng.gen_bool(0.5)).collect();
+            let mask = show.iter().fold(0u64, |mask, &var| mask | 1 << var);
+            let mut projected: Vec<u64> = brute_force(size_vars, &clauses, &[]).into_iter().map(|bits| bits & mask).collect();
+            projected.sort_unstable();
+            projected.dedup();
+
+            let mut counter = counter_from(size_vars, &clauses);
+            counter.set_projection(&show);
+            assert_eq!(counter.count(), BigUint::from_u64(projected.len() as u64), "{:?} {:?}", clauses, show);
+        }
+    }
+
+    #[test]
+    fn count_without_cache() {
+        let mut rng = Random::new(9);
+        for _ in 0..100 {
+            let size_vars = 1 + rng.gen_range(14);
+            let clauses = random_formula(&mut rng, size_vars);
+            let expected = brute_force(size_vars, &clauses, &[]).len() as u64;
+            let mut counter = counter_from(size_vars, &clauses);
+            counter.cache_limit = 0;
+            assert_eq!(counter.count(), BigUint::from_u64(expected), "{:?}", clauses);
+            assert!(counter.cache.len() <= 1);
+        }
+    }
+
+    #[test]
+    fn weighted_count_by_hand() {
+        // (x1 ∨ x2), x3 は節に現れない
+        // w(x1) = 1/3, w(¬x1) = 2/3, w(x2) = 1/4, w(¬x2) = 3/4, w(x3) = 2, w(¬x3) = 5
+        // (1/3 * 1/4 + 1/3 * 3/4 + 2/3 * 1/4) * (2 + 5) = 1/2 * 7 = 7/2
+        let ratio = |num: u64, den: u64| Rational::new(BigUint::from_u64(num), BigUint::from_u64(den));
+        let mut counter: Counter<Rational> = Counter::new(3);
+        counter.add_clause(&mut vec![Literal::Pos(0), Literal::Pos(1)]);
+        for (var, pos, neg) in [(0, ratio(1, 3), ratio(2, 3)), (1, ratio(1, 4), ratio(3, 4)), (2, ratio(2, 1), ratio(5, 1))] {
+            counter.set_weight(Literal::Pos(var), pos);
+            counter.set_weight(Literal::Neg(var), neg);
+        }
+        assert_eq!(counter.count(), ratio(7, 2));
+    }
+
+    #[test]
+    fn weighted_count_f64_agrees_with_rational() {
+        let mut rng = Random::new(10);
+        for _ in 0..100 {
+            let size_vars = 1 + rng.gen_range(10);
+            let clauses = random_formula(&mut rng, size_vars);
+            let mut exact: Counter<Rational> = Counter::new(size_vars);
+            let mut approx: Counter<f64> = Counter::new(size_vars);
+            for clause in &clauses {
+                exact.add_clause(&mut clause.clone());
+                approx.add_clause(&mut clause.clone());
+            }
+            // 重みは 1/8 単位, 総和を総当たりでも求める
+            let mut weights = Vec::new();
+            for var in 0..size_vars {
+                let pos = rng.gen_range(9) as u64;
+                let neg = rng.gen_range(9) as u64;
+                for (lit, weight) in [(Literal::Pos(var), pos), (Literal::Neg(var), neg)] {
+                    exact.set_weight(lit, Rational::new(BigUint::from_u64(weight), BigUint::from_u64(8)));
+                    approx.set_weight(lit, weight as f64 / 8.0);
+                }
+                weights.push((pos, neg));
+            }
+            let expected: f64 = brute_force(size_vars, &clauses, &[]).into_iter()
+                .map(|bits| (0..size_vars).map(|var| {
+                    let (pos, neg) = weights[var];
+                    if (bits >> var) & 1 == 1 { pos as f64 / 8.0 } else { neg as f64 / 8.0 }
+                }).product::<f64>())
+                .sum();
+
+            let exact = exact.count().to_f64();
+            let approx = approx.count();
+            assert!((exact - expected).abs() <= 1e-9 * expected.max(1.0), "{} {}", exact, expected);
+            assert!((approx - exact).abs() <= 1e-9 * exact.max(1.0), "{} {}", approx, exact);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use log::info;
+
+use crate::literal::Literal;
+use crate::solver::{Clauses, Solver};
+
+/// cube-and-conquer の conquer の段階
+/// 各 cube を仮定にして Solver で解く, threads 個のスレッドで cube を分担する
+/// 1つの cube で解が見つかれば他のスレッドは打ち切る
+/// # Returns
+/// * `Some(model)` - いずれかの cube の下で SAT, 元の論理式のモデル
+/// * `None` - 全ての cube の下で UNSAT
+pub fn conquer(clauses: &Clauses, cubes: &[Vec<Literal>], threads: usize) -> Option<Vec<Option<bool>>> {
+    let next_cube = AtomicUsize::new(0);
+    let found = Arc::new(AtomicBool::new(false));
+    let model: Mutex<Option<Vec<Option<bool>>>> = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for id in 0..threads.max(1) {
+            let next_cube = &next_cube;
+            let found = &found;
+            let model = &model;
+            scope.spawn(move || {
+                let mut solver = Solver::new();
+                solver.interrupt = Arc::clone(found);
+                for cube in cubes {
+                    for lit in cube {
+                        solver.freeze(lit.var());
+                    }
+                }
+                for clause in clauses {
+                    if !solver.add_clause(&mut clause.clone()) {
+                        return;
+                    }
+                }
+
+                loop {
+                    let i = next_cube.fetch_add(1, Ordering::Relaxed);
+                    if i >= cubes.len() || found.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    match solver.solve_with_assumptions(&cubes[i]) {
+                        Some(true) => {
+                            info!("thread {}: cube {} is SATISFIABLE", id, i);
+                            found.store(true, Ordering::Relaxed);
+                            *model.lock().unwrap() = Some(solver.model.clone());
+                        },
+                        Some(false) => {
+                            info!("thread {}: cube {} is UNSATISFIABLE", id, i);
+                        },
+                        None => break,
+                    }
+                }
+            });
+        }
+    });
+
+    model.into_inner().unwrap()
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, is_model, pigeonhole, random_clauses};
+
+    fn cubes_of(clauses: &Clauses, depth: usize) -> Vec<Vec<Literal>> {
+        let mut solver = Solver::new();
+        for clause in clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        solver.cube(depth, 0)
+    }
+
+    /// cube を仮定にして, 元の論理式を解く
+    fn solve_under(clauses: &Clauses, cube: &[Literal]) -> Option<bool> {
+        let mut solver = Solver::new();
+        for clause in clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        solver.solve_with_assumptions(cube)
+    }
+
+    #[test]
+    fn every_cube_of_unsat_formula_is_unsat() {
+        let clauses = pigeonhole(6, 5);
+        let cubes = cubes_of(&clauses, 3);
+        assert!(cubes.len() > 1);
+        for cube in &cubes {
+            assert_eq!(solve_under(&clauses, cube), Some(false));
+        }
+        assert_eq!(conquer(&clauses, &cubes, 2), None);
+    }
+
+    #[test]
+    fn conquer_agrees_with_brute_force() {
+        let mut rng = Random::new(3);
+        for _ in 0..100 {
+            let size_vars = 4 + rng.gen_range(9);
+            let size_clauses = size_vars * 3 + rng.gen_range(size_vars * 3);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            let cubes = cubes_of(&clauses, 1 + rng.gen_range(4));
+            match conquer(&clauses, &cubes, 1 + rng.gen_range(2)) {
+                Some(model) => assert!(is_model(&clauses, &model), "{:?} {:?}", clauses, cubes),
+                None => {
+                    assert!(brute_force(size_vars, &clauses, &[]).is_empty(), "{:?} {:?}", clauses, cubes);
+                    for cube in &cubes {
+                        assert_eq!(solve_under(&clauses, cube), Some(false));
+                    }
+                },
+            }
+        }
+    }
+}
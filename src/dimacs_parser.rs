@@ -1,6 +1,6 @@
 use crate::literal::Literal;
 use crate::clause::Clause;
-use crate::solver::Solver;
+use crate::solver::{Clauses, Solver};
 
 fn is_comment_line(line: &str) -> bool {
     for c in line.chars() {
@@ -31,13 +31,7 @@ fn line_to_clause(line: &str) -> Clause {
         if lit_raw_data == 0 {
             break;
         }
-        let lit_var_n: i32 = lit_raw_data.abs();
-        let lit_var_n: usize = (lit_var_n - 1).try_into().unwrap();
-        if lit_raw_data > 0 {
-            clause.push(Literal::Pos(lit_var_n));
-        } else {
-            clause.push(Literal::Neg(lit_var_n));
-        }
+        clause.push(Literal::from_dimacs(lit_raw_data));
     }
     clause
 }
@@ -55,7 +49,7 @@ pub fn parse_dimacs(cnf_data: &mut str, solver: &mut Solver) -> bool {
         if is_comment_line(line) || is_probrem_line(line) {
             continue;
         }
-        if line.len() == 0 {
+        if line.is_empty() {
             continue;
         }
 
@@ -64,5 +58,116 @@ pub fn parse_dimacs(cnf_data: &mut str, solver: &mut Solver) -> bool {
             return false;
         }
     }
-    return true;
-}
\ No newline at end of file
+    true
+}
+
+/// 問題行 `p cnf <変数の数> <節の数>` から変数の数を読む
+pub fn parse_num_vars(cnf_data: &str) -> Option<usize> {
+    let line = cnf_data.lines().find(|line| is_probrem_line(line))?;
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("p") || tokens.next() != Some("cnf") {
+        return None;
+    }
+    tokens.next()?.parse().ok()
+}
+
+/// 節をそのまま読み込む, Solver と違って単位節による簡略化などは行わない
+pub fn parse_clauses(cnf_data: &str) -> Clauses {
+    cnf_data.lines()
+        .filter(|line| !is_comment_line(line) && !is_probrem_line(line) && !line.trim().is_empty())
+        .map(line_to_clause)
+        .collect()
+}
+
+/// 節とその行番号 (1 始まり) を読む, 節の順は parse_clauses と同じ
+pub fn parse_clause_lines(cnf_data: &str) -> Vec<(usize, Clause)> {
+    cnf_data.lines()
+        .enumerate()
+        .filter(|(_, line)| !is_comment_line(line) && !is_probrem_line(line) && !line.trim().is_empty())
+        .map(|(n, line)| (n + 1, line_to_clause(line)))
+        .collect()
+}
+
+/// 射影する変数を指定する `c p show <変数...> 0` の行を読む (MC competition の形式)
+/// # Returns
+/// * `Some(vars)` - 指定された変数 (0 始まり)
+/// * `None` - `c p show` の行がない
+pub fn parse_show_vars(cnf_data: &str) -> Option<Vec<usize>> {
+    let mut vars: Option<Vec<usize>> = None;
+    for line in cnf_data.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("c") || tokens.next() != Some("p") || tokens.next() != Some("show") {
+            continue;
+        }
+        let vars = vars.get_or_insert_with(Vec::new);
+        for token in tokens {
+            match token.parse::<i32>() {
+                Ok(0) | Err(_) => break,
+                Ok(var) => vars.push(Literal::from_dimacs(var.abs()).var()),
+            }
+        }
+    }
+    vars
+}
+
+/// リテラルの重みを指定する `c p weight <リテラル> <重み> 0` の行を読む (MC competition の形式)
+/// 重みは文字列のまま返すので, 呼び出し側で必要な精度の数に直す
+pub fn parse_weights(cnf_data: &str) -> Vec<(Literal, String)> {
+    let mut weights = Vec::new();
+    for line in cnf_data.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 5 || tokens[..3] != ["c", "p", "weight"] {
+            continue;
+        }
+        if let Ok(lit) = tokens[3].parse::<i32>() {
+            if lit != 0 {
+                weights.push((Literal::from_dimacs(lit), tokens[4].to_string()));
+            }
+        }
+    }
+    weights
+}
+
+/// solver の出力からモデルを読み込む
+/// SAT competition の形式 (`v` から始まる行) と,
+/// このソルバーの形式 (`Satisfying solution: x0=1 x1=0 ...`) に対応する
+/// # Returns
+/// * `Some(model)` - 読み込み成功, 出力に現れなかった変数は None
+/// * `None` - モデルが見つからない
+pub fn parse_model(output: &str, size_vars: usize) -> Option<Vec<Option<bool>>> {
+    let mut model: Vec<Option<bool>> = vec![None; size_vars];
+    let mut found = false;
+
+    for line in output.lines() {
+        let line = line.trim();
+        let mut tokens = line.split_whitespace();
+        if tokens.next() == Some("v") {
+            for value in tokens {
+                let value: i32 = value.parse().ok()?;
+                if value == 0 {
+                    continue;
+                }
+                let lit = Literal::from_dimacs(value);
+                if lit.var() < size_vars {
+                    model[lit.var()] = Some(lit.is_pos());
+                }
+                found = true;
+            }
+        } else if let Some(assigns) = line.strip_prefix("Satisfying solution:") {
+            for assign in assigns.split_whitespace() {
+                let (var, value) = assign.strip_prefix('x')?.split_once('=')?;
+                let var: usize = var.parse().ok()?;
+                if var < size_vars {
+                    model[var] = Some(value == "1");
+                }
+                found = true;
+            }
+        }
+    }
+
+    if found {
+        Some(model)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{self, Write};
+
+use crate::literal::Literal;
+use crate::solver::Clauses;
+
+/// DIMACS 形式で論理式を書き出す
+pub fn write_dimacs<W: Write>(w: &mut W, size_vars: usize, clauses: &Clauses) -> io::Result<()> {
+    writeln!(w, "p cnf {} {}", size_vars, clauses.len())?;
+    for clause in clauses {
+        for lit in clause {
+            write!(w, "{} ", lit.to_dimacs())?;
+        }
+        writeln!(w, "0")?;
+    }
+    Ok(())
+}
+
+/// iCNF 形式で論理式と cube を書き出す
+/// 各 cube は `a` から始まる仮定の行になる
+pub fn write_icnf<W: Write>(w: &mut W, clauses: &Clauses, cubes: &[Vec<Literal>]) -> io::Result<()> {
+    writeln!(w, "p inccnf")?;
+    for clause in clauses {
+        for lit in clause {
+            write!(w, "{} ", lit.to_dimacs())?;
+        }
+        writeln!(w, "0")?;
+    }
+    for cube in cubes {
+        write!(w, "a ")?;
+        for lit in cube {
+            write!(w, "{} ", lit.to_dimacs())?;
+        }
+        writeln!(w, "0")?;
+    }
+    Ok(())
+}
+
+/// SAT competition の形式 (`v` から始まる行) でモデルを書き出す
+/// 未割り当ての変数は false として扱う
+pub fn write_model<W: Write>(w: &mut W, model: &[Option<bool>]) -> io::Result<()> {
+    write!(w, "v")?;
+    for (i, assign) in model.iter().enumerate() {
+        let var: i64 = (i + 1).try_into().unwrap();
+        if assign.unwrap_or(false) {
+            write!(w, " {}", var)?;
+        } else {
+            write!(w, " {}", -var)?;
+        }
+    }
+    writeln!(w, " 0")
+}
+
+/// vars の変数だけを `v` から始まる行で書き出す
+/// 未割り当ての変数は false として扱う
+pub fn write_projected_model<W: Write>(w: &mut W, model: &[Option<bool>], vars: &[usize]) -> io::Result<()> {
+    write!(w, "v")?;
+    for &var in vars {
+        let lit = if model.get(var).copied().flatten().unwrap_or(false) { Literal::Pos(var) } else { Literal::Neg(var) };
+        write!(w, " {}", lit.to_dimacs())?;
+    }
+    writeln!(w, " 0")
+}
+
+/// clauses のうち indices の節だけを DIMACS 形式で書き出す
+/// 各節の前に, 元の論理式での節の番号 (1 始まり) を `c clause <番号>` のコメントとして書く
+pub fn write_dimacs_subset<W: Write>(w: &mut W, size_vars: usize, clauses: &Clauses, indices: &[usize]) -> io::Result<()> {
+    writeln!(w, "p cnf {} {}", size_vars, indices.len())?;
+    for &i in indices {
+        writeln!(w, "c clause {}", i + 1)?;
+        for lit in &clauses[i] {
+            write!(w, "{} ", lit.to_dimacs())?;
+        }
+        writeln!(w, "0")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,511 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Clauses;
+
+/// DRAT 形式の証明の1行
+struct ProofLine {
+    line: usize,        // テキスト形式なら行番号, バイナリ形式なら何番目の節か (1 始まり)
+    delete: bool,       // 節の削除か
+    lits: Clause,
+}
+
+/// 前向きに読んだ証明の1つの手順
+struct Step {
+    clause: usize,      // 加えた, または削除した節の番号
+    delete: bool,
+    line: usize,        // 証明の何番目の ProofLine か
+    trail: usize,       // 手順の前の trail の長さ
+}
+
+/// リテラルを watches の添字にする
+fn index(lit: Literal) -> usize {
+    lit.var() * 2 + !lit.is_pos() as usize
+}
+
+/// 証明を読む, NUL を含めばバイナリ形式, そうでなければテキスト形式とする
+fn parse_proof(proof: &[u8]) -> Result<Vec<ProofLine>, String> {
+    if proof.contains(&0) {
+        parse_binary(proof)
+    } else {
+        parse_text(&String::from_utf8_lossy(proof))
+    }
+}
+
+/// テキスト形式: 1行に1つの節 `<リテラル> 0`, 削除は `d <リテラル> 0`, `c` から始まる行はコメント
+fn parse_text(proof: &str) -> Result<Vec<ProofLine>, String> {
+    let mut lines = Vec::new();
+    for (n, line) in proof.lines().enumerate() {
+        let mut tokens = line.split_whitespace().peekable();
+        match tokens.peek() {
+            None => continue,
+            Some(token) if token.starts_with('c') => continue,
+            _ => {},
+        }
+        let delete = tokens.next_if_eq(&"d").is_some();
+        let mut lits = Vec::new();
+        loop {
+            let token = tokens.next().ok_or_else(|| format!("line {}: missing 0", n + 1))?;
+            match token.parse::<i32>() {
+                Ok(0) => break,
+                Ok(lit) => lits.push(Literal::from_dimacs(lit)),
+                Err(_) => return Err(format!("line {}: invalid literal: {}", n + 1, token)),
+            }
+        }
+        lines.push(ProofLine { line: n + 1, delete, lits });
+    }
+    Ok(lines)
+}
+
+/// バイナリ形式: 追加は `a`, 削除は `d` に続いて, リテラル 2 * 変数 + 否定 を
+/// 7 bit ずつ下位から可変長で並べ, 0 で終わる
+fn parse_binary(proof: &[u8]) -> Result<Vec<ProofLine>, String> {
+    let mut lines = Vec::new();
+    let mut bytes = proof.iter();
+    while let Some(&head) = bytes.next() {
+        let n = lines.len() + 1;
+        let delete = match head {
+            b'a' => false,
+            b'd' => true,
+            _ => return Err(format!("clause {}: invalid byte: {:#04x}", n, head)),
+        };
+        let mut lits = Vec::new();
+        loop {
+            let mut value: u64 = 0;
+            let mut shift = 0;
+            loop {
+                let &byte = bytes.next().ok_or_else(|| format!("clause {}: missing 0", n))?;
+                if shift > 56 {
+                    return Err(format!("clause {}: too large literal", n));
+                }
+                value |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            if value == 0 {
+                break;
+            }
+            let var = i32::try_from(value >> 1).map_err(|_| format!("clause {}: too large literal", n))?;
+            if var == 0 {
+                return Err(format!("clause {}: invalid literal", n));
+            }
+            lits.push(Literal::from_dimacs(if value & 1 == 1 { -var } else { var }));
+        }
+        lines.push(ProofLine { line: n, delete, lits });
+    }
+    Ok(lines)
+}
+
+/// 根での単位伝播と, 導いた節の後ろ向きの検査を行う
+/// 節の先頭2つのリテラルを監視する (2 watched literals)
+struct Checker {
+    clauses: Clauses,               // 元の節と導いた節, 重複したリテラルは除く
+    active: Vec<bool>,              // 現在の論理式に含まれるか
+    core: Vec<bool>,                // 矛盾を導くのに使ったか, 検査するのはこれが true の導いた節だけ
+    watches: Vec<Vec<usize>>,       // リテラルを監視している節
+    values: Vec<Option<bool>>,
+    reasons: Vec<Option<usize>>,    // 変数を単位伝播した節
+    positions: Vec<usize>,          // 変数の trail 上の位置
+    trail: Vec<Literal>,
+    core_head: usize,               // core の節で伝播済みの trail の位置
+    head: usize,                    // 全ての節で伝播済みの trail の位置
+    seen: Vec<bool>,
+}
+
+impl Checker {
+    fn value(&self, lit: Literal) -> Option<bool> {
+        self.values[lit.var()].map(|value| value == lit.is_pos())
+    }
+
+    fn assign(&mut self, lit: Literal, reason: Option<usize>) {
+        self.values[lit.var()] = Some(lit.is_pos());
+        self.reasons[lit.var()] = reason;
+        self.positions[lit.var()] = self.trail.len();
+        self.trail.push(lit);
+    }
+
+    fn backtrack(&mut self, len: usize) {
+        for lit in self.trail.drain(len..) {
+            self.values[lit.var()] = None;
+            self.reasons[lit.var()] = None;
+        }
+        self.core_head = self.core_head.min(len);
+        self.head = self.head.min(len);
+    }
+
+    /// 節を加え, 現在の割り当てで単位節なら伝播する
+    /// # Returns
+    /// * `false` - 全てのリテラルが偽
+    fn attach(&mut self, mut clause: Clause) -> bool {
+        let ci = self.clauses.len();
+        // 重複したリテラルを除く, 先頭のリテラル (RAT の pivot) の順は保つ
+        let mut unique: Clause = Vec::with_capacity(clause.len());
+        clause.retain(|lit| {
+            let first = !unique.contains(lit);
+            unique.push(*lit);
+            first
+        });
+        if let Some(size) = clause.iter().map(|lit| lit.var() + 1).max() {
+            if self.values.len() < size {
+                self.values.resize(size, None);
+                self.reasons.resize(size, None);
+                self.positions.resize(size, 0);
+                self.seen.resize(size, false);
+                self.watches.resize(size * 2, Vec::new());
+            }
+        }
+
+        // 偽でないリテラルを前に, 偽のリテラルは後で割り当てたものほど前に置く
+        // 監視するリテラルが偽なら, もう一方が後で偽になるか真であるようにする
+        clause.sort_by_key(|lit| match self.value(*lit) {
+            Some(false) => usize::MAX - self.positions[lit.var()],
+            _ => 0,
+        });
+
+        let result = match clause.first() {
+            None => false,
+            Some(&lit) => match self.value(lit) {
+                Some(false) => false,
+                Some(true) => true,
+                None => {
+                    if clause.get(1).is_none_or(|&second| self.value(second) == Some(false)) {
+                        self.assign(lit, Some(ci));
+                    }
+                    true
+                },
+            },
+        };
+        if clause.len() >= 2 {
+            self.watches[index(clause[0])].push(ci);
+            self.watches[index(clause[1])].push(ci);
+        }
+        self.clauses.push(clause);
+        self.active.push(true);
+        self.core.push(false);
+        result
+    }
+
+    /// core の節での単位伝播を優先して, 全ての節で単位伝播する
+    /// # Returns
+    /// * `Some(ci)` - 全てのリテラルが偽になった節
+    fn propagate(&mut self) -> Option<usize> {
+        loop {
+            if self.core_head < self.trail.len() {
+                let lit = self.trail[self.core_head];
+                self.core_head += 1;
+                if let Some(ci) = self.propagate_lit(lit, true) {
+                    return Some(ci);
+                }
+            } else if self.head < self.trail.len() {
+                let lit = self.trail[self.head];
+                self.head += 1;
+                if let Some(ci) = self.propagate_lit(lit, false) {
+                    return Some(ci);
+                }
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /// lit が真になったので, ¬lit を監視している節のうち core かどうかが引数の core と同じものを調べる
+    fn propagate_lit(&mut self, lit: Literal, core: bool) -> Option<usize> {
+        let falsified = lit.not();
+        let mut watches = std::mem::take(&mut self.watches[index(falsified)]);
+        let mut conflict = None;
+        let mut j = 0;
+        for i in 0..watches.len() {
+            let ci = watches[i];
+            if conflict.is_some() || !self.active[ci] || self.core[ci] != core {
+                watches[j] = ci;
+                j += 1;
+                continue;
+            }
+            let clause = &mut self.clauses[ci];
+            if clause[0] == falsified {
+                clause.swap(0, 1);
+            }
+            let first = clause[0];
+            if self.values[first.var()] == Some(first.is_pos()) {
+                watches[j] = ci;
+                j += 1;
+                continue;
+            }
+            let values = &self.values;
+            let next = clause[2..].iter().position(|lit| values[lit.var()] != Some(!lit.is_pos()));
+            if let Some(k) = next {
+                clause.swap(1, k + 2);
+                self.watches[index(clause[1])].push(ci);
+                continue;
+            }
+            watches[j] = ci;
+            j += 1;
+            if self.values[first.var()].is_some() {
+                conflict = Some(ci);
+            } else {
+                self.assign(first, Some(ci));
+            }
+        }
+        watches.truncate(j);
+        self.watches[index(falsified)] = watches;
+        conflict
+    }
+
+    /// vars の割り当ての理由を逆にたどり, 使った節を core にする
+    fn analyze(&mut self, mut stack: Vec<usize>) {
+        let mut visited = Vec::new();
+        while let Some(var) = stack.pop() {
+            if self.seen[var] {
+                continue;
+            }
+            self.seen[var] = true;
+            visited.push(var);
+            if let Some(ci) = self.reasons[var] {
+                self.core[ci] = true;
+                stack.extend(self.clauses[ci].iter().map(|lit| lit.var()).filter(|&v| v != var));
+            }
+        }
+        for var in visited {
+            self.seen[var] = false;
+        }
+    }
+
+    /// 現在の論理式から lits が単位伝播で導けるか (RUP) 調べ, 導けるなら使った節を core にする
+    fn is_rup(&mut self, lits: &[Literal]) -> bool {
+        let root = self.trail.len();
+        let mut result = None;
+        for &lit in lits {
+            match self.value(lit) {
+                Some(true) => {
+                    result = Some(vec![lit.var()]);
+                    break;
+                },
+                Some(false) => {},
+                None => self.assign(lit.not(), None),
+            }
+        }
+        if result.is_none() {
+            if let Some(ci) = self.propagate() {
+                self.core[ci] = true;
+                result = Some(self.clauses[ci].iter().map(|lit| lit.var()).collect());
+            }
+        }
+        if let Some(vars) = &result {
+            self.analyze(vars.clone());
+        }
+        self.backtrack(root);
+        result.is_some()
+    }
+
+    /// lemma[0] を pivot として RAT か調べる
+    /// ¬pivot を含む全ての節 D について lemma ∪ (D \ {¬pivot}) が RUP なら, D も core にする
+    fn is_rat(&mut self, lemma: usize, pivot: Literal) -> bool {
+        let candidates: Vec<usize> = (0..self.clauses.len())
+            .filter(|&ci| ci != lemma && self.active[ci] && self.clauses[ci].contains(&pivot.not()))
+            .collect();
+        for ci in candidates {
+            let resolvent: Clause = self.clauses[lemma].iter()
+                .chain(self.clauses[ci].iter().filter(|&&lit| lit != pivot.not()))
+                .copied()
+                .collect();
+            if !self.is_rup(&resolvent) {
+                return false;
+            }
+            self.core[ci] = true;
+        }
+        true
+    }
+}
+
+/// clauses に対する DRAT 形式の証明 proof を検査する (テキスト形式とバイナリ形式)
+/// 前向きに節を加えて根での単位伝播で矛盾するまで読み, 矛盾に使った導いた節だけを
+/// 後ろから順に, 節を加える前の論理式で RUP か, 先頭のリテラルを pivot として RAT か調べる
+/// 単位伝播の理由になっている節の削除は無視する
+/// 検査に失敗した導いた節があっても残りの節の検査を続け, 失敗したうちで最も前の行を返す
+/// # Returns
+/// * `Ok(())` - 矛盾が導かれ, 使った導いた節が全て正しい
+/// * `Err(message)` - 読めない行, 矛盾が導かれない, または検査に失敗した導いた節のうち最も前の行
+pub fn check_drat(clauses: &Clauses, proof: &[u8]) -> Result<(), String> {
+    let lines = parse_proof(proof)?;
+
+    let mut checker = Checker {
+        clauses: Vec::new(),
+        active: Vec::new(),
+        core: Vec::new(),
+        watches: Vec::new(),
+        values: Vec::new(),
+        reasons: Vec::new(),
+        positions: Vec::new(),
+        trail: Vec::new(),
+        core_head: 0,
+        head: 0,
+        seen: Vec::new(),
+    };
+    // 削除する節を探すための, 整列したリテラルから節の番号
+    let mut ids: HashMap<Clause, Vec<usize>> = HashMap::new();
+    let key = |clause: &Clause| {
+        let mut key = clause.clone();
+        key.sort_unstable();
+        key.dedup();
+        key
+    };
+
+    let mut conflict = None;
+    for clause in clauses {
+        ids.entry(key(clause)).or_default().push(checker.clauses.len());
+        if !checker.attach(clause.clone()) {
+            conflict = Some(checker.clauses.len() - 1);
+            break;
+        }
+    }
+    if conflict.is_none() {
+        conflict = checker.propagate();
+    }
+
+    // 前向き: 矛盾するまで節を加え, 削除する
+    let mut steps: Vec<Step> = Vec::new();
+    let mut ignored = 0;
+    for (k, line) in lines.iter().enumerate() {
+        if conflict.is_some() {
+            break;
+        }
+        let trail = checker.trail.len();
+        if line.delete {
+            let ci = match ids.get_mut(&key(&line.lits)).and_then(|ids| ids.pop()) {
+                Some(ci) => ci,
+                None => {
+                    ignored += 1;
+                    continue;
+                },
+            };
+            if checker.clauses[ci].iter().any(|lit| checker.reasons[lit.var()] == Some(ci)) {
+                ids.get_mut(&key(&line.lits)).unwrap().push(ci);
+                ignored += 1;
+                continue;
+            }
+            checker.active[ci] = false;
+            steps.push(Step { clause: ci, delete: true, line: k, trail });
+        } else {
+            let ci = checker.clauses.len();
+            steps.push(Step { clause: ci, delete: false, line: k, trail });
+            ids.entry(key(&line.lits)).or_default().push(ci);
+            conflict = if checker.attach(line.lits.clone()) { checker.propagate() } else { Some(ci) };
+        }
+    }
+    let conflict = conflict.ok_or_else(|| "no conflict is derived by unit propagation".to_string())?;
+    checker.core[conflict] = true;
+    let vars: Vec<usize> = checker.clauses[conflict].iter().map(|lit| lit.var()).collect();
+    checker.analyze(vars);
+
+    // 後ろ向き: 矛盾に使った導いた節を, 加える前の論理式で検査する
+    let mut checked = 0;
+    let mut failed = None;
+    for step in steps.iter().rev() {
+        checker.backtrack(step.trail);
+        let ci = step.clause;
+        checker.active[ci] = step.delete;
+        if step.delete || !checker.core[ci] {
+            continue;
+        }
+        checked += 1;
+        let lemma = checker.clauses[ci].clone();
+        if checker.is_rup(&lemma) {
+            continue;
+        }
+        let line = &lines[step.line];
+        if let Some(&pivot) = line.lits.first() {
+            if checker.is_rat(ci, pivot) {
+                continue;
+            }
+        }
+        // 後ろから検査するので, 最後に失敗した節が最も前の行になる
+        failed = Some(line);
+    }
+    if let Some(line) = failed {
+        let lits: Vec<String> = line.lits.iter().map(|lit| lit.to_dimacs()).chain([0]).map(|lit| lit.to_string()).collect();
+        return Err(format!("line {}: lemma {} is not RUP or RAT", line.line, lits.join(" ")));
+    }
+    let lemmas = steps.iter().filter(|step| !step.delete).count();
+    info!("drat: {} lemmas, {} checked, {} deletions ignored", lemmas, checked, ignored);
+    Ok(())
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use crate::lrat::LratWriter;
+    use crate::random::Random;
+    use crate::solver::Solver;
+    use crate::testing::{brute_force, random_clauses};
+
+    /// 書き出した証明を読むための, 共有したバッファ
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn rup_and_rat_lemmas_are_checked() {
+        // x1 ∨ x2 の 4 通りの符号の節
+        let clauses: Clauses = [vec![1, 2], vec![-1, 2], vec![1, -2], vec![-1, -2]].into_iter()
+            .map(|clause| clause.into_iter().map(Literal::from_dimacs).collect())
+            .collect();
+        assert_eq!(check_drat(&clauses, b"1 0\n0\n"), Ok(()));
+        assert_eq!(check_drat(&clauses, b"a\x02\x00a\x00"), Ok(()));
+        assert!(check_drat(&clauses, b"c no lemma\n").is_err());
+
+        // 新しい変数 x3 を x1 と ¬x1 の両方に含める節は RAT, x3 は RUP でなく RAT
+        assert_eq!(check_drat(&clauses, b"-3 1 0\n-3 -1 0\nd -3 1 0\n3 0\n0\n"), Ok(()));
+        assert_eq!(check_drat(&clauses, b"3 0\n-3 0\n0\n"), Err("line 2: lemma -3 0 is not RUP or RAT".to_string()));
+    }
+
+    #[test]
+    fn lowest_failing_line_is_reported() {
+        // ¬x1 と ¬x2 はどちらも RUP でも RAT でもなく, 両方とも矛盾に使う
+        let clauses: Clauses = vec![vec![Literal::Pos(0), Literal::Pos(1)]];
+        assert_eq!(check_drat(&clauses, b"-1 0\n-2 0\n"), Err("line 1: lemma -1 0 is not RUP or RAT".to_string()));
+        assert_eq!(check_drat(&clauses, b"c comment\n-2 0\n-1 0\n"), Err("line 2: lemma -2 0 is not RUP or RAT".to_string()));
+    }
+
+    #[test]
+    fn solver_drat_proofs_are_verified() {
+        let mut rng = Random::new(17);
+        let mut proofs = 0;
+        while proofs < 100 {
+            let size_vars = 3 + rng.gen_range(8);
+            let clauses = random_clauses(&mut rng, size_vars, size_vars * 5, 3);
+            if !brute_force(size_vars, &clauses, &[]).is_empty() {
+                continue;
+            }
+            proofs += 1;
+            let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+            let mut solver = Solver::new();
+            solver.restart_first = 1 + rng.gen_range(3);
+            solver.set_proof(LratWriter::drat(buffer.clone()));
+            for clause in &clauses {
+                solver.add_clause(&mut clause.clone());
+            }
+            assert_eq!(solver.solve(), Some(false));
+            solver.flush_proof().unwrap();
+
+            let proof = buffer.0.lock().unwrap().clone();
+            assert_eq!(check_drat(&clauses, &proof), Ok(()), "{:?}\n{}", clauses, String::from_utf8_lossy(&proof));
+        }
+    }
+}
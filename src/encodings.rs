@@ -0,0 +1,623 @@
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+/// at-most-one の符号化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmoEncoding {
+    /// 全ての2つの組を禁止する, 補助変数なし, n(n-1)/2 節
+    Pairwise,
+    /// 「ここまでに真のものがある」を表す補助変数の列 (Sinz の sequential encoding), 3n 節程度
+    Ladder,
+    /// 3つずつの組に commander 変数を立て, commander の at-most-one を再帰的に符号化する (Klieber, Kwon 2007)
+    Commander,
+}
+
+/// at-most-k の符号化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardEncoding {
+    /// 前から i 個の中で真のものが j 個以上あるかを表す補助変数 (Sinz 2005), nk 個の補助変数
+    SequentialCounter,
+    /// 二分木の各節点で真の数を単進数で数える (Bailleux, Boufkhad 2003)
+    Totalizer,
+    /// 半分の向きの節だけの sorting network と merge (Asín, Nieuwenhuis, Oliveras, Rodríguez-Carbonell 2011)
+    CardinalityNetwork,
+}
+
+/// 擬似ブール制約 (重み付きの和) の符号化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PbEncoding {
+    /// 全加算器で和を2進数にして, 上限と比較する (Eén, Sörensson 2006)
+    Adder,
+    /// 二分木の各節点で取りうる和の値ごとに変数を作る (Joshi, Martins, Manquinho 2015)
+    GeneralizedTotalizer,
+}
+
+/// 節を solver に加えていく, 補助変数は solver から作る
+struct Encoder<'a> {
+    solver: &'a mut Solver,
+    ok: bool,                   // false なら根で矛盾した
+    false_lit: Option<Literal>, // 常に偽のリテラル, 入力を揃えるのに使う
+}
+
+impl<'a> Encoder<'a> {
+    fn new(solver: &'a mut Solver) -> Self {
+        Encoder {
+            solver,
+            ok: true,
+            false_lit: None,
+        }
+    }
+
+    fn clause(&mut self, lits: &[Literal]) {
+        let mut clause: Clause = lits.to_vec();
+        if !self.solver.add_clause(&mut clause) {
+            self.ok = false;
+        }
+    }
+
+    fn new_lit(&mut self) -> Literal {
+        Literal::Pos(self.solver.new_var())
+    }
+
+    fn false_lit(&mut self) -> Literal {
+        if let Some(lit) = self.false_lit {
+            return lit;
+        }
+        let lit = self.new_lit();
+        self.clause(&[lit.not()]);
+        self.false_lit = Some(lit);
+        lit
+    }
+
+    fn pairwise(&mut self, lits: &[Literal]) {
+        for i in 0..lits.len() {
+            for j in i + 1..lits.len() {
+                self.clause(&[lits[i].not(), lits[j].not()]);
+            }
+        }
+    }
+
+    fn ladder(&mut self, lits: &[Literal]) {
+        if lits.len() <= 1 {
+            return;
+        }
+        // s[i] = lits[..=i] に真のものがある
+        let mut prev = lits[0];
+        for &lit in &lits[1..lits.len() - 1] {
+            let s = self.new_lit();
+            self.clause(&[prev.not(), s]);
+            self.clause(&[lit.not(), s]);
+            self.clause(&[prev.not(), lit.not()]);
+            prev = s;
+        }
+        self.clause(&[prev.not(), lits[lits.len() - 1].not()]);
+    }
+
+    fn commander(&mut self, lits: &[Literal]) {
+        const GROUP: usize = 3;
+        if lits.len() <= GROUP + 1 {
+            self.pairwise(lits);
+            return;
+        }
+        let mut commanders = Vec::new();
+        for group in lits.chunks(GROUP) {
+            // 組の中は高々1つ, 組の中に真のものがあれば commander が真
+            self.pairwise(group);
+            let c = self.new_lit();
+            for &lit in group {
+                self.clause(&[lit.not(), c]);
+            }
+            commanders.push(c);
+        }
+        self.commander(&commanders);
+    }
+
+    fn sequential_counter(&mut self, lits: &[Literal], k: usize) {
+        let n = lits.len();
+        if k == 0 {
+            for &lit in lits {
+                self.clause(&[lit.not()]);
+            }
+            return;
+        }
+        if n <= k {
+            return;
+        }
+        // s[j] = 前から i 個の中で真のものが j + 1 個以上
+        let mut prev: Vec<Literal> = Vec::new();
+        for (i, &lit) in lits.iter().enumerate() {
+            if i > 0 {
+                // lits[i] が真なら, それまでに k 個あってはいけない
+                self.clause(&[lit.not(), prev[k - 1].not()]);
+            }
+            if i == n - 1 {
+                break;
+            }
+            let s: Vec<Literal> = (0..k).map(|_| self.new_lit()).collect();
+            self.clause(&[lit.not(), s[0]]);
+            for j in 0..k {
+                if i > 0 {
+                    self.clause(&[prev[j].not(), s[j]]);
+                    if j > 0 {
+                        self.clause(&[lit.not(), prev[j - 1].not(), s[j]]);
+                    }
+                } else if j > 0 {
+                    self.clause(&[s[j].not()]);
+                }
+            }
+            prev = s;
+        }
+    }
+
+    /// 真の数を単進数で数える, outputs[j] は lits の j + 1 個以上が真なら真になる
+    /// limit 個より多くは数えない, limit が 0 なら出力はない
+    fn totalizer(&mut self, lits: &[Literal], limit: usize) -> Vec<Literal> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        if lits.len() <= 1 {
+            return lits.to_vec();
+        }
+        let (left, right) = lits.split_at(lits.len() / 2);
+        let a = self.totalizer(left, limit);
+        let b = self.totalizer(right, limit);
+        let outputs: Vec<Literal> = (0..(a.len() + b.len()).min(limit)).map(|_| self.new_lit()).collect();
+        for i in 0..=a.len() {
+            for j in 0..=b.len() {
+                if i + j == 0 {
+                    continue;
+                }
+                // a の i 個以上かつ b の j 個以上が真なら, 合わせて i + j 個以上
+                let mut clause = vec![outputs[(i + j).min(outputs.len()) - 1]];
+                if i > 0 {
+                    clause.push(a[i - 1].not());
+                }
+                if j > 0 {
+                    clause.push(b[j - 1].not());
+                }
+                self.clause(&clause);
+            }
+        }
+        outputs
+    }
+
+    /// 2入力の比較器, 真の数を上から詰めた (c1, c2) を返す, 上向きの節だけ作る
+    fn comparator(&mut self, a: Literal, b: Literal) -> (Literal, Literal) {
+        let c1 = self.new_lit();
+        let c2 = self.new_lit();
+        self.clause(&[a.not(), c1]);
+        self.clause(&[b.not(), c1]);
+        self.clause(&[a.not(), b.not(), c2]);
+        (c1, c2)
+    }
+
+    /// 整列済みの同じ長さ (2 の冪) の列を併合する
+    fn merge(&mut self, a: &[Literal], b: &[Literal]) -> Vec<Literal> {
+        if a.len() == 1 {
+            let (c1, c2) = self.comparator(a[0], b[0]);
+            return vec![c1, c2];
+        }
+        let odd = |x: &[Literal]| x.iter().step_by(2).copied().collect::<Vec<Literal>>();
+        let even = |x: &[Literal]| x.iter().skip(1).step_by(2).copied().collect::<Vec<Literal>>();
+        let d = self.merge(&odd(a), &odd(b));
+        let e = self.merge(&even(a), &even(b));
+        let mut c = vec![d[0]];
+        for i in 0..a.len() - 1 {
+            let (c1, c2) = self.comparator(d[i + 1], e[i]);
+            c.push(c1);
+            c.push(c2);
+        }
+        c.push(e[e.len() - 1]);
+        c
+    }
+
+    /// 長さ 2 の冪の列を整列する
+    fn sort(&mut self, lits: &[Literal]) -> Vec<Literal> {
+        if lits.len() == 1 {
+            return lits.to_vec();
+        }
+        let (left, right) = lits.split_at(lits.len() / 2);
+        let a = self.sort(left);
+        let b = self.sort(right);
+        self.merge(&a, &b)
+    }
+
+    /// 整列済みの同じ長さ (2 の冪) の列を併合し, 上から len + 1 個だけ返す
+    fn simplified_merge(&mut self, a: &[Literal], b: &[Literal]) -> Vec<Literal> {
+        if a.len() == 1 {
+            let (c1, c2) = self.comparator(a[0], b[0]);
+            return vec![c1, c2];
+        }
+        let odd = |x: &[Literal]| x.iter().step_by(2).copied().collect::<Vec<Literal>>();
+        let even = |x: &[Literal]| x.iter().skip(1).step_by(2).copied().collect::<Vec<Literal>>();
+        let d = self.simplified_merge(&odd(a), &odd(b));
+        let e = self.simplified_merge(&even(a), &even(b));
+        let mut c = vec![d[0]];
+        for i in 0..a.len() / 2 {
+            let (c1, c2) = self.comparator(d[i + 1], e[i]);
+            c.push(c1);
+            c.push(c2);
+        }
+        c
+    }
+
+    /// 真の数の上位 m 個 (m は 2 の冪) を単進数で返す, lits の長さは m の倍数
+    fn cardinality_network(&mut self, lits: &[Literal], m: usize) -> Vec<Literal> {
+        if lits.len() == m {
+            return self.sort(lits);
+        }
+        let c = self.cardinality_network(&lits[..m], m);
+        let d = self.cardinality_network(&lits[m..], m);
+        let mut merged = self.simplified_merge(&c, &d);
+        merged.truncate(m);
+        merged
+    }
+
+    fn at_most_k(&mut self, lits: &[Literal], k: usize, encoding: CardEncoding) {
+        if k >= lits.len() {
+            return;
+        }
+        match encoding {
+            CardEncoding::SequentialCounter => self.sequential_counter(lits, k),
+            CardEncoding::Totalizer => {
+                let outputs = self.totalizer(lits, k + 1);
+                self.clause(&[outputs[k].not()]);
+            },
+            CardEncoding::CardinalityNetwork => {
+                // k + 1 以上の 2 の冪の幅に揃え, 足りない入力は偽で埋める
+                let m = (k + 1).next_power_of_two();
+                let mut padded = lits.to_vec();
+                while !padded.len().is_multiple_of(m) {
+                    let f = self.false_lit();
+                    padded.push(f);
+                }
+                let outputs = self.cardinality_network(&padded, m);
+                self.clause(&[outputs[k].not()]);
+            },
+        }
+    }
+
+    /// 重みを 2 進数で足し合わせる, 返り値の [i] は和の i ビット目
+    fn adder(&mut self, terms: &[(u64, Literal)]) -> Vec<Literal> {
+        let mut buckets: Vec<Vec<Literal>> = vec![Vec::new(); 64];
+        for &(weight, lit) in terms {
+            for (bit, bucket) in buckets.iter_mut().enumerate() {
+                if (weight >> bit) & 1 == 1 {
+                    bucket.push(lit);
+                }
+            }
+        }
+        let mut bits = Vec::new();
+        let mut bit = 0;
+        while buckets[bit..].iter().any(|bucket| !bucket.is_empty()) {
+            while buckets[bit].len() >= 2 {
+                let a = buckets[bit].remove(0);
+                let b = buckets[bit].remove(0);
+                let c = if buckets[bit].len() >= 2 { Some(buckets[bit].remove(0)) } else { None };
+                let (sum, carry) = self.full_adder(a, b, c);
+                buckets[bit].push(sum);
+                if bit + 1 == buckets.len() {
+                    buckets.push(Vec::new());
+                }
+                buckets[bit + 1].push(carry);
+            }
+            match buckets[bit].pop() {
+                Some(lit) => bits.push(lit),
+                None => {
+                    let f = self.false_lit();
+                    bits.push(f);
+                },
+            }
+            bit += 1;
+        }
+        bits
+    }
+
+    /// a + b (+ c) の和の桁と繰り上がり, 両方向の節を作る
+    fn full_adder(&mut self, a: Literal, b: Literal, c: Option<Literal>) -> (Literal, Literal) {
+        let sum = self.new_lit();
+        let carry = self.new_lit();
+        match c {
+            Some(c) => {
+                // sum = a ⊕ b ⊕ c
+                for bits in 0..16u32 {
+                    if bits.count_ones() % 2 == 1 {
+                        let clause: Vec<Literal> = [a, b, c, sum].iter().enumerate()
+                            .map(|(k, &lit)| if (bits >> k) & 1 == 1 { lit.not() } else { lit })
+                            .collect();
+                        self.clause(&clause);
+                    }
+                }
+                // carry = 多数決(a, b, c)
+                for (x, y) in [(a, b), (a, c), (b, c)] {
+                    self.clause(&[x.not(), y.not(), carry]);
+                    self.clause(&[x, y, carry.not()]);
+                }
+            },
+            None => {
+                // sum = a ⊕ b, carry = a ∧ b
+                self.clause(&[a.not(), b.not(), sum.not()]);
+                self.clause(&[a, b, sum.not()]);
+                self.clause(&[a.not(), b, sum]);
+                self.clause(&[a, b.not(), sum]);
+                self.clause(&[a.not(), b.not(), carry]);
+                self.clause(&[a, carry.not()]);
+                self.clause(&[b, carry.not()]);
+            },
+        }
+        (sum, carry)
+    }
+
+    /// 重み付きの和を, 取りうる値ごとの変数で表す, limit より大きい値は limit にまとめる
+    /// 返り値の (v, o) は, 和が v 以上なら o が真になる
+    fn generalized_totalizer(&mut self, terms: &[(u64, Literal)], limit: u64) -> Vec<(u64, Literal)> {
+        if terms.len() == 1 {
+            return vec![(terms[0].0.min(limit), terms[0].1)];
+        }
+        let (left, right) = terms.split_at(terms.len() / 2);
+        let a = self.generalized_totalizer(left, limit);
+        let b = self.generalized_totalizer(right, limit);
+
+        let mut values: Vec<u64> = a.iter().map(|&(v, _)| v)
+            .chain(b.iter().map(|&(v, _)| v))
+            .chain(a.iter().flat_map(|&(va, _)| b.iter().map(move |&(vb, _)| (va + vb).min(limit))))
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+        let outputs: Vec<(u64, Literal)> = values.into_iter().map(|v| (v, self.new_lit())).collect();
+        let output = |v: u64| outputs[outputs.binary_search_by_key(&v, |&(value, _)| value).unwrap()].1;
+        for &(va, la) in &a {
+            self.clause(&[la.not(), output(va)]);
+        }
+        for &(vb, lb) in &b {
+            self.clause(&[lb.not(), output(vb)]);
+        }
+        for &(va, la) in &a {
+            for &(vb, lb) in &b {
+                self.clause(&[la.not(), lb.not(), output((va + vb).min(limit))]);
+            }
+        }
+        outputs
+    }
+
+    fn pb_at_most(&mut self, terms: &[(u64, Literal)], bound: u64, encoding: PbEncoding) {
+        // 重み 0 の項は関係なく, 重みが上限を超える項は偽にするしかない
+        let mut rest = Vec::new();
+        for &(weight, lit) in terms {
+            if weight > bound {
+                self.clause(&[lit.not()]);
+            } else if weight > 0 {
+                rest.push((weight, lit));
+            }
+        }
+        if rest.iter().map(|&(weight, _)| weight).sum::<u64>() <= bound {
+            return;
+        }
+        match encoding {
+            PbEncoding::Adder => {
+                let bits = self.adder(&rest);
+                // 上の桁から見て, 初めて bound と異なる桁で和の方が 1 になることを禁止する
+                for i in 0..bits.len() {
+                    if (bound >> i) & 1 == 1 {
+                        continue;
+                    }
+                    let mut clause = vec![bits[i].not()];
+                    for (j, &bit) in bits.iter().enumerate().skip(i + 1) {
+                        clause.push(if (bound >> j) & 1 == 1 { bit.not() } else { bit });
+                    }
+                    self.clause(&clause);
+                }
+            },
+            PbEncoding::GeneralizedTotalizer => {
+                let outputs = self.generalized_totalizer(&rest, bound + 1);
+                for (value, lit) in outputs {
+                    if value > bound {
+                        self.clause(&[lit.not()]);
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// lits の高々1つが真
+/// # Returns
+/// * `true` - 成功
+/// * `false` - 根で矛盾した, UNSAT
+pub fn at_most_one(solver: &mut Solver, lits: &[Literal], encoding: AmoEncoding) -> bool {
+    let mut encoder = Encoder::new(solver);
+    match encoding {
+        AmoEncoding::Pairwise => encoder.pairwise(lits),
+        AmoEncoding::Ladder => encoder.ladder(lits),
+        AmoEncoding::Commander => encoder.commander(lits),
+    }
+    encoder.ok
+}
+
+/// lits のちょうど1つが真
+pub fn exactly_one(solver: &mut Solver, lits: &[Literal], encoding: AmoEncoding) -> bool {
+    solver.add_clause(&mut lits.to_vec()) && at_most_one(solver, lits, encoding)
+}
+
+/// lits の高々 k 個が真
+pub fn at_most_k(solver: &mut Solver, lits: &[Literal], k: usize, encoding: CardEncoding) -> bool {
+    let mut encoder = Encoder::new(solver);
+    encoder.at_most_k(lits, k, encoding);
+    encoder.ok
+}
+
+/// lits の k 個以上が真, 否定の高々 n - k 個が真と同じ
+pub fn at_least_k(solver: &mut Solver, lits: &[Literal], k: usize, encoding: CardEncoding) -> bool {
+    if k > lits.len() {
+        return solver.add_clause(&mut Vec::new());
+    }
+    let negated: Vec<Literal> = lits.iter().map(|lit| lit.not()).collect();
+    at_most_k(solver, &negated, lits.len() - k, encoding)
+}
+
+/// lits のちょうど k 個が真
+pub fn exactly_k(solver: &mut Solver, lits: &[Literal], k: usize, encoding: CardEncoding) -> bool {
+    at_most_k(solver, lits, k, encoding) && at_least_k(solver, lits, k, encoding)
+}
+
+/// 真のリテラルの重みの和が bound 以下
+pub fn pb_at_most(solver: &mut Solver, terms: &[(u64, Literal)], bound: u64, encoding: PbEncoding) -> bool {
+    let mut encoder = Encoder::new(solver);
+    encoder.pb_at_most(terms, bound, encoding);
+    encoder.ok
+}
+
+/// 真のリテラルの重みの和が bound 以上, 偽のリテラルの重みの和が 総和 - bound 以下と同じ
+pub fn pb_at_least(solver: &mut Solver, terms: &[(u64, Literal)], bound: u64, encoding: PbEncoding) -> bool {
+    let total: u64 = terms.iter().map(|&(weight, _)| weight).sum();
+    if bound > total {
+        return solver.add_clause(&mut Vec::new());
+    }
+    let negated: Vec<(u64, Literal)> = terms.iter().map(|&(weight, lit)| (weight, lit.not())).collect();
+    pb_at_most(solver, &negated, total - bound, encoding)
+}
+
+/// 真の数を単進数で数える totalizer を作り, 出力のリテラルを返す
+/// 返り値の [j] は, lits の j + 1 個以上が真なら真になる (limit 個まで, limit が 0 なら空)
+/// [k] の否定を単位節や仮定にすれば高々 k 個になるので, 上限を少しずつ下げる最適化に使える
+/// 出力の変数は前処理で除去されないように freeze する
+pub fn totalizer(solver: &mut Solver, lits: &[Literal], limit: usize) -> Vec<Literal> {
+    let mut encoder = Encoder::new(solver);
+    let outputs = encoder.totalizer(lits, limit);
+    for lit in &outputs {
+        solver.freeze(lit.var());
+    }
+    outputs
+}
+
+/// 重み付きの和を表す generalized totalizer を作り, 出力を値の昇順に返す
+/// 返り値の (v, o) は, 和が v 以上なら o が真になる (limit より大きい値は limit にまとめる)
+/// 出力の変数は前処理で除去されないように freeze する
+pub fn generalized_totalizer(solver: &mut Solver, terms: &[(u64, Literal)], limit: u64) -> Vec<(u64, Literal)> {
+    let terms: Vec<(u64, Literal)> = terms.iter().copied().filter(|&(weight, _)| weight > 0).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut encoder = Encoder::new(solver);
+    let outputs = encoder.generalized_totalizer(&terms, limit);
+    for (_, lit) in &outputs {
+        solver.freeze(lit.var());
+    }
+    outputs
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 符号化した制約の, 入力の変数に射影したモデル数
+    fn count_models(n: usize, encode: impl Fn(&mut Solver, &[Literal]) -> bool) -> usize {
+        let mut solver = Solver::new();
+        let vars: Vec<usize> = (0..n).map(|_| solver.new_var()).collect();
+        let lits: Vec<Literal> = vars.iter().map(|&var| Literal::Pos(var)).collect();
+        if !encode(&mut solver, &lits) {
+            return 0;
+        }
+        solver.models(Some(&vars)).count()
+    }
+
+    /// 真の数が条件を満たす割り当ての数
+    fn expected(n: usize, weights: &[u64], ok: impl Fn(u64) -> bool) -> usize {
+        (0..1u64 << n)
+            .filter(|bits| ok((0..n).filter(|&i| (bits >> i) & 1 == 1).map(|i| weights[i]).sum()))
+            .count()
+    }
+
+    fn check_amo(encoding: AmoEncoding) {
+        let n = 7;
+        let ones = vec![1; n];
+        assert_eq!(count_models(n, |s, l| at_most_one(s, l, encoding)), expected(n, &ones, |c| c <= 1));
+        assert_eq!(count_models(n, |s, l| exactly_one(s, l, encoding)), expected(n, &ones, |c| c == 1));
+    }
+
+    fn check_card(encoding: CardEncoding) {
+        let n = 7;
+        let ones = vec![1; n];
+        for k in 0..=n {
+            assert_eq!(count_models(n, |s, l| at_most_k(s, l, k, encoding)), expected(n, &ones, |c| c <= k as u64));
+            assert_eq!(count_models(n, |s, l| exactly_k(s, l, k, encoding)), expected(n, &ones, |c| c == k as u64));
+        }
+        for k in 0..=n + 1 {
+            assert_eq!(count_models(n, |s, l| at_least_k(s, l, k, encoding)), expected(n, &ones, |c| c >= k as u64));
+        }
+    }
+
+    fn check_pb(encoding: PbEncoding) {
+        let n = 7;
+        let weights = [3, 1, 4, 1, 5, 9, 2];
+        let terms = |l: &[Literal]| weights.iter().copied().zip(l.iter().copied()).collect::<Vec<_>>();
+        for bound in [0, 4, 7, 12, 25, 30] {
+            assert_eq!(count_models(n, |s, l| pb_at_most(s, &terms(l), bound, encoding)), expected(n, &weights, |w| w <= bound));
+            assert_eq!(count_models(n, |s, l| pb_at_least(s, &terms(l), bound, encoding)), expected(n, &weights, |w| w >= bound));
+        }
+    }
+
+    #[test]
+    fn pairwise_matches_brute_force() {
+        check_amo(AmoEncoding::Pairwise);
+    }
+
+    #[test]
+    fn ladder_matches_brute_force() {
+        check_amo(AmoEncoding::Ladder);
+    }
+
+    #[test]
+    fn commander_matches_brute_force() {
+        check_amo(AmoEncoding::Commander);
+    }
+
+    #[test]
+    fn sequential_counter_matches_brute_force() {
+        check_card(CardEncoding::SequentialCounter);
+    }
+
+    #[test]
+    fn totalizer_matches_brute_force() {
+        check_card(CardEncoding::Totalizer);
+    }
+
+    #[test]
+    fn cardinality_network_matches_brute_force() {
+        check_card(CardEncoding::CardinalityNetwork);
+    }
+
+    #[test]
+    fn adder_matches_brute_force() {
+        check_pb(PbEncoding::Adder);
+    }
+
+    #[test]
+    fn generalized_totalizer_matches_brute_force() {
+        check_pb(PbEncoding::GeneralizedTotalizer);
+    }
+
+    #[test]
+    fn totalizer_outputs_count_true_lits() {
+        // 出力 [j] の否定を仮定すると, 真の数が j 以下のモデルだけが残る
+        let n = 5;
+        let ones = vec![1; n];
+        for limit in 0..=n + 1 {
+            for j in 0..=limit.min(n) {
+                let mut solver = Solver::new();
+                let vars: Vec<usize> = (0..n).map(|_| solver.new_var()).collect();
+                let lits: Vec<Literal> = vars.iter().map(|&var| Literal::Pos(var)).collect();
+                let outputs = totalizer(&mut solver, &lits, limit);
+                assert_eq!(outputs.len(), limit.min(n));
+                if j < outputs.len() {
+                    assert!(solver.add_clause(&mut vec![outputs[j].not()]));
+                    assert_eq!(solver.models(Some(&vars)).count(), expected(n, &ones, |c| c <= j as u64));
+                } else {
+                    assert_eq!(solver.models(Some(&vars)).count(), 1 << n);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+/// Solver::models が返す, 充足割り当てを順に返すイテレータ
+/// 解を1つ見つけるごとに, その解の projection 上の割り当てを禁止する節 (blocking clause) を加える
+pub struct Models<'a> {
+    solver: &'a mut Solver,
+    projection: Vec<usize>,     // 列挙で区別する変数
+    done: bool,                 // これ以上解がない, または打ち切られた
+}
+
+impl Iterator for Models<'_> {
+    type Item = Vec<Option<bool>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.solver.solve() != Some(true) {
+            self.done = true;
+            return None;
+        }
+
+        let model = self.solver.model.clone();
+        let mut blocking: Clause = self.projection.iter()
+            .filter_map(|&var| model[var].map(|value| if value { Literal::Neg(var) } else { Literal::Pos(var) }))
+            .collect();
+        if !self.solver.add_clause(&mut blocking) {
+            // projection 上の割り当てを全て列挙した
+            self.done = true;
+        }
+        Some(model)
+    }
+}
+
+impl Solver {
+    /// 充足割り当てを列挙する
+    /// projection が Some なら, その変数の割り当てが異なる解だけを1つずつ返す (射影した列挙)
+    /// None なら全ての変数 (0..size_vars) について異なる解を返す
+    /// 節に現れない変数も区別するには, 先に new_var で変数を加えておく
+    /// 前処理で除去されないように projection の変数を freeze するので, 最初に解く前に呼ぶこと
+    pub fn models(&mut self, projection: Option<&[usize]>) -> Models<'_> {
+        let projection: Vec<usize> = match projection {
+            Some(vars) => vars.to_vec(),
+            None => (0..self.size_vars()).collect(),
+        };
+        for &var in &projection {
+            let lit = Literal::Pos(var);
+            self.searcher.update_size_vars(&vec![lit]);
+            self.register_lits(&[lit]);
+            self.freeze(var);
+        }
+        Models {
+            solver: self,
+            projection,
+            done: false,
+        }
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, random_clauses};
+
+    fn solver_from(clauses: &[&[i32]]) -> Solver {
+        let mut solver = Solver::new();
+        for clause in clauses {
+            let mut clause: Clause = clause.iter().map(|&lit| Literal::from_dimacs(lit)).collect();
+            solver.add_clause(&mut clause);
+        }
+        solver
+    }
+
+    #[test]
+    fn enumerate_all_models() {
+        // (x1 ∨ x2) ∧ (¬x1 ∨ x3) の解は 4 つ
+        let mut solver = solver_from(&[&[1, 2], &[-1, 3]]);
+        let models: Vec<Vec<Option<bool>>> = solver.models(None).collect();
+        assert_eq!(models.len(), 4);
+        for (i, model) in models.iter().enumerate() {
+            assert!(model[0] == Some(true) || model[1] == Some(true));
+            assert!(model[0] == Some(false) || model[2] == Some(true));
+            assert!(!models[..i].contains(model));
+        }
+    }
+
+    #[test]
+    fn enumerate_projected_models() {
+        // x1 に射影すると x1 = true, false の 2 つ
+        let mut solver = solver_from(&[&[1, 2], &[-1, 3]]);
+        assert_eq!(solver.models(Some(&[0])).count(), 2);
+    }
+
+    #[test]
+    fn enumerate_unused_vars() {
+        // 8 変数のうち節に現れるのは 5 変数まで, 現れない変数はどちらの値でもよい
+        let mut rng = Random::new(5);
+        for _ in 0..50 {
+            let size_clauses = 1 + rng.gen_range(15);
+            let clauses = random_clauses(&mut rng, 5, size_clauses, 3);
+            let used: Vec<usize> = (0..5).filter(|&var| clauses.iter().flatten().any(|lit| lit.var() == var)).collect();
+            let free = 8 - used.len();
+
+            let new_solver = || {
+                let mut solver = Solver::new();
+                while solver.size_vars() < 8 {
+                    solver.new_var();
+                }
+                for clause in &clauses {
+                    solver.add_clause(&mut clause.clone());
+                }
+                solver
+            };
+            let count = new_solver().models(None).count();
+            assert_eq!(count, brute_force(8, &clauses, &[]).len());
+            assert_eq!(count, new_solver().models(Some(&used)).count() << free);
+        }
+    }
+}
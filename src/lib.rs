@@ -1,4 +1,28 @@
 pub mod literal;
 pub mod clause;
 pub mod dimacs_parser;
-pub mod solver;
\ No newline at end of file
+pub mod solver;
+pub mod preprocess;
+pub mod dimacs_writer;
+pub mod random;
+pub mod local_search;
+pub mod lookahead;
+pub mod cube;
+pub mod portfolio;
+pub mod enumerate;
+pub mod bigint;
+pub mod rational;
+pub mod approxmc;
+pub mod count;
+pub mod xor;
+pub mod encodings;
+pub mod opb;
+pub mod maxsat;
+pub mod mus;
+pub mod marco;
+pub mod backbone;
+pub mod unsat_core;
+pub mod lrat;
+pub mod drat_check;
+#[cfg(test)]
+mod testing;
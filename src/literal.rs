@@ -1,6 +1,6 @@
 use std::cmp;
 
-#[derive(Debug, Clone, Copy, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Literal {
     Pos(usize),
     Neg(usize),
@@ -30,6 +30,26 @@ impl Literal {
             Self::Neg(v) => Self::Pos(*v),
         }
     }
+
+    /// DIMACS 形式のリテラル (1始まり, 負なら否定) から作る
+    pub fn from_dimacs(lit: i32) -> Self {
+        let var: usize = (lit.abs() - 1).try_into().unwrap();
+        if lit > 0 {
+            Self::Pos(var)
+        } else {
+            Self::Neg(var)
+        }
+    }
+
+    /// DIMACS 形式のリテラル (1始まり, 負なら否定) に直す
+    pub fn to_dimacs(&self) -> i32 {
+        let var: i32 = (self.var() + 1).try_into().unwrap();
+        if self.is_pos() {
+            var
+        } else {
+            -var
+        }
+    }
 }
 
 impl PartialOrd for Literal {
@@ -62,18 +82,6 @@ impl Ord for Literal {
     }
 }
 
-
-impl PartialEq for Literal {
-    fn eq(&self, other : &Self) -> bool {
-        match (self, other) {
-            (Literal::Pos(p1), Literal::Pos(p2)) |
-            (Literal::Neg(p1), Literal::Neg(p2)) => p1 == p2,
-            (Literal::Pos(_), Literal::Neg(_)) |
-            (Literal::Neg(_), Literal::Pos(_)) => false,
-        }
-    }
-}
-
 // テストコード
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,291 @@
+use log::info;
+
+use crate::literal::Literal;
+use crate::solver::Clauses;
+use crate::random::Random;
+
+/// 局所探索で使う, 反転する変数の選び方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// break 数に応じた確率で選ぶ (Balint, Schöning 2012)
+    ProbSat,
+    /// break 数が最小の変数を選び, 確率 noise でランダムに選ぶ (WalkSAT/SKC)
+    WalkSat,
+}
+
+/// lit を含む節の一覧を引くためのインデックス
+fn lit_index(lit: &Literal) -> usize {
+    lit.var() * 2 + if lit.is_pos() { 0 } else { 1 }
+}
+
+/// 確率的局所探索で充足割り当てを探す
+/// 完全ではないので, 見つからなければ UNSAT ではなく判定不能として返す
+pub struct LocalSearch {
+    clauses: Clauses,
+    occurs: Vec<Vec<usize>>,        // occurs[lit_index(lit)] = lit を含む節の clauses 上のインデックス
+    assigns: Vec<bool>,             // 現在の割り当て
+    true_count: Vec<usize>,         // 各節で真になっているリテラルの数
+    unsat: Vec<usize>,              // 偽になっている節のインデックス
+    unsat_pos: Vec<usize>,          // unsat_pos[i] = unsat 上での節 i の位置, 偽でなければ usize::MAX
+    rng: Random,
+
+    pub algorithm: Algorithm,
+    pub noise: f64,                 // WalkSAT でランダムに選ぶ確率
+    pub cb: f64,                    // probSAT の break 数に対する重み (eps + break)^-cb
+    pub max_flips: usize,           // 1回の試行での反転回数の上限
+    pub max_tries: usize,           // 初期割り当てをやり直す回数の上限
+
+    // 見つかった解
+    pub model: Vec<Option<bool>>,
+    // 偽になっている節が最も少なかった割り当て
+    pub best_assigns: Vec<bool>,
+    pub best_unsat: usize,
+
+    // ログ等
+    pub flips: usize,
+    pub tries: usize,
+}
+
+impl LocalSearch {
+    /// clauses は単位節を含んでよい, 空節は含まないこと
+    pub fn new(size_vars: usize, clauses: &Clauses, seed: u64) -> Self {
+        let mut occurs = vec![Vec::new(); size_vars * 2];
+        for (i, clause) in clauses.iter().enumerate() {
+            for lit in clause {
+                occurs[lit_index(lit)].push(i);
+            }
+        }
+        LocalSearch {
+            clauses: clauses.clone(),
+            occurs,
+            assigns: vec![false; size_vars],
+            true_count: vec![0; clauses.len()],
+            unsat: Vec::new(),
+            unsat_pos: vec![usize::MAX; clauses.len()],
+            rng: Random::new(seed),
+
+            algorithm: Algorithm::ProbSat,
+            noise: 0.567,
+            cb: 2.38,
+            max_flips: 100_000,
+            max_tries: 100,
+
+            model: Vec::new(),
+            best_assigns: vec![false; size_vars],
+            best_unsat: usize::MAX,
+
+            flips: 0,
+            tries: 0,
+        }
+    }
+
+    fn is_true(&self, lit: &Literal) -> bool {
+        self.assigns[lit.var()] == lit.is_pos()
+    }
+
+    fn mark_unsat(&mut self, i: usize) {
+        self.unsat_pos[i] = self.unsat.len();
+        self.unsat.push(i);
+    }
+
+    fn mark_sat(&mut self, i: usize) {
+        let pos = self.unsat_pos[i];
+        let last = *self.unsat.last().unwrap();
+        self.unsat.swap_remove(pos);
+        if last != i {
+            self.unsat_pos[last] = pos;
+        }
+        self.unsat_pos[i] = usize::MAX;
+    }
+
+    /// 割り当てを initial にして, 各節の状態を計算し直す
+    fn reset(&mut self, initial: &[bool]) {
+        self.assigns.copy_from_slice(initial);
+        self.unsat.clear();
+        for i in 0..self.clauses.len() {
+            let count = self.clauses[i].iter().filter(|lit| self.is_true(lit)).count();
+            self.true_count[i] = count;
+            self.unsat_pos[i] = usize::MAX;
+            if count == 0 {
+                self.mark_unsat(i);
+            }
+        }
+    }
+
+    /// var を反転したときに, 真から偽になる節の数
+    fn break_count(&self, var: usize) -> usize {
+        let true_lit = if self.assigns[var] { Literal::Pos(var) } else { Literal::Neg(var) };
+        self.occurs[lit_index(&true_lit)].iter()
+            .filter(|&&i| self.true_count[i] == 1)
+            .count()
+    }
+
+    fn flip(&mut self, var: usize) {
+        let true_lit = if self.assigns[var] { Literal::Pos(var) } else { Literal::Neg(var) };
+        self.assigns[var] = !self.assigns[var];
+        self.flips += 1;
+
+        for k in 0..self.occurs[lit_index(&true_lit)].len() {
+            let i = self.occurs[lit_index(&true_lit)][k];
+            self.true_count[i] -= 1;
+            if self.true_count[i] == 0 {
+                self.mark_unsat(i);
+            }
+        }
+        let false_lit = true_lit.not();
+        for k in 0..self.occurs[lit_index(&false_lit)].len() {
+            let i = self.occurs[lit_index(&false_lit)][k];
+            self.true_count[i] += 1;
+            if self.true_count[i] == 1 {
+                self.mark_sat(i);
+            }
+        }
+    }
+
+    /// 偽になっている節 clause から反転する変数を選ぶ
+    fn pick_var(&mut self, i: usize) -> usize {
+        let breaks: Vec<(usize, usize)> = self.clauses[i].iter()
+            .map(|lit| (lit.var(), self.break_count(lit.var())))
+            .collect();
+
+        match self.algorithm {
+            Algorithm::ProbSat => {
+                let probs: Vec<f64> = breaks.iter()
+                    .map(|&(_, b)| (1.0 + b as f64).powf(-self.cb))
+                    .collect();
+                let mut r = self.rng.gen_f64() * probs.iter().sum::<f64>();
+                for (k, p) in probs.iter().enumerate() {
+                    if r < *p {
+                        return breaks[k].0;
+                    }
+                    r -= p;
+                }
+                breaks[breaks.len() - 1].0
+            },
+            Algorithm::WalkSat => {
+                let min_break = breaks.iter().map(|&(_, b)| b).min().unwrap();
+                if min_break > 0 && self.rng.gen_bool(self.noise) {
+                    return breaks[self.rng.gen_range(breaks.len())].0;
+                }
+                let candidates: Vec<usize> = breaks.iter()
+                    .filter(|&&(_, b)| b == min_break)
+                    .map(|&(var, _)| var)
+                    .collect();
+                candidates[self.rng.gen_range(candidates.len())]
+            },
+        }
+    }
+
+    /// initial から max_flips 回まで反転して充足割り当てを探す
+    /// # Returns
+    /// * `true` - 充足割り当てが見つかった
+    /// * `false` - 見つからなかった
+    pub fn search(&mut self, initial: &[bool], max_flips: usize) -> bool {
+        self.reset(initial);
+        for _ in 0..max_flips {
+            if self.unsat.len() < self.best_unsat {
+                self.best_unsat = self.unsat.len();
+                self.best_assigns.copy_from_slice(&self.assigns);
+            }
+            if self.unsat.is_empty() {
+                return true;
+            }
+            let i = self.unsat[self.rng.gen_range(self.unsat.len())];
+            let var = self.pick_var(i);
+            self.flip(var);
+        }
+        if self.unsat.len() < self.best_unsat {
+            self.best_unsat = self.unsat.len();
+            self.best_assigns.copy_from_slice(&self.assigns);
+        }
+        self.unsat.is_empty()
+    }
+
+    /// ランダムな初期割り当てから max_tries 回まで探索する
+    /// # Returns
+    /// * `Some(true)` - SAT, model に解が入る
+    /// * `None` - 判定不能
+    pub fn solve(&mut self) -> Option<bool> {
+        info!("==========[LOCAL SEARCH]==========");
+        info!("| Tries |    Flips    | Best   |");
+        info!("==================================");
+
+        let mut initial = vec![false; self.assigns.len()];
+        for _ in 0..self.max_tries {
+            self.tries += 1;
+            for assign in initial.iter_mut() {
+                *assign = self.rng.gen_bool(0.5);
+            }
+            let found = self.search(&initial, self.max_flips);
+            info!("| {:5} | {:11} | {:6} |", self.tries, self.flips, self.best_unsat);
+            if found {
+                info!("==================================");
+                self.model = self.assigns.iter().map(|&assign| Some(assign)).collect();
+                return Some(true);
+            }
+        }
+        info!("==================================");
+        None
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 割り当て planted を解に持つ, ランダムな 3-SAT
+    fn planted_3sat(size_vars: usize, size_clauses: usize, seed: u64) -> Clauses {
+        let mut rng = Random::new(seed);
+        let planted: Vec<bool> = (0..size_vars).map(|_| rng.gen_bool(0.5)).collect();
+        let mut clauses = Vec::new();
+        while clauses.len() < size_clauses {
+            let clause: Vec<Literal> = (0..3)
+                .map(|_| {
+                    let var = rng.gen_range(size_vars);
+                    if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) }
+                })
+                .collect();
+            if clause.iter().any(|lit| planted[lit.var()] == lit.is_pos()) {
+                clauses.push(clause);
+            }
+        }
+        clauses
+    }
+
+    #[test]
+    fn find_model_of_satisfiable_formula() {
+        let clauses = planted_3sat(50, 200, 1);
+        for algorithm in [Algorithm::ProbSat, Algorithm::WalkSat] {
+            let mut searcher = LocalSearch::new(50, &clauses, 7);
+            searcher.algorithm = algorithm;
+            assert_eq!(searcher.solve(), Some(true));
+            assert_eq!(searcher.best_unsat, 0);
+            for clause in &clauses {
+                assert!(clause.iter().any(|lit| searcher.model[lit.var()] == Some(lit.is_pos())));
+            }
+        }
+    }
+
+    #[test]
+    fn stop_at_flip_limit_on_unsatisfiable_formula() {
+        // (x1 ∨ x2) ∧ (x1 ∨ ¬x2) ∧ (¬x1 ∨ x2) ∧ (¬x1 ∨ ¬x2) は UNSAT, 1 つの節が常に偽
+        let clauses = vec![
+            vec![Literal::Pos(0), Literal::Pos(1)],
+            vec![Literal::Pos(0), Literal::Neg(1)],
+            vec![Literal::Neg(0), Literal::Pos(1)],
+            vec![Literal::Neg(0), Literal::Neg(1)],
+        ];
+        for algorithm in [Algorithm::ProbSat, Algorithm::WalkSat] {
+            let mut searcher = LocalSearch::new(2, &clauses, 0);
+            searcher.algorithm = algorithm;
+            searcher.max_flips = 50;
+            searcher.max_tries = 4;
+            assert_eq!(searcher.solve(), None);
+            assert_eq!(searcher.tries, 4);
+            assert_eq!(searcher.flips, 200);
+            assert_eq!(searcher.best_unsat, 1);
+            assert!(searcher.model.is_empty());
+        }
+    }
+}
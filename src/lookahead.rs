@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+
+use log::info;
+
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+/// 1つの節点での先読みの結果
+enum Lookahead {
+    /// 現在の割り当てでは充足できない, backtrack する
+    Conflict,
+    /// failed literal などで新しく割り当てたので, 伝播してから先読みし直す
+    Assigned,
+    /// 次に決定変数にするリテラル
+    Branch(Literal),
+    /// 全ての節が充足した
+    Sat,
+}
+
+/// リテラルを仮定して伝播した結果
+struct Look {
+    score: f64,                 // 節がどれだけ短くなったか
+    implied: Vec<Literal>,      // 伝播で真になったリテラル (仮定したリテラルを含む)
+}
+
+/// 節点をまたいで持ち回る先読みの状態
+struct LookaheadState {
+    occurs: HashMap<Literal, Vec<usize>>,   // occurs[lit] = lit を含む節の clauses 上のインデックス
+    double_trigger: f64,                    // 2段目の先読みを行う score の閾値
+}
+
+impl Solver {
+    fn is_clause_satisfied(&self, i: usize) -> bool {
+        self.clauses[i].iter().any(|lit| self.searcher.is_satisfied(lit) == Some(true))
+    }
+
+    /// 現在の割り当てで lit を仮定して伝播し, 元に戻す
+    /// score は, 偽になったリテラルを含む未充足の節について,
+    /// 残った未割り当てリテラルが少ないほど大きくなる重みを足したもの
+    /// # Returns
+    /// * `Some(look)` - 伝播の結果
+    /// * `None` - 矛盾した, lit は failed literal
+    fn look(&mut self, lit: Literal, state: &LookaheadState) -> Option<Look> {
+        let level = self.searcher.dlevel();
+        let start = self.searcher.trail_tail;
+        self.searcher.assume(lit);
+        if self.propagate() {
+            self.searcher.canceluntil(level);
+            return None;
+        }
+
+        let implied: Vec<Literal> = self.searcher.trail[start..self.searcher.trail_tail]
+            .iter()
+            .map(|lit| lit.unwrap())
+            .collect();
+        let mut reduced: HashSet<usize> = HashSet::new();
+        for lit in &implied {
+            if let Some(indices) = state.occurs.get(&lit.not()) {
+                reduced.extend(indices.iter().filter(|&&i| !self.is_clause_satisfied(i)));
+            }
+        }
+        let score = reduced.iter()
+            .map(|&i| {
+                let unassigned = self.clauses[i].iter()
+                    .filter(|lit| self.searcher.is_satisfied(lit).is_none())
+                    .count();
+                // 2リテラル節になったものを1として, 長いものほど小さくする
+                0.2f64.powi(unassigned as i32 - 2)
+            })
+            .sum();
+
+        self.searcher.canceluntil(level);
+        Some(Look { score, implied })
+    }
+
+    /// lit を仮定した上で, candidates のどれかの変数が両方の真偽値で矛盾するか調べる
+    /// # Returns
+    /// * `true` - lit は failed literal
+    fn double_look(&mut self, lit: Literal, candidates: &[usize], state: &LookaheadState) -> bool {
+        let level = self.searcher.dlevel();
+        self.searcher.assume(lit);
+        if self.propagate() {
+            self.searcher.canceluntil(level);
+            return true;
+        }
+        let mut failed = false;
+        for &var in candidates.iter().take(self.double_lookahead_candidates) {
+            if self.searcher.assigns[var].is_some() {
+                continue;
+            }
+            if self.look(Literal::Pos(var), state).is_none() && self.look(Literal::Neg(var), state).is_none() {
+                failed = true;
+                break;
+            }
+        }
+        self.searcher.canceluntil(level);
+        failed
+    }
+
+    /// 未充足の節に多く現れる未割り当ての変数を, 先読みする候補として選ぶ
+    fn preselect(&self) -> Vec<usize> {
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for i in 0..self.clauses.len() {
+            if self.is_clause_satisfied(i) {
+                continue;
+            }
+            for lit in &self.clauses[i] {
+                if self.searcher.is_satisfied(lit).is_none() {
+                    *counts.entry(lit.var()).or_default() += 1;
+                }
+            }
+        }
+        // XOR 制約の変数も, 全て割り当てるまでは候補にする
+        for var in self.xor.unassigned_vars(&self.searcher.assigns) {
+            *counts.entry(var).or_default() += 1;
+        }
+        let mut candidates: Vec<(usize, usize)> = counts.into_iter().collect();
+        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        candidates.into_iter()
+            .take(self.lookahead_candidates)
+            .map(|(var, _)| var)
+            .collect()
+    }
+
+    /// 現在の割り当てで lit を真にして伝播する
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 矛盾した
+    fn assign_and_propagate(&mut self, lit: Literal) -> bool {
+        self.searcher.assign_bool(lit) && !self.propagate()
+    }
+
+    /// 候補の変数を両方の真偽値で先読みし, 分岐する変数を決める
+    /// 矛盾するリテラルの否定, どちらの真偽値でも真になるリテラルは現在の決定レベルで割り当てる
+    fn lookahead(&mut self, state: &mut LookaheadState) -> Lookahead {
+        self.stats.lookaheads += 1;
+        let candidates = self.preselect();
+        if candidates.is_empty() {
+            return Lookahead::Sat;
+        }
+
+        let mut assigned = false;
+        let mut best: Option<(f64, Literal)> = None;
+        for &var in &candidates {
+            if self.searcher.assigns[var].is_some() {
+                continue;
+            }
+
+            let mut looks = Vec::new();
+            for lit in [Literal::Pos(var), Literal::Neg(var)] {
+                let mut look = self.look(lit, state);
+                if let Some(l) = &look {
+                    if l.score > state.double_trigger {
+                        if self.double_look(lit, &candidates, state) {
+                            look = None;
+                        } else {
+                            state.double_trigger = l.score;
+                        }
+                    }
+                }
+                looks.push(look);
+            }
+
+            match (&looks[0], &looks[1]) {
+                (None, None) => return Lookahead::Conflict,
+                (None, Some(_)) | (Some(_), None) => {
+                    self.stats.failed_literals += 1;
+                    let lit = if looks[0].is_none() { Literal::Neg(var) } else { Literal::Pos(var) };
+                    if !self.assign_and_propagate(lit) {
+                        return Lookahead::Conflict;
+                    }
+                    assigned = true;
+                },
+                (Some(pos), Some(neg)) => {
+                    // どちらを仮定しても真になるリテラル
+                    let implied_by_pos: HashSet<&Literal> = pos.implied.iter().collect();
+                    for lit in &neg.implied {
+                        if implied_by_pos.contains(lit) && self.searcher.is_satisfied(lit).is_none() {
+                            if !self.assign_and_propagate(*lit) {
+                                return Lookahead::Conflict;
+                            }
+                            assigned = true;
+                        }
+                    }
+
+                    let score = 1024.0 * pos.score * neg.score + pos.score + neg.score;
+                    // 節を短くしない方が充足しやすいと考えて先に試す
+                    let lit = if pos.score <= neg.score { Literal::Pos(var) } else { Literal::Neg(var) };
+                    if best.is_none_or(|(best_score, _)| score > best_score) {
+                        best = Some((score, lit));
+                    }
+                },
+            }
+        }
+
+        if assigned {
+            return Lookahead::Assigned;
+        }
+        match best {
+            Some((_, lit)) => Lookahead::Branch(lit),
+            None => Lookahead::Assigned,
+        }
+    }
+
+    fn lookahead_state(&self) -> LookaheadState {
+        let mut occurs: HashMap<Literal, Vec<usize>> = HashMap::new();
+        for (i, clause) in self.clauses.iter().enumerate() {
+            for &lit in clause {
+                occurs.entry(lit).or_default().push(i);
+            }
+        }
+        LookaheadState {
+            occurs,
+            double_trigger: 0.0,
+        }
+    }
+
+    /// 現在の節点から先読みで分岐を進め, 葉になった節点の決定変数を cube として cubes に積む
+    /// 矛盾した節点は cube にしない
+    fn split(&mut self, state: &mut LookaheadState, path: &mut Vec<Literal>, depth: usize, cutoff: usize, cubes: &mut Vec<Vec<Literal>>) {
+        let level = self.searcher.dlevel();
+        let lit = loop {
+            if self.propagate() {
+                return;
+            }
+            state.double_trigger *= 0.95;
+            match self.lookahead(state) {
+                Lookahead::Conflict => return,
+                Lookahead::Assigned => {},
+                Lookahead::Branch(lit) => break lit,
+                Lookahead::Sat => {
+                    cubes.push(path.clone());
+                    return;
+                },
+            }
+        };
+
+        let free_vars = self.searcher.assigns.iter().filter(|assign| assign.is_none()).count();
+        if path.len() >= depth || free_vars <= cutoff {
+            cubes.push(path.clone());
+            return;
+        }
+        for lit in [lit, lit.not()] {
+            path.push(lit);
+            self.searcher.assume(lit);
+            self.split(state, path, depth, cutoff, cubes);
+            self.searcher.canceluntil(level);
+            path.pop();
+        }
+    }
+
+    /// 先読みで論理式を cube に分割する (cube-and-conquer の cube の段階)
+    /// depth 回分岐するか, 未割り当ての変数が cutoff 以下になったら分割をやめる
+    /// 返した cube のどれかの下で充足できれば元の論理式も充足でき, 全て UNSAT なら元の論理式も UNSAT
+    pub fn cube(&mut self, depth: usize, cutoff: usize) -> Vec<Vec<Literal>> {
+        let mut cubes = Vec::new();
+        if !self.simplify() {
+            return cubes;
+        }
+        let mut state = self.lookahead_state();
+        self.split(&mut state, &mut Vec::new(), depth, cutoff, &mut cubes);
+        self.searcher.canceluntil(0);
+        cubes
+    }
+
+    /// 先読みによる DPLL で解く (march などの look-ahead ソルバーと同じ方針)
+    /// ランダム 3-SAT の UNSAT な問題では, solve による探索より速いことが多い
+    /// # Returns
+    /// * `Some`
+    ///   - `true` - SAT
+    ///   - `false` - UNSAT
+    pub fn solve_lookahead(&mut self) -> Option<bool> {
+        if !self.simplify() {
+            return Some(false);
+        }
+
+        let mut state = self.lookahead_state();
+
+        info!("==========[LOOKAHEAD]===========");
+        info!("| {:7} clauses {:8} vars |", self.clauses.len(), self.searcher.size_vars);
+        info!("================================");
+        loop {
+            if self.propagate() {
+                self.stats.conflicts += 1;
+                if !self.searcher.backtrack() {
+                    return Some(false);
+                }
+                continue;
+            }
+
+            state.double_trigger *= 0.95;
+            match self.lookahead(&mut state) {
+                Lookahead::Conflict => {
+                    self.stats.conflicts += 1;
+                    if !self.searcher.backtrack() {
+                        return Some(false);
+                    }
+                },
+                Lookahead::Assigned => {},
+                Lookahead::Branch(lit) => {
+                    self.stats.decisions += 1;
+                    self.searcher.assume(lit);
+                },
+                Lookahead::Sat => {
+                    self.model = self.searcher.assigns.clone();
+                    self.elim_stack.extend_model(&mut self.model);
+                    self.searcher.canceluntil(0);
+                    return Some(true);
+                },
+            }
+        }
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, is_model, is_xor_model, pigeonhole, random_clauses};
+
+    /// solve_lookahead の答えが総当たりと一致し, SAT ならモデルが節と XOR 制約を充足するか調べる
+    fn check(size_vars: usize, clauses: &[Vec<Literal>], xors: &[(Vec<usize>, bool)]) {
+        let mut solver = Solver::new();
+        for clause in clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        for (vars, parity) in xors {
+            solver.add_xor(vars, *parity);
+        }
+        let expected = !brute_force(size_vars, clauses, xors).is_empty();
+        assert_eq!(solver.solve_lookahead(), Some(expected), "{:?} {:?}", clauses, xors);
+        if expected {
+            assert!(is_model(clauses, &solver.model));
+            assert!(is_xor_model(xors, &solver.model));
+        }
+    }
+
+    #[test]
+    fn agree_with_brute_force() {
+        let mut rng = Random::new(0);
+        for _ in 0..300 {
+            let size_vars = 1 + rng.gen_range(10);
+            let size_clauses = 1 + rng.gen_range(size_vars * 6);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            check(size_vars, &clauses, &[]);
+        }
+    }
+
+    #[test]
+    fn agree_with_brute_force_under_xors() {
+        let mut rng = Random::new(1);
+        for _ in 0..300 {
+            let size_vars = 2 + rng.gen_range(9);
+            let size_clauses = rng.gen_range(size_vars * 4);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            let xors: Vec<(Vec<usize>, bool)> = (0..1 + rng.gen_range(3))
+                .map(|_| {
+                    let vars = (0..2 + rng.gen_range(3)).map(|_| rng.gen_range(size_vars)).collect();
+                    (vars, rng.gen_bool(0.5))
+                })
+                .collect();
+            check(size_vars, &clauses, &xors);
+        }
+    }
+
+    #[test]
+    fn pigeonhole_is_unsat() {
+        let clauses = pigeonhole(5, 4);
+        check(20, &clauses, &[]);
+        // 鳩を 1 羽減らすと SAT
+        let clauses = pigeonhole(4, 4);
+        check(16, &clauses, &[]);
+    }
+}
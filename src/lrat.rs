@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::{Clauses, Solver};
+
+/// LRAT 形式の証明を書き出す
+/// 導いた節ごとに `<番号> <リテラル> 0 <単位伝播に使う節の番号> 0` の行を書く
+/// DRAT 形式なら番号と hints を省いた `<リテラル> 0` の行を書く
+pub struct LratWriter {
+    w: Box<dyn Write + Send>,
+    next_id: u64,       // 次に導く節の番号
+    drat: bool,         // DRAT 形式で書き出すか
+}
+
+impl LratWriter {
+    /// num_clauses は元の論理式の節の数, 導いた節の番号はその次から付ける
+    pub fn new(w: impl Write + Send + 'static, num_clauses: usize) -> Self {
+        LratWriter {
+            w: Box::new(w),
+            next_id: num_clauses as u64 + 1,
+            drat: false,
+        }
+    }
+
+    /// 同じ節を DRAT 形式で書き出す, 導いた節は全て単位伝播で導ける (RUP)
+    pub fn drat(w: impl Write + Send + 'static) -> Self {
+        LratWriter {
+            w: Box::new(w),
+            next_id: 1,
+            drat: true,
+        }
+    }
+
+    /// 節を書き出す, added_clauses は Solver に加えた元の節の数
+    /// # Returns
+    /// * 導いた節の番号
+    pub(crate) fn add(&mut self, added_clauses: u64, clause: &Clause, hints: &[u64]) -> u64 {
+        let id = self.next_id.max(added_clauses + 1);
+        self.next_id = id + 1;
+        self.write_line(id, clause, hints).expect("proof writing error");
+        id
+    }
+
+    fn write_line(&mut self, id: u64, clause: &Clause, hints: &[u64]) -> io::Result<()> {
+        if self.drat {
+            for lit in clause {
+                write!(self.w, "{} ", lit.to_dimacs())?;
+            }
+            return writeln!(self.w, "0");
+        }
+        write!(self.w, "{}", id)?;
+        for lit in clause {
+            write!(self.w, " {}", lit.to_dimacs())?;
+        }
+        write!(self.w, " 0")?;
+        for hint in hints {
+            write!(self.w, " {}", hint)?;
+        }
+        writeln!(self.w, " 0")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+impl Solver {
+    /// 探索で導いた節を LRAT 形式の証明として書き出す, 節を加える前に設定する
+    /// 元の節の番号は add_clause で加えた順に 1 から, 解き始めた後に節を加えないこと
+    /// 前処理と vivification は行わない, 先読みによる探索は証明に含まれない
+    /// 導く節は矛盾の解析で学習した節で, hints は根で偽のリテラルの理由の節, 導出に使った理由の節,
+    /// 矛盾した節を単位伝播する順に並べたもの (Solver::explain_learnt), 削除した学習節は証明からは削除しない
+    /// # Panics
+    /// * XOR 制約を加えた後に呼んだ場合, XOR 制約による割り当てには理由の節がなく hints を作れない
+    /// * 節を加えた後に呼んだ場合, 加えた節の導き方を記録していない
+    pub fn set_proof(&mut self, proof: LratWriter) {
+        assert!(self.xors.is_empty(), "set_proof cannot be used with XOR constraints");
+        assert!(self.added_clauses == 0, "set_proof must be called before adding clauses");
+        self.proof = Some(proof);
+    }
+
+    /// 書き出した証明を flush する
+    pub fn flush_proof(&mut self) -> io::Result<()> {
+        match &mut self.proof {
+            Some(proof) => proof.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 単位伝播に使う割り当て
+struct Assignment {
+    values: HashMap<usize, bool>,
+}
+
+impl Assignment {
+    fn value(&self, lit: &Literal) -> Option<bool> {
+        self.values.get(&lit.var()).map(|&value| value == lit.is_pos())
+    }
+
+    /// lit を偽にする
+    /// # Returns
+    /// * `false` - lit が既に真だった
+    fn falsify(&mut self, lit: &Literal) -> bool {
+        if self.value(lit) == Some(true) {
+            return false;
+        }
+        self.values.insert(lit.var(), !lit.is_pos());
+        true
+    }
+
+    /// hints の節で順に単位伝播する
+    /// # Returns
+    /// * `Ok(true)` - 偽になる節が見つかった
+    /// * `Ok(false)` - 矛盾せずに hints を使い切った
+    /// * `Err(message)` - hint の節がない, または単位節にならない
+    fn propagate(&mut self, db: &HashMap<u64, Clause>, hints: &[i64]) -> Result<bool, String> {
+        for &hint in hints {
+            let clause = db.get(&(hint as u64)).ok_or_else(|| format!("hint {} is not found", hint))?;
+            if clause.iter().any(|lit| self.value(lit) == Some(true)) {
+                return Err(format!("hint {} is satisfied", hint));
+            }
+            // 元の節には同じリテラルが重複していることがある
+            let mut unassigned: Vec<&Literal> = clause.iter().filter(|lit| self.value(lit).is_none()).collect();
+            unassigned.sort_unstable_by_key(|lit| lit.to_dimacs());
+            unassigned.dedup();
+            match unassigned[..] {
+                [] => return Ok(true),
+                [lit] => {
+                    self.values.insert(lit.var(), lit.is_pos());
+                },
+                _ => return Err(format!("hint {} is not unit", hint)),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// clause が db の節から hints で導けるか調べる
+/// 最初の負の hint までが clause の否定からの単位伝播 (RUP), それで矛盾しなければ
+/// clause[0] を pivot として, ¬pivot を含む全ての節 D について `-D の番号` に続く hints で
+/// clause ∪ (D \ {¬pivot}) の否定から矛盾を導く (RAT)
+fn check_step(db: &HashMap<u64, Clause>, clause: &Clause, hints: &[i64]) -> Result<(), String> {
+    let mut assignment = Assignment { values: HashMap::new() };
+    for lit in clause {
+        if !assignment.falsify(lit) {
+            // 恒真な節
+            return Ok(());
+        }
+    }
+    let k = hints.iter().position(|&hint| hint < 0).unwrap_or(hints.len());
+    if assignment.propagate(db, &hints[..k])? {
+        return Ok(());
+    }
+    let pivot = match clause.first() {
+        Some(&pivot) if k < hints.len() => pivot,
+        _ => return Err("no conflict by unit propagation".to_string()),
+    };
+
+    // RAT の各候補の hints
+    let mut groups: HashMap<u64, &[i64]> = HashMap::new();
+    let mut rest = &hints[k..];
+    while let Some((&head, tail)) = rest.split_first() {
+        let end = tail.iter().position(|&hint| hint < 0).unwrap_or(tail.len());
+        groups.insert(head.unsigned_abs(), &tail[..end]);
+        rest = &tail[end..];
+    }
+    let mut candidates: Vec<(&u64, &Clause)> = db.iter().filter(|(_, d)| d.contains(&pivot.not())).collect();
+    candidates.sort_unstable_by_key(|(&id, _)| id);
+    for (id, d) in candidates {
+        let group = groups.get(id).ok_or_else(|| format!("RAT candidate {} is not given", id))?;
+        let mut resolvent = Assignment { values: assignment.values.clone() };
+        if d.iter().filter(|&&lit| lit != pivot.not()).any(|lit| !resolvent.falsify(lit)) {
+            // 導出節が恒真
+            continue;
+        }
+        if !resolvent.propagate(db, group)? {
+            return Err(format!("no conflict for RAT candidate {}", id));
+        }
+    }
+    Ok(())
+}
+
+/// clauses に対する LRAT 形式の証明 proof を検査する, 元の節の番号は clauses の順に 1 から
+/// 削除は `<番号> d <節の番号> 0` の行, `c` から始まる行はコメント
+/// # Returns
+/// * `Ok(())` - 全ての行が正しく, 空節が導かれた
+/// * `Err(message)` - 最初に検査に失敗した行とその理由
+pub fn check_lrat(clauses: &Clauses, proof: &str) -> Result<(), String> {
+    let mut db: HashMap<u64, Clause> = clauses.iter()
+        .enumerate()
+        .map(|(i, clause)| (i as u64 + 1, clause.clone()))
+        .collect();
+    for (n, line) in proof.lines().enumerate() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() || tokens[0].starts_with('c') {
+            continue;
+        }
+        let error = |message: String| format!("line {}: {}", n + 1, message);
+        let id: u64 = tokens[0].parse().map_err(|_| error(format!("invalid clause id: {}", tokens[0])))?;
+        let numbers = |tokens: &[&str]| -> Result<(Vec<i64>, usize), String> {
+            let end = tokens.iter().position(|&token| token == "0").ok_or_else(|| error("missing 0".to_string()))?;
+            let numbers = tokens[..end].iter()
+                .map(|token| token.parse().map_err(|_| error(format!("invalid number: {}", token))))
+                .collect::<Result<Vec<i64>, String>>()?;
+            Ok((numbers, end + 1))
+        };
+
+        if tokens.get(1) == Some(&"d") {
+            let (ids, _) = numbers(&tokens[2..])?;
+            for id in ids {
+                let id = u64::try_from(id).map_err(|_| error(format!("invalid clause id: {}", id)))?;
+                db.remove(&id);
+            }
+            continue;
+        }
+
+        let (lits, end) = numbers(&tokens[1..])?;
+        let (hints, _) = numbers(&tokens[1 + end..])?;
+        if db.contains_key(&id) {
+            return Err(error(format!("clause {} already exists", id)));
+        }
+        // 0 は終端なので lits に現れない, i32::MIN は符号を反転できない
+        let clause: Clause = lits.iter()
+            .map(|&lit| match i32::try_from(lit) {
+                Ok(lit) if lit != i32::MIN => Ok(Literal::from_dimacs(lit)),
+                _ => Err(error(format!("literal out of range: {}", lit))),
+            })
+            .collect::<Result<Clause, String>>()?;
+        check_step(&db, &clause, &hints).map_err(|message| error(format!("clause {}: {}", id, message)))?;
+        if clause.is_empty() {
+            return Ok(());
+        }
+        db.insert(id, clause);
+    }
+    Err("the empty clause is not derived".to_string())
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::random::Random;
+    use crate::testing::{brute_force, random_clauses};
+
+    /// テストで書き出した証明を読むための, 共有したバッファ
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn solver_proof_is_verified() {
+        // x1 ∨ x2 の 4 通りの符号の節と余分な節, 探索で学習した節も証明に入る
+        let clauses: Clauses = [vec![3, 4], vec![1, 2], vec![1, -2], vec![-1, 2], vec![3, -4], vec![-1, -2]].into_iter()
+            .map(|clause| clause.into_iter().map(Literal::from_dimacs).collect())
+            .collect();
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut solver = Solver::new();
+        solver.set_proof(LratWriter::new(buffer.clone(), clauses.len()));
+        for clause in &clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        assert_eq!(solver.solve(), Some(false));
+        solver.flush_proof().unwrap();
+
+        let proof = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(check_lrat(&clauses, &proof), Ok(()));
+
+        // hint を落とすと単位伝播で矛盾しない
+        assert!(check_lrat(&clauses, "7 -1 0 2 0\n8 0 7 3 0\n").is_err());
+        assert_eq!(check_lrat(&clauses, "7 -1 0 4 6 0\n8 0 7 2 3 0\n"), Ok(()));
+    }
+
+    #[test]
+    fn proofs_of_random_instances_are_verified() {
+        // リスタートと学習節の削除を頻繁に起こしても, 学習節の hints で検査できる
+        let mut rng = Random::new(13);
+        let mut proofs = 0;
+        let mut deleted = 0;
+        while proofs < 100 {
+            let size_vars = 3 + rng.gen_range(8);
+            let clauses = random_clauses(&mut rng, size_vars, size_vars * 5, 3);
+            if !brute_force(size_vars, &clauses, &[]).is_empty() {
+                continue;
+            }
+            proofs += 1;
+            let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+            let mut solver = Solver::new();
+            solver.restart_first = 1 + rng.gen_range(3);
+            solver.reduce_first = rng.gen_range(4);
+            solver.set_proof(LratWriter::new(buffer.clone(), clauses.len()));
+            for clause in &clauses {
+                solver.add_clause(&mut clause.clone());
+            }
+            assert_eq!(solver.solve(), Some(false));
+            solver.flush_proof().unwrap();
+
+            let proof = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+            assert_eq!(check_lrat(&clauses, &proof), Ok(()), "{:?}\n{}", clauses, proof);
+            deleted += solver.stats.deleted_clauses;
+        }
+        assert!(deleted > 0);
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        let clauses: Clauses = vec![vec![Literal::from_dimacs(1)], vec![Literal::from_dimacs(-1)]];
+        // i32 に収まらないリテラルは切り捨てずにエラーにする
+        assert_eq!(check_lrat(&clauses, "3 4294967296 0 1 0\n"), Err("line 1: literal out of range: 4294967296".to_string()));
+        assert_eq!(check_lrat(&clauses, "3 -2147483648 0 1 0\n"), Err("line 1: literal out of range: -2147483648".to_string()));
+        // 負の番号の節は削除できない
+        assert_eq!(check_lrat(&clauses, "3 d -1 0\n"), Err("line 1: invalid clause id: -1".to_string()));
+        assert_eq!(check_lrat(&clauses, "c comment\n3 d 1 0\n4 0 1 2 0\n").map_err(|message| message.starts_with("line 3")), Err(true));
+        assert_eq!(check_lrat(&clauses, "3 0 1 2 0\n"), Ok(()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn proof_is_rejected_with_xors() {
+        let mut solver = Solver::new();
+        solver.add_xor(&[0, 1], true);
+        solver.set_proof(LratWriter::new(io::sink(), 0));
+    }
+}
@@ -2,34 +2,70 @@
 extern crate log;
 extern crate env_logger as logger;
 
-use std::{env, io::Read};
+use std::{env, io::{self, BufWriter, Read, Write}};
+use std::collections::HashMap;
 use std::fs::File;
+use std::process;
+use std::str::FromStr;
 use std::time::Instant;
 
 use three_sat_solver_practice_2_h::solver::{Stats, Solver};
-use three_sat_solver_practice_2_h::dimacs_parser::parse_dimacs;
+use three_sat_solver_practice_2_h::dimacs_parser::{parse_clauses, parse_dimacs, parse_model, parse_num_vars, parse_show_vars, parse_weights};
+use three_sat_solver_practice_2_h::dimacs_writer::{write_dimacs, write_dimacs_subset, write_icnf, write_model, write_projected_model};
+use three_sat_solver_practice_2_h::preprocess::ReconstructionStack;
+use three_sat_solver_practice_2_h::local_search::{Algorithm, LocalSearch};
+use three_sat_solver_practice_2_h::cube::conquer;
+use three_sat_solver_practice_2_h::portfolio::Portfolio;
+use three_sat_solver_practice_2_h::count::{Counter, Weight};
+use three_sat_solver_practice_2_h::approxmc::ApproxCounter;
+use three_sat_solver_practice_2_h::bigint::BigUint;
+use three_sat_solver_practice_2_h::rational::Rational;
+use three_sat_solver_practice_2_h::encodings::{CardEncoding, PbEncoding};
+use three_sat_solver_practice_2_h::opb::parse_opb;
+use three_sat_solver_practice_2_h::maxsat::{MaxSat, parse_wcnf};
+use three_sat_solver_practice_2_h::mus::MusExtractor;
+use three_sat_solver_practice_2_h::marco::{Marco, MarcoSet};
+use three_sat_solver_practice_2_h::lrat::{LratWriter, check_lrat};
+use three_sat_solver_practice_2_h::drat_check::check_drat;
 
 fn print_stats(stats: Stats, start_time: Instant) {
     let time = Instant::now().duration_since(start_time);
     let time = time.as_secs_f64();
+    println!("restarts      : {} ", stats.restarts);
+    println!("rephases      : {} ", stats.rephases);
     println!("conflicts     : {} ", stats.conflicts);
     println!("decisions     : {} ", stats.decisions);
+    println!("propagations  : {} ", stats.propagations);
+    println!("subsumed      : {} clauses", stats.subsumed_clauses);
+    println!("substituted   : {} vars", stats.substituted_vars);
+    println!("failed lits   : {} ", stats.failed_literals);
+    println!("eliminated    : {} vars, {} clauses", stats.eliminated_vars, stats.eliminated_clauses);
+    println!("vivified      : {} literals", stats.vivified_literals);
+    println!("learnt        : {} clauses, {} deleted", stats.learnt_clauses, stats.deleted_clauses);
+    println!("lookaheads    : {} ", stats.lookaheads);
+    println!("imported      : {} clauses", stats.imported_clauses);
+    println!("xors          : {} ", stats.xors);
     println!("CPU time      : {:.3} sec", time);
 }
 
-fn main() {
-    let start_time = Instant::now();
-
-    env::set_var("RUST_LOG", "info");
-    logger::init();
+fn read_file(path: &str) -> String {
+    let mut f = File::open(path).expect("file not found");
+    let mut data = String::new();
+    f.read_to_string(&mut data).expect("file reading error");
+    data
+}
 
-    let args: Vec<String> = env::args().collect();
+fn create_file(path: &str) -> BufWriter<File> {
+    BufWriter::new(File::create(path).expect("file creating error"))
+}
 
-    info!("input file: {}", &args[1]);
+/// 論理式を解いて結果を出力する
+/// lookahead が true なら先読みによる探索で解く
+/// threads が 2 以上なら, 設定を変えた threads 個の Solver で並列に解く, lookahead とは同時に使えない
+fn solve(input: &str, lookahead: bool, threads: usize, start_time: Instant) {
+    info!("input file: {}", input);
 
-    let mut f = File::open(&args[1]).expect("file not found");
-    let mut cnf_data = String::new();
-    f.read_to_string(&mut cnf_data).expect("file reading error");
+    let mut cnf_data = read_file(input);
 
     let mut solver = Solver::new();
     let st = parse_dimacs(cnf_data.as_mut_str(), &mut solver);
@@ -41,28 +77,852 @@ fn main() {
         return;
     }
 
-    let st = solver.solve().unwrap();
+    if threads > 1 {
+        let mut portfolio = Portfolio::new(threads);
+        let st = portfolio.solve(&solver.simplified_clauses()).unwrap();
+        info!("portfolio: solver {} of {} answered", portfolio.winner.unwrap(), threads);
+        print_stats(portfolio.stats, start_time);
+        if st {
+            print_model(&portfolio.model);
+        } else {
+            println!("UNSATISFIABLE");
+        }
+        return;
+    }
+
+    let st = if lookahead {
+        solver.solve_lookahead().unwrap()
+    } else {
+        solver.solve().unwrap()
+    };
 
     print_stats(solver.stats, start_time);
 
     if st {
-        println!("SATISFIABLE");
-        print!("Satisfying solution: ");
-        for (i, assign) in solver.model.iter().enumerate() {
-            let assign = match assign {
-                Some(assign) => *assign,
-                None => false,
-            };
-            let assign = if assign {
-                1
-            } else {
-                0
-            };
-            print!("x{}={} ", i, assign);
-        }
-        println!("");
+        print_model(&solver.model);
+    } else {
+        println!("UNSATISFIABLE");
+    }
+}
+
+/// 論理式を解き, 探索で導いた節を LRAT 形式 (drat が true なら DRAT 形式) の証明として書き出す
+/// 証明の元の節の番号は入力の節の順に 1 から, 前処理は行わない
+fn solve_with_proof(input: &str, proof_output: &str, drat: bool, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+    let num_clauses = parse_clauses(&cnf_data).len();
 
+    let mut solver = Solver::new();
+    let proof = if drat {
+        LratWriter::drat(create_file(proof_output))
+    } else {
+        LratWriter::new(create_file(proof_output), num_clauses)
+    };
+    solver.set_proof(proof);
+    let st = parse_dimacs(cnf_data.as_mut_str(), &mut solver) && solver.solve().unwrap();
+    solver.flush_proof().expect("file writing error");
+
+    print_stats(solver.stats, start_time);
+
+    if st {
+        print_model(&solver.model);
     } else {
         println!("UNSATISFIABLE");
     }
 }
+
+/// LRAT 形式の証明を検査して, 空節が導かれていれば `s VERIFIED` を出力する
+fn verify_lrat(input: &str, proof_input: &str, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let clauses = parse_clauses(&read_file(input));
+    let result = check_lrat(&clauses, &read_file(proof_input));
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c CPU time    : {:.3} sec", time);
+    match result {
+        Ok(()) => println!("s VERIFIED"),
+        Err(e) => {
+            println!("s NOT VERIFIED");
+            println!("c {}", e);
+        },
+    }
+}
+
+/// DRAT 形式 (テキストかバイナリ) の証明を後ろ向きに検査して, 矛盾が導かれていれば `s VERIFIED` を出力する
+fn verify_drat(input: &str, proof_input: &str, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let clauses = parse_clauses(&read_file(input));
+    let proof = std::fs::read(proof_input).expect("file reading error");
+    let result = check_drat(&clauses, &proof);
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c CPU time    : {:.3} sec", time);
+    match result {
+        Ok(()) => println!("s VERIFIED"),
+        Err(e) => {
+            println!("s NOT VERIFIED");
+            println!("c {}", e);
+        },
+    }
+}
+
+fn print_model(model: &[Option<bool>]) {
+    println!("SATISFIABLE");
+    print!("Satisfying solution: ");
+    for (i, assign) in model.iter().enumerate() {
+        let assign = match assign {
+            Some(assign) => *assign,
+            None => false,
+        };
+        let assign = if assign {
+            1
+        } else {
+            0
+        };
+        print!("x{}={} ", i, assign);
+    }
+    println!();
+}
+
+/// 局所探索で充足割り当てを探して結果を出力する
+/// 前処理をした論理式を探索し, モデルは元の論理式のものに直す
+fn local_search(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+
+    let mut solver = Solver::new();
+    if !parse_dimacs(cnf_data.as_mut_str(), &mut solver) || !solver.simplify() {
+        println!("UNSATISFIABLE");
+        return;
+    }
+
+    let seed = option_value(options, "seed").unwrap_or(0);
+    let mut searcher = LocalSearch::new(solver.size_vars(), &solver.simplified_clauses(), seed);
+    searcher.algorithm = option_choice(options, "local-search", &[
+        ("probsat", Algorithm::ProbSat),
+        ("walksat", Algorithm::WalkSat),
+    ]);
+    // --cb は probSAT の break 数に対する重みの指数, --noise は WalkSAT でランダムに選ぶ確率
+    match searcher.algorithm {
+        Algorithm::ProbSat => {
+            assert!(!options.contains_key("noise"), "--noise is only for --local-search=walksat, use --cb for probsat");
+            if let Some(cb) = option_value(options, "cb") {
+                searcher.cb = cb;
+            }
+        },
+        Algorithm::WalkSat => {
+            assert!(!options.contains_key("cb"), "--cb is only for --local-search=probsat, use --noise for walksat");
+            if let Some(noise) = option_value(options, "noise") {
+                searcher.noise = noise;
+            }
+        },
+    }
+    if let Some(max_flips) = option_value(options, "max-flips") {
+        searcher.max_flips = max_flips;
+    }
+    if let Some(max_tries) = option_value(options, "max-tries") {
+        searcher.max_tries = max_tries;
+    }
+
+    let st = searcher.solve();
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("tries         : {} ", searcher.tries);
+    println!("flips         : {} ", searcher.flips);
+    println!("CPU time      : {:.3} sec", time);
+
+    if st == Some(true) {
+        let mut model = searcher.model;
+        solver.reconstruction_stack().extend_model(&mut model);
+        print_model(&model);
+    } else {
+        println!("UNKNOWN");
+    }
+}
+
+/// 先読みで論理式を cube に分割し, 各 cube を仮定にして解いて結果を出力する
+fn cube_and_conquer(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+
+    let mut original = Solver::new();
+    if !parse_dimacs(cnf_data.as_mut_str(), &mut original) {
+        println!("UNSATISFIABLE");
+        return;
+    }
+    let clauses = original.simplified_clauses();
+
+    let mut solver = Solver::new();
+    for clause in &clauses {
+        solver.add_clause(&mut clause.clone());
+    }
+    let depth = option_value(options, "cube-depth").unwrap_or(10);
+    let cutoff = option_value(options, "cube-cutoff").unwrap_or(0);
+    let cubes = solver.cube(depth, cutoff);
+    info!("{} cubes", cubes.len());
+
+    if let Some(path) = options.get("icnf") {
+        let mut w = create_file(path);
+        write_icnf(&mut w, &clauses, &cubes).expect("file writing error");
+    }
+
+    let threads = option_value(options, "threads").unwrap_or(1);
+    let model = conquer(&clauses, &cubes, threads);
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("cubes         : {} ", cubes.len());
+    println!("CPU time      : {:.3} sec", time);
+
+    match model {
+        Some(model) => print_model(&model),
+        None => println!("UNSATISFIABLE"),
+    }
+}
+
+/// 問題行 `p cnf <変数の数> <節の数>` の数だけ変数を加えた Solver
+/// 節に現れない変数も列挙や出力の対象にする
+fn declared_solver(cnf_data: &str) -> Solver {
+    let num_vars = parse_num_vars(cnf_data).unwrap_or(0);
+    let mut solver = Solver::new();
+    while solver.size_vars() < num_vars {
+        solver.new_var();
+    }
+    solver
+}
+
+/// 充足割り当てを列挙して, 1つの解ごとに `v` から始まる行を出力する
+/// `--project=1,2,...` が与えられたら, その変数に射影した解を列挙する
+fn all_solutions(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+
+    let mut solver = declared_solver(&cnf_data);
+    if !parse_dimacs(cnf_data.as_mut_str(), &mut solver) {
+        println!("s UNSATISFIABLE");
+        return;
+    }
+
+    let max_solutions = option_value(options, "max-solutions").unwrap_or(usize::MAX);
+    let projection = option_vars(options, "project");
+
+    let mut out = io::stdout().lock();
+    let mut count = 0;
+    for model in solver.models(projection.as_deref()).take(max_solutions) {
+        if count == 0 {
+            writeln!(out, "s SATISFIABLE").expect("writing error");
+        }
+        match &projection {
+            Some(vars) => write_projected_model(&mut out, &model, vars),
+            None => write_model(&mut out, &model),
+        }.expect("writing error");
+        count += 1;
+    }
+    if count == 0 {
+        writeln!(out, "s UNSATISFIABLE").expect("writing error");
+    }
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    writeln!(out, "c solutions   : {} ", count).expect("writing error");
+    writeln!(out, "c CPU time    : {:.3} sec", time).expect("writing error");
+}
+
+/// DIMACS の入力からモデル数を数える Counter を作る, `c p weight` の重みは W として読む
+fn read_counter<W: Weight + FromStr>(cnf_data: &str) -> Counter<W> {
+    let mut counter = Counter::new(parse_num_vars(cnf_data).unwrap_or(0));
+    for mut clause in parse_clauses(cnf_data) {
+        counter.add_clause(&mut clause);
+    }
+    if let Some(vars) = parse_show_vars(cnf_data) {
+        counter.set_projection(&vars);
+    }
+    for (lit, weight) in parse_weights(cnf_data) {
+        let weight = weight.parse().unwrap_or_else(|_| panic!("invalid weight: {}", weight));
+        counter.set_weight(lit, weight);
+    }
+    counter
+}
+
+fn print_count_stats<W: Weight>(counter: &Counter<W>, start_time: Instant) {
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c decisions   : {} ", counter.decisions);
+    println!("c components  : {} ", counter.components);
+    println!("c cache hits  : {} ", counter.cache_hits);
+    println!("c CPU time    : {:.3} sec", time);
+}
+
+/// モデル数を数えて出力する
+/// `c p show` の行があれば, その変数に射影したモデル数を数える
+/// `c p weight` の行があれば重み付きモデル数を f64 で数える, `--precision=exact` なら有理数で厳密に数える
+fn count_models(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let cnf_data = read_file(input);
+
+    if parse_weights(&cnf_data).is_empty() {
+        let mut counter: Counter<BigUint> = read_counter(&cnf_data);
+        let count = counter.count();
+        print_count_stats(&counter, start_time);
+        println!("s mc {}", count);
+        return;
+    }
+
+    match options.get("precision").map(String::as_str) {
+        Some("exact") => {
+            let mut counter: Counter<Rational> = read_counter(&cnf_data);
+            let count = counter.count();
+            print_count_stats(&counter, start_time);
+            println!("c approx      : {:e}", count.to_f64());
+            println!("s wmc {}", count);
+        },
+        _ => {
+            let mut counter: Counter<f64> = read_counter(&cnf_data);
+            let count = counter.count();
+            print_count_stats(&counter, start_time);
+            println!("s wmc {:e}", count);
+        },
+    }
+}
+
+/// ランダムな XOR 制約を使ってモデル数を近似して出力する
+/// `c p show` の行があれば, その変数に射影したモデル数を近似する
+fn approx_count(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let cnf_data = read_file(input);
+
+    let seed = option_value(options, "seed").unwrap_or(0);
+    let mut counter = ApproxCounter::new(parse_num_vars(&cnf_data).unwrap_or(0), &parse_clauses(&cnf_data), seed);
+    if let Some(vars) = parse_show_vars(&cnf_data) {
+        counter.set_sampling_set(&vars);
+    }
+    if let Some(epsilon) = option_value(options, "epsilon") {
+        counter.epsilon = epsilon;
+    }
+    if let Some(delta) = option_value(options, "delta") {
+        counter.delta = delta;
+    }
+
+    let count = counter.count();
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c solver calls: {} ", counter.solver_calls);
+    println!("c CPU time    : {:.3} sec", time);
+    println!("s mc {}", count);
+}
+
+/// OPB 形式の擬似ブール制約の問題を解き, 目的関数があれば最小化して出力する
+/// 良い解が見つかるたびに `o <値>` の行を出力する
+fn solve_opb(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let problem = match parse_opb(&read_file(input)) {
+        Ok(problem) => problem,
+        Err(e) => {
+            println!("s UNSUPPORTED");
+            println!("c {}", e);
+            return;
+        },
+    };
+    let card = option_choice(options, "card-encoding", &[
+        ("totalizer", CardEncoding::Totalizer),
+        ("seq", CardEncoding::SequentialCounter),
+        ("network", CardEncoding::CardinalityNetwork),
+    ]);
+    let pb = option_choice(options, "pb-encoding", &[
+        ("gte", PbEncoding::GeneralizedTotalizer),
+        ("adder", PbEncoding::Adder),
+    ]);
+
+    let mut solver = Solver::new();
+    let result = match problem.encode(&mut solver, card, pb) {
+        Ok(true) => problem.minimize(&mut solver, |value| {
+            println!("o {}", value);
+            io::stdout().flush().expect("writing error");
+        }),
+        Ok(false) => None,
+        Err(e) => {
+            println!("s UNSUPPORTED");
+            println!("c {}", e);
+            return;
+        },
+    };
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c conflicts   : {} ", solver.stats.conflicts);
+    println!("c CPU time    : {:.3} sec", time);
+    match result {
+        Some((model, _)) => {
+            if problem.objective.is_some() {
+                println!("s OPTIMUM FOUND");
+            } else {
+                println!("s SATISFIABLE");
+            }
+            let values: Vec<String> = (0..problem.num_vars)
+                .map(|var| if model[var] == Some(true) { format!("x{}", var + 1) } else { format!("-x{}", var + 1) })
+                .collect();
+            println!("v {}", values.join(" "));
+        },
+        None => println!("s UNSATISFIABLE"),
+    }
+}
+
+/// WCNF 形式の MaxSAT の問題を core-guided な探索 (OLL) で解いて出力する
+/// 良い解が見つかるたびに `o <偽になる soft 節の重みの和>` の行を出力する
+fn solve_maxsat(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let wcnf = match parse_wcnf(&read_file(input)) {
+        Ok(wcnf) => wcnf,
+        Err(e) => {
+            println!("s UNKNOWN");
+            println!("c {}", e);
+            return;
+        },
+    };
+
+    let mut maxsat = MaxSat::new(&wcnf);
+    if options.contains_key("no-stratification") {
+        maxsat.use_stratification = false;
+    }
+    if options.contains_key("no-core-minimization") {
+        maxsat.minimize_cores = false;
+    }
+    let result = maxsat.solve(|cost| {
+        println!("o {}", cost);
+        io::stdout().flush().expect("writing error");
+    });
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c cores       : {} ", maxsat.cores);
+    println!("c solver calls: {} ", maxsat.solver_calls);
+    println!("c CPU time    : {:.3} sec", time);
+    match result {
+        Some((model, _)) => {
+            println!("s OPTIMUM FOUND");
+            let vars: Vec<usize> = (0..wcnf.num_vars).collect();
+            write_projected_model(&mut io::stdout().lock(), &model, &vars).expect("writing error");
+        },
+        None => println!("s UNSATISFIABLE"),
+    }
+}
+
+/// 探索で使った元の節 (UNSAT core) を, 元の論理式での節の番号を付けて DIMACS 形式で出力する
+/// MUS と違って極小とは限らないが, 1回解くだけで求まる
+fn unsat_core(input: &str, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+    let clauses = parse_clauses(&cnf_data);
+
+    let mut solver = Solver::new();
+    solver.track_core = true;
+    let result = if parse_dimacs(cnf_data.as_mut_str(), &mut solver) { solver.solve() } else { Some(false) };
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c conflicts   : {} ", solver.stats.conflicts);
+    println!("c CPU time    : {:.3} sec", time);
+    match result {
+        Some(true) => println!("s SATISFIABLE"),
+        Some(false) => {
+            println!("s UNSATISFIABLE");
+            let core = solver.unsat_core().expect("UNSAT core is not tracked");
+            println!("c core: {} of {} clauses", core.len(), clauses.len());
+            write_dimacs_subset(&mut io::stdout().lock(), solver.size_vars(), &clauses, &core).expect("writing error");
+        },
+        None => println!("s UNKNOWN"),
+    }
+}
+
+/// 節に selector を付けて MusExtractor に加える
+fn read_mus_extractor(cnf_data: &str, options: &HashMap<String, String>) -> (MusExtractor, usize) {
+    let clauses = parse_clauses(cnf_data);
+    let num_vars = clauses.iter().flatten()
+        .map(|lit| lit.var() + 1)
+        .fold(parse_num_vars(cnf_data).unwrap_or(0), usize::max);
+
+    let mut extractor = MusExtractor::new(num_vars);
+    if options.contains_key("no-refinement") {
+        extractor.use_refinement = false;
+    }
+    if options.contains_key("no-model-rotation") {
+        extractor.use_model_rotation = false;
+    }
+    for clause in &clauses {
+        extractor.add_clause(clause);
+    }
+    (extractor, num_vars)
+}
+
+/// 全ての解で真になるリテラル (backbone) を `b` から始まる行で出力する
+fn backbone(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+
+    let mut solver = declared_solver(&cnf_data);
+    if !parse_dimacs(cnf_data.as_mut_str(), &mut solver) {
+        println!("s UNSATISFIABLE");
+        return;
+    }
+    let chunk_size = option_value(options, "chunk-size").unwrap_or(20);
+    let projection = option_vars(options, "project");
+
+    let backbone = solver.backbone(projection.as_deref(), chunk_size);
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    match backbone {
+        Some(backbone) => {
+            println!("s SATISFIABLE");
+            let lits: Vec<String> = backbone.iter().map(|lit| lit.to_dimacs().to_string()).collect();
+            println!("b {} 0", lits.join(" "));
+            println!("c backbone    : {} ", backbone.len());
+        },
+        None => println!("s UNSATISFIABLE"),
+    }
+    println!("c CPU time    : {:.3} sec", time);
+}
+
+/// UNSAT な論理式の MUS を求め, 元の論理式での節の番号を付けて DIMACS 形式で出力する
+fn extract_mus(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let (mut extractor, num_vars) = read_mus_extractor(&read_file(input), options);
+    let mus = extractor.extract();
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    println!("c solver calls: {} ", extractor.solver_calls);
+    println!("c rotated     : {} ", extractor.rotated);
+    println!("c CPU time    : {:.3} sec", time);
+    match mus {
+        Some(mus) => {
+            println!("s UNSATISFIABLE");
+            println!("c mus: {} of {} clauses", mus.len(), extractor.clauses().len());
+            write_dimacs_subset(&mut io::stdout().lock(), num_vars, extractor.clauses(), &mus).expect("writing error");
+        },
+        None => println!("s SATISFIABLE"),
+    }
+}
+
+/// MARCO で MUS と MCS を列挙し, 見つけるたびに節の番号 (1 始まり) を出力する
+/// MUS は `U` から, MCS は `C` から始まる行にする
+fn enumerate_marco(input: &str, options: &HashMap<String, String>, start_time: Instant) {
+    info!("input file: {}", input);
+
+    let (extractor, _) = read_mus_extractor(&read_file(input), options);
+    let max_results = option_value(options, "max-results").unwrap_or(usize::MAX);
+
+    let mut marco = Marco::new(extractor);
+    let mut out = io::stdout().lock();
+    for set in marco.by_ref().take(max_results) {
+        let (kind, indices) = match set {
+            MarcoSet::Mus(mus) => ("U", mus),
+            MarcoSet::Mcs(mcs) => ("C", mcs),
+        };
+        let indices: Vec<String> = indices.iter().map(|i| (i + 1).to_string()).collect();
+        writeln!(out, "{} {}", kind, indices.join(" ")).expect("writing error");
+        out.flush().expect("writing error");
+    }
+
+    let time = Instant::now().duration_since(start_time).as_secs_f64();
+    writeln!(out, "c MUSes       : {} ", marco.muses).expect("writing error");
+    writeln!(out, "c MCSes       : {} ", marco.mcses).expect("writing error");
+    writeln!(out, "c CPU time    : {:.3} sec", time).expect("writing error");
+}
+
+/// 前処理だけ行い, 簡略化した論理式とモデルの復元に使うスタックを書き出す
+fn preprocess(input: &str, output: &str, stack_output: &str) {
+    info!("input file: {}", input);
+
+    let mut cnf_data = read_file(input);
+
+    let mut solver = Solver::new();
+    let mut w = create_file(output);
+    if !parse_dimacs(cnf_data.as_mut_str(), &mut solver) || !solver.simplify() {
+        // 空節だけの論理式にする
+        write_dimacs(&mut w, solver.size_vars(), &vec![Vec::new()]).expect("file writing error");
+        println!("UNSATISFIABLE");
+        return;
+    }
+
+    let clauses = solver.simplified_clauses();
+    write_dimacs(&mut w, solver.size_vars(), &clauses).expect("file writing error");
+    let mut w = create_file(stack_output);
+    solver.reconstruction_stack().write(solver.size_vars(), &mut w).expect("file writing error");
+
+    info!("simplified: {} clauses", clauses.len());
+}
+
+/// 簡略化した論理式のモデルから, 元の論理式のモデルを作る
+fn extend_model(model_input: &str, stack_input: &str) {
+    let (size_vars, stack) = ReconstructionStack::parse(&read_file(stack_input))
+        .expect("invalid reconstruction stack file");
+    let mut model = parse_model(&read_file(model_input), size_vars)
+        .expect("model not found");
+
+    stack.extend_model(&mut model);
+
+    println!("s SATISFIABLE");
+    write_model(&mut io::stdout().lock(), &model).expect("writing error");
+}
+
+/// コマンドラインで選ぶ動作
+struct Mode {
+    flags: &'static [&'static str],         // このいずれかのオプションがあればこの動作を選ぶ, 空なら既定の動作
+    extension: Option<&'static str>,        // 入力ファイルがこの拡張子ならオプションがなくても選ぶ
+    options: &'static [&'static str],       // flags の他に受け付けるオプション
+    inputs: usize,                          // オプション以外の引数の数
+    usage: &'static str,
+    run: fn(&[&str], &HashMap<String, String>, Instant),
+}
+
+/// 上から順に, 最初に選ばれた動作を行う
+const MODES: &[Mode] = &[
+    Mode {
+        flags: &["preprocess"],
+        extension: None,
+        options: &[],
+        inputs: 3,
+        usage: "--preprocess <input.cnf> <output.cnf> <stack>",
+        run: |inputs, _, _| preprocess(inputs[0], inputs[1], inputs[2]),
+    },
+    Mode {
+        flags: &["extend-model"],
+        extension: None,
+        options: &[],
+        inputs: 2,
+        usage: "--extend-model <model> <stack>",
+        run: |inputs, _, _| extend_model(inputs[0], inputs[1]),
+    },
+    Mode {
+        flags: &["check-lrat"],
+        extension: None,
+        options: &[],
+        inputs: 2,
+        usage: "--check-lrat <input.cnf> <proof.lrat>",
+        run: |inputs, _, start_time| verify_lrat(inputs[0], inputs[1], start_time),
+    },
+    Mode {
+        flags: &["check-drat"],
+        extension: None,
+        options: &[],
+        inputs: 2,
+        usage: "--check-drat <input.cnf> <proof.drat>",
+        run: |inputs, _, start_time| verify_drat(inputs[0], inputs[1], start_time),
+    },
+    Mode {
+        flags: &["all-solutions", "max-solutions"],
+        extension: None,
+        options: &["project"],
+        inputs: 1,
+        usage: "--all-solutions [--max-solutions=N] [--project=1,2,...] <input.cnf>",
+        run: |inputs, options, start_time| all_solutions(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["approx-count"],
+        extension: None,
+        options: &["epsilon", "delta", "seed"],
+        inputs: 1,
+        usage: "--approx-count [--epsilon=X] [--delta=X] [--seed=N] <input.cnf>",
+        run: |inputs, options, start_time| approx_count(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["count"],
+        extension: None,
+        options: &["precision"],
+        inputs: 1,
+        usage: "--count [--precision=f64|exact] <input.cnf>",
+        run: |inputs, options, start_time| count_models(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["opb"],
+        extension: Some(".opb"),
+        options: &["card-encoding", "pb-encoding"],
+        inputs: 1,
+        usage: "--opb [--card-encoding=totalizer|seq|network] [--pb-encoding=gte|adder] <input.opb>",
+        run: |inputs, options, start_time| solve_opb(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["maxsat"],
+        extension: Some(".wcnf"),
+        options: &["no-stratification", "no-core-minimization"],
+        inputs: 1,
+        usage: "--maxsat [--no-stratification] [--no-core-minimization] <input.wcnf>",
+        run: |inputs, options, start_time| solve_maxsat(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["backbone"],
+        extension: None,
+        options: &["chunk-size", "project"],
+        inputs: 1,
+        usage: "--backbone [--chunk-size=N] [--project=1,2,...] <input.cnf>",
+        run: |inputs, options, start_time| backbone(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["marco"],
+        extension: None,
+        options: &["max-results", "no-refinement", "no-model-rotation"],
+        inputs: 1,
+        usage: "--marco [--max-results=N] [--no-refinement] [--no-model-rotation] <input.cnf>",
+        run: |inputs, options, start_time| enumerate_marco(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["unsat-core"],
+        extension: None,
+        options: &[],
+        inputs: 1,
+        usage: "--unsat-core <input.cnf>",
+        run: |inputs, _, start_time| unsat_core(inputs[0], start_time),
+    },
+    Mode {
+        flags: &["mus"],
+        extension: None,
+        options: &["no-refinement", "no-model-rotation"],
+        inputs: 1,
+        usage: "--mus [--no-refinement] [--no-model-rotation] <input.cnf>",
+        run: |inputs, options, start_time| extract_mus(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["cube"],
+        extension: None,
+        options: &["cube-depth", "cube-cutoff", "icnf", "threads"],
+        inputs: 1,
+        usage: "--cube [--cube-depth=N] [--cube-cutoff=N] [--icnf=<output.icnf>] [--threads=N] <input.cnf>",
+        run: |inputs, options, start_time| cube_and_conquer(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["local-search"],
+        extension: None,
+        options: &["seed", "cb", "noise", "max-flips", "max-tries"],
+        inputs: 1,
+        usage: "--local-search[=probsat|walksat] [--seed=N] [--cb=X (probsat) | --noise=X (walksat)] [--max-flips=N] [--max-tries=N] <input.cnf>",
+        run: |inputs, options, start_time| local_search(inputs[0], options, start_time),
+    },
+    Mode {
+        flags: &["lrat"],
+        extension: None,
+        options: &[],
+        inputs: 1,
+        usage: "--lrat=<output.lrat> <input.cnf>",
+        run: |inputs, options, start_time| solve_with_proof(inputs[0], &option_path(options, "lrat"), false, start_time),
+    },
+    Mode {
+        flags: &["drat"],
+        extension: None,
+        options: &[],
+        inputs: 1,
+        usage: "--drat=<output.drat> <input.cnf>",
+        run: |inputs, options, start_time| solve_with_proof(inputs[0], &option_path(options, "drat"), true, start_time),
+    },
+    Mode {
+        flags: &[],
+        extension: None,
+        options: &["lookahead", "threads"],
+        inputs: 1,
+        usage: "[--lookahead | --threads=N] <input.cnf>",
+        run: |inputs, options, start_time| {
+            let threads = option_value(options, "threads").unwrap_or(1);
+            // 並列に解く Solver は先読みによる探索を使わない
+            assert!(threads <= 1 || !options.contains_key("lookahead"), "--lookahead cannot be used with --threads=N (N > 1)");
+            solve(inputs[0], options.contains_key("lookahead"), threads, start_time)
+        },
+    },
+];
+
+impl Mode {
+    fn is_selected(&self, options: &HashMap<String, String>, inputs: &[&str]) -> bool {
+        self.flags.iter().any(|flag| options.contains_key(*flag))
+            || self.extension.is_some_and(|extension| inputs.first().is_some_and(|input| input.ends_with(extension)))
+            || self.flags.is_empty()
+    }
+
+    /// この動作で受け付けないオプション
+    fn unknown_option<'a>(&self, options: &'a HashMap<String, String>) -> Option<&'a str> {
+        options.keys()
+            .map(String::as_str)
+            .find(|key| !self.flags.contains(key) && !self.options.contains(key))
+    }
+}
+
+fn print_usage(program: &str) {
+    for (i, mode) in MODES.iter().enumerate() {
+        let prefix = if i == 0 { "usage:" } else { "      " };
+        eprintln!("{} {} {}", prefix, program, mode.usage);
+    }
+}
+
+/// `--key=value` または `--key` の形のオプションの値を読む
+fn option_value<T: FromStr>(options: &HashMap<String, String>, key: &str) -> Option<T> {
+    options.get(key).map(|value| value.parse().unwrap_or_else(|_| {
+        panic!("invalid value for --{}: {}", key, value)
+    }))
+}
+
+/// `--key=name` の形の選択肢を読む, オプションがないか値がなければ choices の最初のもの
+fn option_choice<T: Copy>(options: &HashMap<String, String>, key: &str, choices: &[(&str, T)]) -> T {
+    match options.get(key) {
+        Some(value) if !value.is_empty() => match choices.iter().find(|(name, _)| name == value) {
+            Some(&(_, choice)) => choice,
+            None => panic!("invalid value for --{}: {}", key, value),
+        },
+        _ => choices[0].1,
+    }
+}
+
+/// `--key=<path>` の形のファイル名を読む
+fn option_path(options: &HashMap<String, String>, key: &str) -> String {
+    match options.get(key) {
+        Some(path) if !path.is_empty() => path.clone(),
+        _ => panic!("missing file name for --{}", key),
+    }
+}
+
+/// `--key=1,2,...` の形の変数 (1 始まり) の一覧を読む
+fn option_vars(options: &HashMap<String, String>, key: &str) -> Option<Vec<usize>> {
+    options.get(key).map(|vars| {
+        vars.split(',')
+            .map(|var| match var.trim().parse::<usize>() {
+                Ok(var) if var > 0 => var - 1,
+                _ => panic!("invalid value for --{}: {}", key, vars),
+            })
+            .collect()
+    })
+}
+
+fn main() {
+    let start_time = Instant::now();
+
+    env::set_var("RUST_LOG", "info");
+    logger::init();
+
+    let args: Vec<String> = env::args().collect();
+
+    // `--key=value` の形のオプションとそれ以外の引数に分ける
+    let mut options: HashMap<String, String> = HashMap::new();
+    let mut inputs: Vec<&str> = Vec::new();
+    for arg in &args[1..] {
+        if let Some(option) = arg.strip_prefix("--") {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            options.insert(key.to_string(), value.to_string());
+        } else {
+            inputs.push(arg);
+        }
+    }
+
+    let mode = MODES.iter().find(|mode| mode.is_selected(&options, &inputs)).unwrap();
+    if let Some(key) = mode.unknown_option(&options) {
+        eprintln!("unknown option: --{}", key);
+        print_usage(&args[0]);
+        process::exit(2);
+    }
+    if inputs.len() != mode.inputs {
+        print_usage(&args[0]);
+        process::exit(2);
+    }
+    (mode.run)(&inputs, &options, start_time);
+}
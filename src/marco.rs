@@ -0,0 +1,147 @@
+use log::info;
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::mus::MusExtractor;
+use crate::solver::Solver;
+
+/// MARCO が見つけた節の集合, 中身は節の番号の昇順
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarcoSet {
+    /// 極小な UNSAT 部分集合
+    Mus(Vec<usize>),
+    /// 極小な修正集合, 除くと残りが SAT になる
+    Mcs(Vec<usize>),
+}
+
+/// MARCO (Liffiton, Malik 2013) で MUS と MCS を見つけた順に返すイテレータ
+/// map solver の変数 i が真なら節 i を含む集合 (seed) として, まだ調べていない seed を map solver で選ぶ
+/// seed が SAT なら極大な SAT 部分集合まで広げて補集合の MCS を返し, UNSAT なら MUS まで縮めて返す
+/// MCS のどれかを含む集合と, MUS を含まない集合だけを以後の seed にする
+pub struct Marco {
+    subset: MusExtractor,   // 節の部分集合が SAT かを調べる
+    map: Solver,            // まだ調べていない部分集合を表す
+    size: usize,            // 節の数
+
+    // ログ等
+    pub muses: usize,
+    pub mcses: usize,
+}
+
+impl Marco {
+    /// 節を加えた MusExtractor を使って列挙する, shrink の設定はそのまま使う
+    /// 論理式全体が SAT なら, 空の MCS を1つだけ返す
+    pub fn new(subset: MusExtractor) -> Self {
+        let size = subset.clauses().len();
+        let mut map = Solver::new();
+        map.use_rephase = false;
+        // なるべく大きな seed から調べる
+        map.initial_phase = true;
+        for i in 0..size {
+            map.new_var();
+            // 解くたびに節を加えるので, 前処理で除去されないようにしておく
+            map.freeze(i);
+        }
+        for i in (0..size).filter(|&i| subset.is_tautology(i)) {
+            map.add_clause(&mut vec![Literal::Pos(i)]);
+        }
+        Marco {
+            subset,
+            map,
+            size,
+
+            muses: 0,
+            mcses: 0,
+        }
+    }
+
+    /// SAT な seed を, 加えても SAT のままの節を全て加えた極大な集合にする
+    fn grow(&mut self, seed: Vec<usize>) -> Vec<bool> {
+        let mut included = vec![false; self.size];
+        for i in seed {
+            included[i] = true;
+        }
+        self.include_satisfied(&mut included);
+        for i in 0..self.size {
+            if included[i] {
+                continue;
+            }
+            included[i] = true;
+            let subset: Vec<usize> = (0..self.size).filter(|&j| included[j]).collect();
+            if self.subset.solve_subset(&subset) {
+                self.include_satisfied(&mut included);
+            } else {
+                included[i] = false;
+            }
+        }
+        included
+    }
+
+    /// 直前に見つけた解で真になる節を全て加える
+    fn include_satisfied(&self, included: &mut [bool]) {
+        let model = self.subset.model();
+        for (i, clause) in self.subset.clauses().iter().enumerate() {
+            if clause.iter().any(|lit| model[lit.var()] == Some(lit.is_pos())) {
+                included[i] = true;
+            }
+        }
+    }
+}
+
+impl Iterator for Marco {
+    type Item = MarcoSet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.map.solve() != Some(true) {
+            info!("marco: {} MUSes, {} MCSes, {} solver calls", self.muses, self.mcses, self.subset.solver_calls);
+            return None;
+        }
+        // 割り当てのない変数は真として, なるべく大きな seed にする
+        let seed: Vec<usize> = (0..self.size).filter(|&i| self.map.model[i] != Some(false)).collect();
+
+        if self.subset.solve_subset(&seed) {
+            let included = self.grow(seed);
+            let mcs: Vec<usize> = (0..self.size).filter(|&i| !included[i]).collect();
+            // 以後の seed は MCS の節を1つは含む
+            let mut blocking: Clause = mcs.iter().map(|&i| Literal::Pos(i)).collect();
+            self.map.add_clause(&mut blocking);
+            self.mcses += 1;
+            Some(MarcoSet::Mcs(mcs))
+        } else {
+            let core = if self.subset.use_refinement { self.subset.failed_clauses() } else { seed };
+            let mus = self.subset.shrink(&core);
+            // 以後の seed は MUS の節を全ては含まない
+            let mut blocking: Clause = mus.iter().map(|&i| Literal::Neg(i)).collect();
+            self.map.add_clause(&mut blocking);
+            self.muses += 1;
+            Some(MarcoSet::Mus(mus))
+        }
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enumerate_muses_and_mcses() {
+        // x1, ¬x1, x2, ¬x2 の MUS は {0, 1}, {2, 3}, MCS はそれぞれから1つずつ選んだ 4 つ
+        let mut extractor = MusExtractor::new(2);
+        for lit in [1, -1, 2, -2] {
+            extractor.add_clause(&vec![Literal::from_dimacs(lit)]);
+        }
+        let mut muses = Vec::new();
+        let mut mcses = Vec::new();
+        for set in Marco::new(extractor) {
+            match set {
+                MarcoSet::Mus(mus) => muses.push(mus),
+                MarcoSet::Mcs(mcs) => mcses.push(mcs),
+            }
+        }
+        muses.sort();
+        mcses.sort();
+        assert_eq!(muses, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(mcses, vec![vec![0, 2], vec![0, 3], vec![1, 2], vec![1, 3]]);
+    }
+}
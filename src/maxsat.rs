@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use log::{debug, info};
+
+use crate::clause::Clause;
+use crate::encodings::totalizer;
+use crate::literal::Literal;
+use crate::solver::{Clauses, Solver};
+
+/// WCNF 形式の MaxSAT の問題, hard 節を全て満たす中で偽になる soft 節の重みの和を最小化する
+#[derive(Debug, Clone)]
+pub struct Wcnf {
+    pub num_vars: usize,
+    pub hard: Clauses,
+    pub soft: Vec<(u64, Clause)>,
+}
+
+/// WCNF 形式を読む
+/// `p wcnf <変数の数> <節の数> [top]` の行がある古い形式では, 重みが top 以上の節を hard 節とする
+/// 2022 年からの形式では `h` から始まる行が hard 節, 重みから始まる行が soft 節になる
+pub fn parse_wcnf(data: &str) -> Result<Wcnf, String> {
+    let mut num_vars = 0;
+    let mut top: Option<u64> = None;
+    let mut hard = Vec::new();
+    let mut soft = Vec::new();
+    for line in data.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() || tokens[0].starts_with('c') {
+            continue;
+        }
+        if tokens[0] == "p" {
+            if tokens.get(1) != Some(&"wcnf") {
+                return Err(format!("invalid problem line: {}", line));
+            }
+            num_vars = tokens.get(2).and_then(|n| n.parse().ok()).ok_or_else(|| format!("invalid problem line: {}", line))?;
+            top = tokens.get(4).and_then(|n| n.parse().ok());
+            continue;
+        }
+
+        let weight: Option<u64> = match tokens[0] {
+            "h" => None,
+            w => Some(w.parse().map_err(|_| format!("invalid weight: {}", line))?),
+        };
+        let mut clause: Clause = Vec::new();
+        for token in &tokens[1..] {
+            let lit: i32 = token.parse().map_err(|_| format!("invalid literal: {}", line))?;
+            if lit == 0 {
+                break;
+            }
+            clause.push(Literal::from_dimacs(lit));
+        }
+        num_vars = clause.iter().map(|lit| lit.var() + 1).fold(num_vars, usize::max);
+        match weight {
+            Some(w) if top.is_none_or(|top| w < top) => soft.push((w, clause)),
+            _ => hard.push(clause),
+        }
+    }
+    Ok(Wcnf { num_vars, hard, soft })
+}
+
+/// これまでに見つけた最良の解
+struct Best {
+    cost: u64,
+    model: Option<Vec<Option<bool>>>,
+}
+
+/// OLL (Morgado, Dodaro, Marques-Silva 2014) による core-guided な MaxSAT ソルバ
+/// soft 節に緩和変数を加え, 緩和変数が偽であることを仮定して解く
+/// 仮定の下で UNSAT なら, final conflict (core) の仮定の重みの最小値だけ下界を上げ,
+/// core の中で偽になる仮定の数を totalizer で数えて「2つ以上偽にならない」を新しい仮定にする
+pub struct MaxSat {
+    solver: Solver,
+    num_vars: usize,
+    soft: Vec<(u64, Clause)>,
+    weights: HashMap<Literal, u64>,             // 仮定するリテラルと, それが偽になったときの残りの重み
+    outputs: HashMap<Literal, (usize, usize)>,  // 仮定 ¬o が totalizers[t] の [j] の否定なら (t, j)
+    totalizers: Vec<Vec<Literal>>,              // core ごとの, 偽になった仮定の数の単進数
+    ok: bool,                                   // false なら hard 節が矛盾している
+
+    pub use_stratification: bool,               // 重みの大きい仮定から順に加えて解くか
+    pub minimize_cores: bool,                   // 仮定を1つずつ外して core を極小にするか
+
+    // ログ等
+    pub lower_bound: u64,
+    pub cores: usize,
+    pub solver_calls: usize,
+}
+
+impl MaxSat {
+    pub fn new(wcnf: &Wcnf) -> Self {
+        let mut solver = Solver::new();
+        // 仮定を変えて何度も解くので, 毎回の局所探索の負担の方が大きい
+        solver.use_rephase = false;
+        let mut ok = true;
+        for clause in &wcnf.hard {
+            ok &= solver.add_clause(&mut clause.clone());
+        }
+        while solver.size_vars() < wcnf.num_vars {
+            solver.new_var();
+        }
+
+        let mut weights: HashMap<Literal, u64> = HashMap::new();
+        let mut lower_bound = 0;
+        for (weight, clause) in &wcnf.soft {
+            match clause.len() {
+                _ if *weight == 0 => {},
+                0 => lower_bound += weight,
+                // 単位節はリテラルそのものを仮定にする
+                1 => *weights.entry(clause[0]).or_default() += weight,
+                _ => {
+                    let relax = Literal::Pos(solver.new_var());
+                    let mut relaxed = clause.clone();
+                    relaxed.push(relax);
+                    ok &= solver.add_clause(&mut relaxed);
+                    *weights.entry(relax.not()).or_default() += weight;
+                },
+            }
+        }
+
+        // 後から仮定に加える変数も, 前処理で除去されないようにしておく
+        for lit in weights.keys() {
+            solver.freeze(lit.var());
+        }
+
+        MaxSat {
+            solver,
+            num_vars: wcnf.num_vars,
+            soft: wcnf.soft.clone(),
+            weights,
+            outputs: HashMap::new(),
+            totalizers: Vec::new(),
+            ok,
+
+            use_stratification: true,
+            minimize_cores: true,
+
+            lower_bound,
+            cores: 0,
+            solver_calls: 0,
+        }
+    }
+
+    /// model で偽になる soft 節の重みの和
+    pub fn cost(&self, model: &[Option<bool>]) -> u64 {
+        self.soft.iter()
+            .filter(|(_, clause)| !clause.iter().any(|lit| model.get(lit.var()).copied().flatten().unwrap_or(false) == lit.is_pos()))
+            .map(|&(weight, _)| weight)
+            .sum()
+    }
+
+    /// 重みが threshold 以上の仮定, 重みの大きい順
+    fn assumptions(&self, threshold: u64) -> Vec<Literal> {
+        let mut assumptions: Vec<(u64, Literal)> = self.weights.iter()
+            .filter(|&(_, &weight)| weight > 0 && weight >= threshold)
+            .map(|(&lit, &weight)| (weight, lit))
+            .collect();
+        assumptions.sort_unstable_by(|a, b| b.cmp(a));
+        assumptions.into_iter().map(|(_, lit)| lit).collect()
+    }
+
+    /// 残っている仮定の重みのうち, threshold 未満で最大のもの
+    fn next_threshold(&self, threshold: u64) -> Option<u64> {
+        self.weights.values().copied().filter(|&weight| weight > 0 && weight < threshold).max()
+    }
+
+    fn solve_with(&mut self, assumptions: &[Literal]) -> bool {
+        self.solver_calls += 1;
+        self.solver.solve_with_assumptions(assumptions) == Some(true)
+    }
+
+    /// 直前に見つけた解が best より良ければ置き換えて, その重みの和を report に渡す
+    fn update(&self, best: &mut Best, report: &mut dyn FnMut(u64)) {
+        let cost = self.cost(&self.solver.model);
+        if cost < best.cost {
+            best.cost = cost;
+            best.model = Some(self.solver.model.clone());
+            report(cost);
+        }
+    }
+
+    /// 仮定を1つずつ外して解き, UNSAT のままなら外したままにする
+    /// 外して SAT になった場合の解は上界の候補にする
+    fn minimize_core(&mut self, core: Vec<Literal>, best: &mut Best, report: &mut dyn FnMut(u64)) -> Vec<Literal> {
+        let mut core = core;
+        let mut k = 0;
+        while k < core.len() && core.len() > 1 {
+            let mut rest = core.clone();
+            rest.remove(k);
+            if self.solve_with(&rest) {
+                self.update(best, report);
+                k += 1;
+            } else {
+                // rest の中の final conflict に縮める
+                let failed = self.solver.failed_assumptions().to_vec();
+                core = rest.into_iter().filter(|lit| failed.contains(lit)).collect();
+            }
+        }
+        core
+    }
+
+    /// core の重みの最小値だけ下界を上げ, core の仮定を緩和する
+    fn relax(&mut self, core: &[Literal]) {
+        self.cores += 1;
+        let w_min = core.iter().map(|lit| self.weights[lit]).min().unwrap();
+        self.lower_bound += w_min;
+        for lit in core {
+            *self.weights.get_mut(lit).unwrap() -= w_min;
+            // totalizer の出力が core に入ったら, 上限を1つ緩めた出力を仮定に加える
+            if let Some(&(t, j)) = self.outputs.get(lit) {
+                if j + 1 < self.totalizers[t].len() {
+                    let next = self.totalizers[t][j + 1].not();
+                    *self.weights.entry(next).or_default() += w_min;
+                    self.outputs.insert(next, (t, j + 1));
+                }
+            }
+        }
+        if core.len() > 1 {
+            // core の仮定の1つは必ず偽になるので, 2つ以上偽にならないことを仮定にする
+            let violated: Vec<Literal> = core.iter().map(|lit| lit.not()).collect();
+            let outputs = totalizer(&mut self.solver, &violated, core.len());
+            let next = outputs[1].not();
+            *self.weights.entry(next).or_default() += w_min;
+            self.outputs.insert(next, (self.totalizers.len(), 1));
+            self.totalizers.push(outputs);
+        }
+        debug!("core: {} literals, weight {}, lower bound {}", core.len(), w_min, self.lower_bound);
+    }
+
+    /// 最適解を求める, 良い解が見つかるたびにその重みの和を report に渡す
+    /// # Returns
+    /// * `Some((model, cost))` - 最適解と, 偽になる soft 節の重みの和
+    /// * `None` - hard 節を満たす解がない
+    pub fn solve(&mut self, mut report: impl FnMut(u64)) -> Option<(Vec<Option<bool>>, u64)> {
+        if !self.ok {
+            return None;
+        }
+        let mut best = Best {
+            cost: u64::MAX,
+            model: None,
+        };
+
+        let mut threshold = if self.use_stratification {
+            self.weights.values().copied().max().unwrap_or(0)
+        } else {
+            1
+        };
+        loop {
+            let assumptions = self.assumptions(threshold);
+            if self.solve_with(&assumptions) {
+                self.update(&mut best, &mut report);
+                // 全ての仮定を満たす解は下界と同じ重みになる
+                match self.next_threshold(threshold) {
+                    Some(next) if best.cost > self.lower_bound => threshold = next,
+                    _ => break,
+                }
+                continue;
+            }
+
+            let mut core = self.solver.failed_assumptions().to_vec();
+            if self.minimize_cores && !core.is_empty() {
+                core = self.minimize_core(core, &mut best, &mut report);
+            }
+            if core.is_empty() {
+                // hard 節だけで UNSAT
+                self.ok = false;
+                return None;
+            }
+            self.relax(&core);
+            if best.cost == self.lower_bound {
+                break;
+            }
+        }
+        info!("maxsat: {} cores, lower bound {}, {} solver calls", self.cores, self.lower_bound, self.solver_calls);
+
+        let model: Vec<Option<bool>> = best.model?.into_iter().take(self.num_vars).collect();
+        Some((model, best.cost))
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, is_model, random_clauses, satisfies};
+
+    #[test]
+    fn solve_small_wcnf() {
+        // x1 ∨ x2 が hard, ¬x1 (重み 3), ¬x2 (重み 2), x1 ∨ ¬x2 (重み 4)
+        let data = "c 2022 形式\nh 1 2 0\n3 -1 0\n2 -2 0\n4 1 -2 0\n";
+        let wcnf = parse_wcnf(data).unwrap();
+        assert_eq!((wcnf.num_vars, wcnf.hard.len(), wcnf.soft.len()), (2, 1, 3));
+        let mut maxsat = MaxSat::new(&wcnf);
+        let mut reported = Vec::new();
+        let (model, cost) = maxsat.solve(|cost| reported.push(cost)).unwrap();
+        // x1 = 1, x2 = 0 で ¬x1 だけが偽になる
+        assert_eq!(cost, 3);
+        assert_eq!(model, vec![Some(true), Some(false)]);
+        assert_eq!(reported.last(), Some(&3));
+
+        let old = parse_wcnf("p wcnf 2 4 10\n10 1 2 0\n3 -1 0\n2 -2 0\n4 1 -2 0\n").unwrap();
+        assert_eq!((old.hard.len(), old.soft.len()), (1, 3));
+    }
+
+    #[test]
+    fn optimal_cost_matches_brute_force() {
+        let mut rng = Random::new(8);
+        for _ in 0..200 {
+            let size_vars = 2 + rng.gen_range(7);
+            let (size_hard, size_soft, k) = (rng.gen_range(size_vars * 2), 1 + rng.gen_range(size_vars * 2), 1 + rng.gen_range(2));
+            let hard = random_clauses(&mut rng, size_vars, size_hard, 3);
+            let soft: Vec<(u64, Clause)> = random_clauses(&mut rng, size_vars, size_soft, k)
+                .into_iter()
+                .map(|clause| (1 + rng.gen_range(5) as u64, clause))
+                .collect();
+            let wcnf = Wcnf {
+                num_vars: size_vars,
+                hard: hard.clone(),
+                soft: soft.clone(),
+            };
+            let expected = brute_force(size_vars, &hard, &[]).into_iter()
+                .map(|bits| soft.iter().filter(|(_, clause)| !satisfies(bits, clause)).map(|&(weight, _)| weight).sum::<u64>())
+                .min();
+
+            for use_stratification in [false, true] {
+                for minimize_cores in [false, true] {
+                    let mut maxsat = MaxSat::new(&wcnf);
+                    maxsat.use_stratification = use_stratification;
+                    maxsat.minimize_cores = minimize_cores;
+                    let result = maxsat.solve(|_| {});
+                    assert_eq!(result.as_ref().map(|(_, cost)| *cost), expected, "{:?}", wcnf);
+                    if let Some((model, cost)) = result {
+                        assert!(is_model(&hard, &model));
+                        assert_eq!(maxsat.cost(&model), cost);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{debug, info};
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::{Clauses, Solver};
+
+/// MUS を求める途中での各節の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Unknown,
+    Necessary,  // MUS に必ず含まれる
+    Removed,    // 除いても UNSAT のまま
+}
+
+/// UNSAT な論理式の極小な UNSAT 部分集合 (MUS) を求める
+/// 各節 C_i に selector s_i を付けて C_i ∨ ¬s_i として加え, s_i を仮定にすることで節を出し入れする
+/// 節を1つずつ除いて解き (deletion-based),
+/// UNSAT なら final conflict に現れない節もまとめて除き (clause-set refinement),
+/// SAT ならその節は MUS に必要で, 解の変数を1つ反転して他に必要な節を探す (model rotation)
+pub struct MusExtractor {
+    solver: Solver,
+    clauses: Clauses,
+    selectors: Vec<Literal>,                    // 各節の selector, 真と仮定すると節が有効になる
+    selected: HashMap<usize, usize>,            // selector の変数から節の番号
+    occurs: HashMap<Literal, Vec<usize>>,       // リテラルを含む節の番号
+
+    pub use_refinement: bool,                   // UNSAT のときに final conflict で節を絞るか
+    pub use_model_rotation: bool,               // SAT のときに model rotation で必要な節を探すか
+
+    // ログ等
+    pub solver_calls: usize,
+    pub rotated: usize,                         // model rotation で見つけた必要な節の数
+}
+
+impl MusExtractor {
+    /// 変数が num_vars 個の論理式の MUS を求める, selector は num_vars 番目以降の変数にする
+    pub fn new(num_vars: usize) -> Self {
+        let mut solver = Solver::new();
+        // 仮定を変えて何度も解くので, 毎回の局所探索の負担の方が大きい
+        solver.use_rephase = false;
+        while solver.size_vars() < num_vars {
+            solver.new_var();
+        }
+        MusExtractor {
+            solver,
+            clauses: Vec::new(),
+            selectors: Vec::new(),
+            selected: HashMap::new(),
+            occurs: HashMap::new(),
+
+            use_refinement: true,
+            use_model_rotation: true,
+
+            solver_calls: 0,
+            rotated: 0,
+        }
+    }
+
+    /// 節に selector を付けて加える, 節の変数は new に渡した変数の数より小さいこと
+    /// # Returns
+    /// * 節の番号, 加えた順に 0 から
+    pub fn add_clause(&mut self, clause: &Clause) -> usize {
+        let i = self.clauses.len();
+        let selector = Literal::Pos(self.solver.new_var());
+        assert!(clause.iter().all(|lit| lit.var() < selector.var()), "variable out of range: {:?}", clause);
+        let mut guarded = clause.clone();
+        guarded.push(selector.not());
+        self.solver.add_clause(&mut guarded);
+        // selector は後から仮定に加えるので, 前処理で除去されないようにしておく
+        self.solver.freeze(selector.var());
+
+        for &lit in clause {
+            self.occurs.entry(lit).or_default().push(i);
+        }
+        self.clauses.push(clause.clone());
+        self.selectors.push(selector);
+        self.selected.insert(selector.var(), i);
+        i
+    }
+
+    pub fn clauses(&self) -> &Clauses {
+        &self.clauses
+    }
+
+    /// subset の節だけを有効にして解く
+    /// # Returns
+    /// * `true` - SAT, 解は model で得られる
+    /// * `false` - UNSAT, final conflict は failed_clauses で得られる
+    pub fn solve_subset(&mut self, subset: &[usize]) -> bool {
+        self.solver_calls += 1;
+        let assumptions: Vec<Literal> = subset.iter().map(|&i| self.selectors[i]).collect();
+        self.solver.solve_with_assumptions(&assumptions) == Some(true)
+    }
+
+    /// 直前の solve_subset が SAT のときの解
+    pub fn model(&self) -> &[Option<bool>] {
+        &self.solver.model
+    }
+
+    /// 直前の solve_subset が UNSAT のときに, 同時に有効にすると UNSAT になる節 (final conflict)
+    pub fn failed_clauses(&self) -> Vec<usize> {
+        self.solver.failed_assumptions().iter().map(|lit| self.selected[&lit.var()]).collect()
+    }
+
+    /// 恒真な節, MUS には含まれない
+    pub fn is_tautology(&self, i: usize) -> bool {
+        self.clauses[i].iter().any(|lit| self.clauses[i].contains(&lit.not()))
+    }
+
+    /// 直前の UNSAT の final conflict に現れない節を除く
+    /// # Returns
+    /// * 除いた節
+    fn refine(&self, unknown: &mut Vec<usize>, status: &mut [Status]) -> Vec<usize> {
+        let failed: HashSet<usize> = self.failed_clauses().into_iter().collect();
+        let (rest, removed): (Vec<usize>, Vec<usize>) = unknown.iter().partition(|i| failed.contains(i));
+        for &i in &removed {
+            status[i] = Status::Removed;
+        }
+        *unknown = rest;
+        removed
+    }
+
+    /// model で偽になる節が clause だけのとき, clause の変数を1つ反転して偽になる節が1つだけなら,
+    /// その節も MUS に必要になる, 見つけた節からさらに同じことを繰り返す
+    /// # Returns
+    /// * 新たに必要だと分かった節
+    fn rotate(&self, model: &[Option<bool>], clause: usize, status: &mut [Status]) -> Vec<usize> {
+        let mut values: Vec<bool> = model.iter().map(|value| value.unwrap_or(false)).collect();
+        let mut found = Vec::new();
+        // (偽になる節, 元の model から反転した変数)
+        let mut stack: Vec<(usize, Vec<usize>)> = vec![(clause, Vec::new())];
+        while let Some((c, flips)) = stack.pop() {
+            for &var in &flips {
+                values[var] = !values[var];
+            }
+            for lit in &self.clauses[c] {
+                // lit は偽なので, 反転すると c は真になり, ¬lit を含む節だけが偽になりうる
+                let var = lit.var();
+                values[var] = !values[var];
+                let falsified: Vec<usize> = self.occurs.get(&lit.not()).into_iter().flatten()
+                    .copied()
+                    .filter(|&d| status[d] != Status::Removed)
+                    .filter(|&d| self.clauses[d].iter().all(|l| values[l.var()] != l.is_pos()))
+                    .collect();
+                if let [d] = falsified[..] {
+                    if status[d] == Status::Unknown {
+                        status[d] = Status::Necessary;
+                        found.push(d);
+                        let mut next = flips.clone();
+                        next.push(var);
+                        stack.push((d, next));
+                    }
+                }
+                values[var] = !values[var];
+            }
+            for &var in &flips {
+                values[var] = !values[var];
+            }
+        }
+        found
+    }
+
+    /// UNSAT な節の集合 seed を MUS まで縮める
+    /// # Returns
+    /// * MUS の節の番号, 昇順
+    pub fn shrink(&mut self, seed: &[usize]) -> Vec<usize> {
+        self.shrink_with(seed, false)
+    }
+
+    /// fix なら, 必要な節と除いた節の selector を単位節にして以後の探索を軽くする
+    /// その後は selector を仮定にしても節を出し入れできなくなるので, MUS を1つ求めるときだけ使う
+    fn shrink_with(&mut self, seed: &[usize], fix: bool) -> Vec<usize> {
+        let mut status = vec![Status::Removed; self.clauses.len()];
+        for &i in seed {
+            status[i] = Status::Unknown;
+        }
+        let mut unknown = seed.to_vec();
+        let mut necessary = Vec::new();
+        while let Some(c) = unknown.pop() {
+            // 必要な節を先に仮定すると, final conflict には unknown の前の方だけが残る
+            let subset: Vec<usize> = necessary.iter().chain(unknown.iter()).copied().collect();
+            if self.solve_subset(&subset) {
+                // c を除くと SAT になるので, c は MUS に必要
+                status[c] = Status::Necessary;
+                let first = necessary.len();
+                necessary.push(c);
+                if self.use_model_rotation {
+                    let model = self.solver.model.clone();
+                    let found = self.rotate(&model, c, &mut status);
+                    self.rotated += found.len();
+                    necessary.extend(found);
+                    unknown.retain(|&i| status[i] == Status::Unknown);
+                }
+                if fix {
+                    for &i in &necessary[first..] {
+                        self.solver.add_clause(&mut vec![self.selectors[i]]);
+                    }
+                }
+            } else {
+                status[c] = Status::Removed;
+                let mut removed = vec![c];
+                if self.use_refinement {
+                    removed.extend(self.refine(&mut unknown, &mut status));
+                }
+                if fix {
+                    for i in removed {
+                        self.solver.add_clause(&mut vec![self.selectors[i].not()]);
+                    }
+                }
+            }
+        }
+        necessary.sort_unstable();
+        necessary
+    }
+
+    /// 加えた節全体が UNSAT なら MUS を求める
+    /// # Returns
+    /// * `Some(indices)` - MUS の節の番号, 昇順
+    /// * `None` - SAT, MUS はない
+    pub fn extract(&mut self) -> Option<Vec<usize>> {
+        let all: Vec<usize> = (0..self.clauses.len()).filter(|&i| !self.is_tautology(i)).collect();
+        if self.solve_subset(&all) {
+            return None;
+        }
+        let seed = if self.use_refinement { self.failed_clauses() } else { all };
+        debug!("mus: initial core {} clauses", seed.len());
+
+        let mus = self.shrink_with(&seed, true);
+        info!("mus: {} of {} clauses, {} solver calls, {} by model rotation", mus.len(), self.clauses.len(), self.solver_calls, self.rotated);
+        Some(mus)
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+    use crate::testing::{brute_force, random_clauses};
+
+    #[test]
+    fn extract_small_mus() {
+        // x1, ¬x1 ∨ x2, ¬x2 が MUS, 残りは不要
+        let clauses: Clauses = vec![
+            vec![Literal::Pos(2), Literal::Pos(3)],
+            vec![Literal::Pos(0)],
+            vec![Literal::Pos(2), Literal::Neg(2)],
+            vec![Literal::Neg(0), Literal::Pos(1)],
+            vec![Literal::Neg(3), Literal::Pos(0)],
+            vec![Literal::Neg(1)],
+        ];
+        for (refinement, rotation) in [(true, true), (true, false), (false, true), (false, false)] {
+            let mut extractor = MusExtractor::new(4);
+            extractor.use_refinement = refinement;
+            extractor.use_model_rotation = rotation;
+            for clause in &clauses {
+                extractor.add_clause(clause);
+            }
+            assert_eq!(extractor.extract(), Some(vec![1, 3, 5]));
+        }
+
+        let mut extractor = MusExtractor::new(2);
+        extractor.add_clause(&vec![Literal::Pos(0), Literal::Pos(1)]);
+        extractor.add_clause(&vec![Literal::Neg(0)]);
+        assert_eq!(extractor.extract(), None);
+    }
+
+    #[test]
+    fn random_mus_is_minimal() {
+        let mut rng = Random::new(6);
+        let mut found = 0;
+        let mut calls = [0; 4];                 // 設定ごとの solve_subset の回数
+        while found < 100 {
+            let size_vars = 2 + rng.gen_range(6);
+            let k = 1 + rng.gen_range(3);
+            let clauses = random_clauses(&mut rng, size_vars, size_vars * 4, k);
+            if !brute_force(size_vars, &clauses, &[]).is_empty() {
+                continue;
+            }
+            found += 1;
+            for (k, (refinement, rotation)) in [(true, true), (true, false), (false, true), (false, false)].into_iter().enumerate() {
+                let mut extractor = MusExtractor::new(size_vars);
+                extractor.use_refinement = refinement;
+                extractor.use_model_rotation = rotation;
+                for clause in &clauses {
+                    extractor.add_clause(clause);
+                }
+                let mus = extractor.extract().unwrap();
+                calls[k] += extractor.solver_calls;
+                let subset: Clauses = mus.iter().map(|&i| clauses[i].clone()).collect();
+                assert!(brute_force(size_vars, &subset, &[]).is_empty(), "{:?} {:?}", clauses, mus);
+                // どの節を除いても SAT になる
+                for j in 0..subset.len() {
+                    let mut rest = subset.clone();
+                    rest.remove(j);
+                    assert!(!brute_force(size_vars, &rest, &[]).is_empty(), "{:?} {:?}", clauses, mus);
+                }
+            }
+        }
+        // final conflict で節を絞ると, 解く回数が減る
+        assert!(calls[0] < calls[2] && calls[1] < calls[3], "{:?}", calls);
+    }
+}
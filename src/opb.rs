@@ -0,0 +1,381 @@
+use log::info;
+
+use crate::encodings::{CardEncoding, PbEncoding, at_least_k, generalized_totalizer, pb_at_least};
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+/// 線形制約の左辺と右辺の関係
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    GreaterEq,
+    Equal,
+    LessEq,
+}
+
+/// 線形制約 Σ 係数 * リテラル (関係) 右辺, リテラルは真なら 1, 偽なら 0 とする
+#[derive(Debug, Clone)]
+pub struct PbConstraint {
+    pub terms: Vec<(i64, Literal)>,
+    pub relation: Relation,
+    pub rhs: i64,
+}
+
+/// PB competition の OPB 形式の問題, 目的関数があれば最小化する
+#[derive(Debug, Clone)]
+pub struct PbProblem {
+    pub num_vars: usize,
+    pub objective: Option<Vec<(i64, Literal)>>,
+    pub constraints: Vec<PbConstraint>,
+}
+
+/// `x3` や `~x3` を読む
+fn parse_literal(token: &str) -> Option<Literal> {
+    let (negated, name) = match token.strip_prefix('~') {
+        Some(name) => (true, name),
+        None => (false, token),
+    };
+    let var: usize = name.strip_prefix('x')?.parse().ok()?;
+    if var == 0 {
+        return None;
+    }
+    Some(if negated { Literal::Neg(var - 1) } else { Literal::Pos(var - 1) })
+}
+
+/// `+2 x1 -3 ~x2` の形の項の列を読む
+fn parse_terms(tokens: &[&str]) -> Result<Vec<(i64, Literal)>, String> {
+    let mut terms = Vec::new();
+    let mut k = 0;
+    while k < tokens.len() {
+        let coef: i64 = tokens[k].parse().map_err(|_| format!("invalid coefficient: {}", tokens[k]))?;
+        let lit = tokens.get(k + 1)
+            .and_then(|token| parse_literal(token))
+            .ok_or_else(|| format!("missing literal after {}", tokens[k]))?;
+        if tokens.get(k + 2).is_some_and(|token| parse_literal(token).is_some()) {
+            return Err(format!("non-linear term is not supported: {} {} {}", tokens[k], tokens[k + 1], tokens[k + 2]));
+        }
+        terms.push((coef, lit));
+        k += 2;
+    }
+    Ok(terms)
+}
+
+/// OPB 形式を読む, `*` から始まる行はコメント
+/// 制約と目的関数はそれぞれ `;` で終わる
+pub fn parse_opb(data: &str) -> Result<PbProblem, String> {
+    let mut num_vars = 0;
+    let mut body = String::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('*') {
+            // `* #variable= 5 #constraint= 4`
+            let tokens: Vec<&str> = comment.split_whitespace().collect();
+            if let Some(k) = tokens.iter().position(|&token| token == "#variable=") {
+                num_vars = tokens.get(k + 1).and_then(|n| n.parse().ok()).unwrap_or(0);
+            }
+            continue;
+        }
+        body.push_str(line);
+        body.push(' ');
+    }
+
+    let mut objective = None;
+    let mut constraints = Vec::new();
+    for statement in body.split(';') {
+        let statement = statement.trim();
+        if statement.is_empty() {
+            continue;
+        }
+        if let Some(rest) = statement.strip_prefix("min:") {
+            let tokens: Vec<&str> = rest.split_whitespace().collect();
+            objective = Some(parse_terms(&tokens)?);
+            continue;
+        }
+        let tokens: Vec<&str> = statement.split_whitespace().collect();
+        let k = tokens.iter()
+            .position(|&token| token == ">=" || token == "=" || token == "<=")
+            .ok_or_else(|| format!("missing relation: {}", statement))?;
+        let relation = match tokens[k] {
+            ">=" => Relation::GreaterEq,
+            "=" => Relation::Equal,
+            _ => Relation::LessEq,
+        };
+        if tokens.len() != k + 2 {
+            return Err(format!("invalid right-hand side: {}", statement));
+        }
+        let rhs: i64 = tokens[k + 1].parse().map_err(|_| format!("invalid right-hand side: {}", statement))?;
+        constraints.push(PbConstraint {
+            terms: parse_terms(&tokens[..k])?,
+            relation,
+            rhs,
+        });
+    }
+
+    let max_var = objective.iter().flatten()
+        .chain(constraints.iter().flat_map(|c| c.terms.iter()))
+        .map(|&(_, lit)| lit.var() + 1)
+        .max()
+        .unwrap_or(0);
+    Ok(PbProblem {
+        num_vars: num_vars.max(max_var),
+        objective,
+        constraints,
+    })
+}
+
+/// Σ 係数 * リテラル を, 正の重みの項と定数の和に直す
+/// 負の係数 a の項は a * l = a + (-a) * ¬l とする
+/// # Returns
+/// * `Err(message)` - 係数の絶対値の和が i64 に収まらない
+fn normalize(terms: &[(i64, Literal)]) -> Result<(Vec<(u64, Literal)>, i64), String> {
+    let overflow = || "sum of coefficients overflows".to_string();
+    let mut offset: i64 = 0;
+    let mut total: i64 = 0;
+    let mut normalized = Vec::new();
+    for &(coef, lit) in terms {
+        total = i64::try_from(coef.unsigned_abs()).ok().and_then(|weight| total.checked_add(weight)).ok_or_else(overflow)?;
+        if coef > 0 {
+            normalized.push((coef as u64, lit));
+        } else if coef < 0 {
+            offset += coef;
+            normalized.push((coef.unsigned_abs(), lit.not()));
+        }
+    }
+    Ok((normalized, offset))
+}
+
+/// Σ 係数 * リテラル >= rhs を節にして solver に加える
+/// 重みが全て等しければ cardinality 制約として符号化する
+/// 右辺より大きい重みは右辺に揃える, その項が真なら重みによらず制約を満たす
+/// # Returns
+/// * `Ok(false)` - 根で矛盾した, UNSAT
+/// * `Err(message)` - 係数や右辺が i64 に収まらない
+fn add_at_least(solver: &mut Solver, terms: &[(i64, Literal)], rhs: i64, card: CardEncoding, pb: PbEncoding) -> Result<bool, String> {
+    let (terms, offset) = normalize(terms)?;
+    let bound = rhs.checked_sub(offset).ok_or_else(|| format!("right-hand side overflows: {}", rhs))?;
+    if bound <= 0 {
+        return Ok(true);
+    }
+    let bound = bound as u64;
+    let terms: Vec<(u64, Literal)> = terms.into_iter().map(|(weight, lit)| (weight.min(bound), lit)).collect();
+    if terms.iter().all(|&(weight, _)| weight == terms[0].0) {
+        let lits: Vec<Literal> = terms.iter().map(|&(_, lit)| lit).collect();
+        let weight = terms.first().map_or(1, |&(weight, _)| weight);
+        let k = bound.div_ceil(weight) as usize;
+        if k == 1 {
+            return Ok(solver.add_clause(&mut lits.clone()));
+        }
+        return Ok(at_least_k(solver, &lits, k, card));
+    }
+    Ok(pb_at_least(solver, &terms, bound, pb))
+}
+
+/// model での Σ 係数 * リテラル の値, 割り当てのない変数は偽とする
+fn evaluate(terms: &[(i64, Literal)], model: &[Option<bool>]) -> i64 {
+    terms.iter()
+        .filter(|&&(_, lit)| model.get(lit.var()).copied().flatten().unwrap_or(false) == lit.is_pos())
+        .map(|&(coef, _)| coef)
+        .sum()
+}
+
+impl PbProblem {
+    /// 制約を全て節にして solver に加え, 目的関数の変数を freeze する
+    /// # Returns
+    /// * `Ok(true)` - 成功
+    /// * `Ok(false)` - 根で矛盾した, UNSAT
+    /// * `Err(message)` - 係数の和や右辺の符号の反転が i64 に収まらない
+    pub fn encode(&self, solver: &mut Solver, card: CardEncoding, pb: PbEncoding) -> Result<bool, String> {
+        // 目的関数も minimize で同じように重みの和に直す
+        if let Some(objective) = &self.objective {
+            normalize(objective)?;
+        }
+        // 制約に現れない変数も解に含める
+        while solver.size_vars() < self.num_vars {
+            solver.new_var();
+        }
+        for &(_, lit) in self.objective.iter().flatten() {
+            solver.freeze(lit.var());
+        }
+        for constraint in &self.constraints {
+            let negate = || -> Result<(Vec<(i64, Literal)>, i64), String> {
+                let terms = constraint.terms.iter()
+                    .map(|&(coef, lit)| coef.checked_neg().map(|coef| (coef, lit)).ok_or_else(|| format!("coefficient overflows: {}", coef)))
+                    .collect::<Result<Vec<(i64, Literal)>, String>>()?;
+                let rhs = constraint.rhs.checked_neg().ok_or_else(|| format!("right-hand side overflows: {}", constraint.rhs))?;
+                Ok((terms, rhs))
+            };
+            let ok = match constraint.relation {
+                Relation::GreaterEq => add_at_least(solver, &constraint.terms, constraint.rhs, card, pb)?,
+                Relation::LessEq => {
+                    let (negated, rhs) = negate()?;
+                    add_at_least(solver, &negated, rhs, card, pb)?
+                },
+                Relation::Equal => {
+                    let (negated, rhs) = negate()?;
+                    add_at_least(solver, &constraint.terms, constraint.rhs, card, pb)?
+                        && add_at_least(solver, &negated, rhs, card, pb)?
+                },
+            };
+            if !ok {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// encode に成功した solver で目的関数を線形探索 (SAT-UNSAT) で最小化する
+    /// 解が見つかるたびに目的関数の値を report に渡し, それより小さい値しか許さない制約を加えて解き直す
+    /// 目的関数の和は generalized totalizer で1度だけ符号化し, 出力の否定を単位節にして上限を下げる
+    /// # Returns
+    /// * `Some((model, value))` - 最適解と目的関数の値 (目的関数がなければ最初の解と 0)
+    /// * `None` - 制約を満たす解がない
+    pub fn minimize(&self, solver: &mut Solver, mut report: impl FnMut(i64)) -> Option<(Vec<Option<bool>>, i64)> {
+        if solver.solve() != Some(true) {
+            return None;
+        }
+        let mut best = solver.model.clone();
+        let objective = match &self.objective {
+            Some(objective) => objective,
+            None => return Some((best, 0)),
+        };
+        let mut value = evaluate(objective, &best);
+        report(value);
+
+        // encode で和が i64 に収まることを確かめている
+        let (terms, offset) = normalize(objective).expect("objective is checked in encode");
+        let outputs = generalized_totalizer(solver, &terms, (value - offset) as u64);
+        loop {
+            // 和を value - offset 未満にする
+            let limit = (value - offset) as u64;
+            let mut ok = true;
+            for &(sum, lit) in &outputs {
+                if sum >= limit {
+                    ok &= solver.add_clause(&mut vec![lit.not()]);
+                }
+            }
+            if limit == 0 || !ok || solver.solve() != Some(true) {
+                break;
+            }
+            best = solver.model.clone();
+            value = evaluate(objective, &best);
+            report(value);
+        }
+        info!("optimum: {}", value);
+        Some((best, value))
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    const CARD_ENCODINGS: [CardEncoding; 3] = [CardEncoding::SequentialCounter, CardEncoding::Totalizer, CardEncoding::CardinalityNetwork];
+    const PB_ENCODINGS: [PbEncoding; 2] = [PbEncoding::Adder, PbEncoding::GeneralizedTotalizer];
+
+    /// Σ 係数 * リテラル >= rhs を加えた solver の, 変数 size_vars 個に射影したモデル数, UNSAT なら 0
+    fn count_at_least(size_vars: usize, terms: &[(i64, Literal)], rhs: i64, card: CardEncoding, pb: PbEncoding) -> usize {
+        let mut solver = Solver::new();
+        let vars: Vec<usize> = (0..size_vars).map(|_| solver.new_var()).collect();
+        if !add_at_least(&mut solver, terms, rhs, card, pb).unwrap() {
+            return 0;
+        }
+        solver.models(Some(&vars)).count()
+    }
+
+    /// Σ 係数 * リテラル >= rhs を満たす割り当ての数
+    fn expected(size_vars: usize, terms: &[(i64, Literal)], rhs: i64) -> usize {
+        (0..1u64 << size_vars)
+            .filter(|bits| {
+                let model: Vec<Option<bool>> = (0..size_vars).map(|var| Some((bits >> var) & 1 == 1)).collect();
+                evaluate(terms, &model) >= rhs
+            })
+            .count()
+    }
+
+    #[test]
+    fn at_least_edge_cases() {
+        let x = |var| Literal::Pos(var);
+        for card in CARD_ENCODINGS {
+            for pb in PB_ENCODINGS {
+                // 項がなければ左辺は 0
+                assert_eq!(count_at_least(2, &[], 1, card, pb), 0);
+                assert_eq!(count_at_least(2, &[], 3, card, pb), 0);
+                assert_eq!(count_at_least(2, &[], 0, card, pb), 4);
+                assert_eq!(count_at_least(2, &[], -2, card, pb), 4);
+                // 右辺が 0 以下なら常に満たす
+                assert_eq!(count_at_least(2, &[(1, x(0)), (1, x(1))], 0, card, pb), 4);
+                assert_eq!(count_at_least(2, &[(-1, x(0)), (-1, x(1))], -2, card, pb), 4);
+                // 重みが右辺より大きい項, 5 x1 + 1 x2 >= 3 なら x1 は真
+                assert_eq!(count_at_least(2, &[(5, x(0)), (1, x(1))], 3, card, pb), 2);
+                // 全て右辺より大きければ節と同じ
+                assert_eq!(count_at_least(3, &[(4, x(0)), (7, x(1)), (5, x(2))], 2, card, pb), 7);
+                // 総和が右辺に届かない
+                assert_eq!(count_at_least(2, &[(1, x(0)), (2, x(1))], 4, card, pb), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn at_least_agrees_with_brute_force() {
+        let mut rng = Random::new(1);
+        for _ in 0..100 {
+            let size_vars = 5;
+            let size_terms = rng.gen_range(6);
+            let terms: Vec<(i64, Literal)> = (0..size_terms)
+                .map(|_| {
+                    let coef = rng.gen_range(11) as i64 - 4;
+                    let var = rng.gen_range(size_vars);
+                    (coef, if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) })
+                })
+                .collect();
+            let rhs = rng.gen_range(15) as i64 - 5;
+            let expected = expected(size_vars, &terms, rhs);
+            for card in CARD_ENCODINGS {
+                for pb in PB_ENCODINGS {
+                    assert_eq!(count_at_least(size_vars, &terms, rhs, card, pb), expected, "{:?} >= {}", terms, rhs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn minimize_small_problem() {
+        let data = "* #variable= 3 #constraint= 2\n\
+                    min: +2 x1 +3 x2 -1 x3 ;\n\
+                    +1 x1 +1 x2 >= 1 ;\n\
+                    +1 x1 -1 ~x3 <= 0 ;\n";
+        let problem = parse_opb(data).unwrap();
+        assert_eq!(problem.num_vars, 3);
+        assert_eq!(problem.constraints.len(), 2);
+
+        // x1 ≤ ¬x3 なので, x1 = 1 なら x3 = 0 で 2, x2 = 1 なら x3 = 1 で 2
+        let mut solver = Solver::new();
+        assert_eq!(problem.encode(&mut solver, CardEncoding::Totalizer, PbEncoding::GeneralizedTotalizer), Ok(true));
+        let mut reported = Vec::new();
+        let (model, value) = problem.minimize(&mut solver, |v| reported.push(v)).unwrap();
+        assert_eq!(value, 2);
+        assert_eq!(evaluate(problem.objective.as_ref().unwrap(), &model), 2);
+        assert!(reported.windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn overflowing_constraints_are_rejected() {
+        let encode = |data: &str| {
+            let problem = parse_opb(data).unwrap();
+            problem.encode(&mut Solver::new(), CardEncoding::Totalizer, PbEncoding::GeneralizedTotalizer)
+        };
+        // 右辺の符号を反転できない
+        assert!(encode("+1 x1 <= -9223372036854775808 ;").is_err());
+        assert!(encode("+1 x1 = -9223372036854775808 ;").is_err());
+        assert_eq!(encode("+1 x1 >= -9223372036854775808 ;"), Ok(true));
+        // 係数の符号を反転できない, 係数の和が収まらない
+        assert!(encode("-9223372036854775808 x1 <= 0 ;").is_err());
+        assert!(encode("+9223372036854775807 x1 +1 x2 >= 1 ;").is_err());
+        assert!(encode("-9223372036854775807 x1 -2 x2 >= 1 ;").is_err());
+        // 右辺から負の係数の和を引くと収まらない
+        assert!(encode("-1 x1 >= 9223372036854775807 ;").is_err());
+        assert!(encode("min: +9223372036854775807 x1 +1 x2 ;\n+1 x1 >= 1 ;").is_err());
+        assert_eq!(encode("+4611686018427387903 x1 -4611686018427387904 x2 >= 0 ;"), Ok(true));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use log::info;
+
+use crate::clause::Clause;
+use crate::solver::{Clauses, Solver, Stats};
+
+/// 並列に動く Solver の間で節を受け渡す, 大きさに上限のある置き場
+/// 古い節から捨てるので, 読むのが遅れた Solver は一部の節を受け取らないことがある
+pub struct ClauseExchange {
+    capacity: usize,
+    buffer: Mutex<ExchangeBuffer>,
+}
+
+struct ExchangeBuffer {
+    clauses: VecDeque<(usize, Clause)>,     // (出した Solver の番号, 節)
+    head: usize,                            // clauses の先頭の節の通し番号
+}
+
+impl ClauseExchange {
+    pub fn new(capacity: usize) -> Self {
+        ClauseExchange {
+            capacity,
+            buffer: Mutex::new(ExchangeBuffer {
+                clauses: VecDeque::new(),
+                head: 0,
+            }),
+        }
+    }
+
+    /// from 番の Solver が節を出す
+    pub fn export(&self, from: usize, clauses: Clauses) {
+        let mut buffer = self.buffer.lock().unwrap();
+        for clause in clauses {
+            buffer.clauses.push_back((from, clause));
+        }
+        while buffer.clauses.len() > self.capacity {
+            buffer.clauses.pop_front();
+            buffer.head += 1;
+        }
+    }
+
+    /// 通し番号 cursor 以降で, to 番以外の Solver が出した節を取り出して cursor を進める
+    pub fn import(&self, to: usize, cursor: &mut usize) -> Clauses {
+        let buffer = self.buffer.lock().unwrap();
+        let start = (*cursor).max(buffer.head) - buffer.head;
+        let clauses = buffer.clauses.iter()
+            .skip(start)
+            .filter(|(from, _)| *from != to)
+            .map(|(_, clause)| clause.clone())
+            .collect();
+        *cursor = buffer.head + buffer.clauses.len();
+        clauses
+    }
+}
+
+/// 最初に答えを出した Solver の結果
+struct Answer {
+    id: usize,
+    st: bool,
+    model: Vec<Option<bool>>,
+    stats: Stats,
+}
+
+/// 設定を変えた複数の Solver で同じ論理式を並列に解き, 最初に出た答えを返す
+/// 各 Solver は根での割り当てと, LBD の小さい学習節を,
+/// リスタートの合間に ClauseExchange を通して共有する
+pub struct Portfolio {
+    pub threads: usize,                 // 並列に動かす Solver の数
+    pub share_lbd_limit: usize,         // LBD がこれ以下の学習節を共有する
+    pub exchange_capacity: usize,       // ClauseExchange に置いておく節の数の上限
+
+    // 見つかった解
+    pub model: Vec<Option<bool>>,
+    // 答えを出した Solver の番号と, その Solver の状態
+    pub winner: Option<usize>,
+    pub stats: Stats,
+}
+
+impl Portfolio {
+    pub fn new(threads: usize) -> Self {
+        Portfolio {
+            threads,
+            share_lbd_limit: 4,
+            exchange_capacity: 10_000,
+
+            model: Vec::new(),
+            winner: None,
+            stats: Stats::new(),
+        }
+    }
+
+    /// id 番の Solver の設定を変える, 0 番は既定の設定のまま
+    fn diversify(solver: &mut Solver, id: usize) {
+        solver.seed = id as u64;
+        if id > 0 {
+            solver.random_var_freq = 0.02;
+        }
+        match id % 4 {
+            1 => {
+                solver.initial_phase = true;
+                solver.restart_first = 50;
+                solver.restart_inc = 2.0;
+            },
+            2 => {
+                solver.use_rephase = false;
+                solver.vivify_effort = 0.3;
+            },
+            3 => {
+                solver.initial_phase = true;
+                solver.use_elim = false;
+                solver.restart_inc = 1.2;
+                solver.rephase_interval = 1;
+            },
+            _ => {},
+        }
+    }
+
+    /// # Returns
+    /// * `Some`
+    ///   - `true` - SAT, model に解が入る
+    ///   - `false` - UNSAT
+    /// * `None` - どの Solver も答えを出さなかった
+    pub fn solve(&mut self, clauses: &Clauses) -> Option<bool> {
+        let exchange = Arc::new(ClauseExchange::new(self.exchange_capacity));
+        let found = Arc::new(AtomicBool::new(false));
+        let answer: Mutex<Option<Answer>> = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for id in 0..self.threads.max(1) {
+                let exchange = Arc::clone(&exchange);
+                let found = &found;
+                let answer = &answer;
+                let share_lbd_limit = self.share_lbd_limit;
+                scope.spawn(move || {
+                    let mut solver = Solver::new();
+                    solver.interrupt = Arc::clone(found);
+                    solver.share_lbd_limit = share_lbd_limit;
+                    solver.attach_exchange(exchange, id);
+                    Portfolio::diversify(&mut solver, id);
+
+                    let mut ok = true;
+                    for clause in clauses {
+                        if !solver.add_clause(&mut clause.clone()) {
+                            ok = false;
+                            break;
+                        }
+                    }
+                    let st = if ok { solver.solve() } else { Some(false) };
+
+                    if let Some(st) = st {
+                        let mut answer = answer.lock().unwrap();
+                        if answer.is_none() {
+                            info!("portfolio: solver {} finished first", id);
+                            *answer = Some(Answer {
+                                id,
+                                st,
+                                model: solver.model.clone(),
+                                stats: solver.stats,
+                            });
+                            found.store(true, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+
+        let answer = answer.into_inner().unwrap()?;
+        self.winner = Some(answer.id);
+        self.model = answer.model;
+        self.stats = answer.stats;
+        Some(answer.st)
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::literal::Literal;
+    use crate::random::Random;
+    use crate::testing::{is_model, pigeonhole, random_clauses};
+
+    fn lits(dimacs: &[i32]) -> Clause {
+        dimacs.iter().map(|&lit| Literal::from_dimacs(lit)).collect()
+    }
+
+    #[test]
+    fn exchange_skips_own_clauses() {
+        let exchange = ClauseExchange::new(10);
+        exchange.export(0, vec![lits(&[1, 2])]);
+        exchange.export(1, vec![lits(&[-1, 3]), lits(&[2])]);
+
+        let mut cursor = 0;
+        assert_eq!(exchange.import(1, &mut cursor), vec![lits(&[1, 2])]);
+        assert_eq!(cursor, 3);
+        // 読んだ節はもう返さない
+        assert!(exchange.import(1, &mut cursor).is_empty());
+
+        let mut cursor = 0;
+        assert_eq!(exchange.import(0, &mut cursor), vec![lits(&[-1, 3]), lits(&[2])]);
+        let mut cursor = 0;
+        assert_eq!(exchange.import(2, &mut cursor).len(), 3);
+    }
+
+    #[test]
+    fn exchange_drops_oldest_clauses() {
+        let exchange = ClauseExchange::new(2);
+        let mut cursor = 0;
+        exchange.export(0, vec![lits(&[1])]);
+        assert_eq!(exchange.import(1, &mut cursor), vec![lits(&[1])]);
+
+        exchange.export(0, vec![lits(&[2]), lits(&[3]), lits(&[4])]);
+        // 読むのが遅れたので [2] は捨てられている
+        assert_eq!(exchange.import(1, &mut cursor), vec![lits(&[3]), lits(&[4])]);
+        assert_eq!(cursor, 4);
+    }
+
+    #[test]
+    fn shared_clauses_are_implied() {
+        let clauses = pigeonhole(5, 4);
+        let exchange = Arc::new(ClauseExchange::new(100_000));
+        let mut solver = Solver::new();
+        solver.restart_first = 1;
+        solver.attach_exchange(Arc::clone(&exchange), 0);
+        for clause in &clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        assert_eq!(solver.solve(), Some(false));
+
+        let mut cursor = 0;
+        let shared = exchange.import(1, &mut cursor);
+        // 根での割り当てだけでなく, 学習節も共有している
+        assert!(shared.iter().any(|clause| clause.len() > 1));
+        for clause in &shared {
+            // 節の否定を仮定すると UNSAT, つまり元の論理式から導かれる
+            let mut checker = Solver::new();
+            for clause in &clauses {
+                checker.add_clause(&mut clause.clone());
+            }
+            let negation: Clause = clause.iter().map(|lit| lit.not()).collect();
+            assert_eq!(checker.solve_with_assumptions(&negation), Some(false));
+        }
+
+        // 取り込んだ Solver も同じ答えを出す
+        let mut other = Solver::new();
+        other.restart_first = 1;
+        other.attach_exchange(exchange, 1);
+        for clause in &clauses {
+            other.add_clause(&mut clause.clone());
+        }
+        assert_eq!(other.solve(), Some(false));
+        assert!(other.stats.imported_clauses > 0);
+    }
+
+    #[test]
+    fn portfolio_returns_first_answer() {
+        let mut rng = Random::new(4);
+        for _ in 0..20 {
+            let clauses = random_clauses(&mut rng, 30, 120, 3);
+            let mut portfolio = Portfolio::new(4);
+            let st = portfolio.solve(&clauses);
+            assert!(portfolio.winner.is_some_and(|id| id < 4));
+            let mut solver = Solver::new();
+            for clause in &clauses {
+                solver.add_clause(&mut clause.clone());
+            }
+            assert_eq!(st, solver.solve());
+            if st == Some(true) {
+                assert!(is_model(&clauses, &portfolio.model));
+            }
+        }
+
+        let mut portfolio = Portfolio::new(3);
+        assert_eq!(portfolio.solve(&pigeonhole(5, 4)), Some(false));
+        assert!(portfolio.stats.conflicts > 0);
+    }
+}
@@ -0,0 +1,848 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
+
+use crate::literal::Literal;
+use crate::clause::{Clause, NormalizeError, normalize_clause};
+use crate::solver::Clauses;
+
+/// 前処理で削除した節を, モデル復元用に積んでおくスタック
+/// 各要素は (witness, 削除した節) で, 復元時は後ろから見ていき
+/// 節が偽になっていれば witness を真に反転させる
+pub struct ReconstructionStack {
+    entries: Vec<(Literal, Clause)>,
+}
+
+impl ReconstructionStack {
+    pub fn new() -> Self {
+        ReconstructionStack {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, witness: Literal, clause: Clause) {
+        self.entries.push((witness, clause));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Literal, Clause)> {
+        self.entries.iter()
+    }
+
+    /// 簡略化後の論理式のモデルを, 元の論理式のモデルに直す
+    /// 未割り当ての変数は false として扱う
+    pub fn extend_model(&self, model: &mut [Option<bool>]) {
+        for (witness, clause) in self.entries.iter().rev() {
+            let is_satisfied = clause.iter().any(|lit| {
+                model[lit.var()].unwrap_or(false) == lit.is_pos()
+            });
+            if !is_satisfied {
+                model[witness.var()] = Some(witness.is_pos());
+            }
+        }
+    }
+
+    /// vars を witness に持つ節をスタックから取り除いて返す
+    /// 取り除いた節に現れる変数を witness に持つ節も, 論理式に戻さないと制約が足りなくなるので続けて取り除く
+    pub fn restore(&mut self, vars: impl IntoIterator<Item = usize>) -> Clauses {
+        let mut queue: Vec<usize> = vars.into_iter().collect();
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut restored = Vec::new();
+        while let Some(var) = queue.pop() {
+            if self.entries.is_empty() {
+                break;
+            }
+            if !seen.insert(var) {
+                continue;
+            }
+            let (taken, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.entries).into_iter()
+                .partition(|(witness, _)| witness.var() == var);
+            self.entries = kept;
+            for (_, clause) in taken {
+                queue.extend(clause.iter().map(|lit| lit.var()));
+                restored.push(clause);
+            }
+        }
+        restored
+    }
+
+    /// ファイルに書き出す
+    /// 1行目が `p stack <変数の数> <要素数>`, 以降1行1要素で `<witness> <節のリテラル> 0`
+    pub fn write<W: Write>(&self, size_vars: usize, w: &mut W) -> io::Result<()> {
+        writeln!(w, "p stack {} {}", size_vars, self.entries.len())?;
+        for (witness, clause) in &self.entries {
+            write!(w, "{}", witness.to_dimacs())?;
+            for lit in clause {
+                write!(w, " {}", lit.to_dimacs())?;
+            }
+            writeln!(w, " 0")?;
+        }
+        Ok(())
+    }
+
+    /// write で書き出したものを読み込む
+    /// # Returns
+    /// * `Some((変数の数, スタック))` - 読み込み成功
+    /// * `None` - ファイル形式が違っている
+    pub fn parse(data: &str) -> Option<(usize, Self)> {
+        let mut lines = data.lines().filter(|line| !line.trim().is_empty());
+        let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+        if header.len() != 4 || header[0] != "p" || header[1] != "stack" {
+            return None;
+        }
+        let size_vars: usize = header[2].parse().ok()?;
+        let size_entries: usize = header[3].parse().ok()?;
+
+        let mut stack = ReconstructionStack::new();
+        for line in lines {
+            let lits: Vec<i32> = line.split_whitespace()
+                .map(|k| k.parse().ok())
+                .collect::<Option<Vec<i32>>>()?;
+            let (&witness, rest) = lits.split_first()?;
+            if witness == 0 || rest.last() != Some(&0) {
+                return None;
+            }
+            let clause: Clause = rest[..rest.len() - 1].iter()
+                .map(|&lit| Literal::from_dimacs(lit))
+                .collect();
+            stack.push(Literal::from_dimacs(witness), clause);
+        }
+        if stack.len() != size_entries {
+            return None;
+        }
+        Some((size_vars, stack))
+    }
+}
+
+impl Default for ReconstructionStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// clause の全てのリテラルが other に含まれるか
+/// flip が与えられた場合は, clause の flip の代わりに other が ¬flip を含んでいればよい
+fn is_subset(clause: &Clause, other: &Clause, flip: Option<Literal>) -> bool {
+    clause.iter().all(|lit| {
+        if Some(*lit) == flip {
+            other.contains(&lit.not())
+        } else {
+            other.contains(lit)
+        }
+    })
+}
+
+/// clause と other を lit で導出した導出節がトートロジーになるか
+/// clause は lit を, other は ¬lit を含むとする
+fn is_tautological_resolvent(clause: &Clause, other: &Clause, lit: Literal) -> bool {
+    clause.iter()
+        .filter(|&&l| l != lit)
+        .any(|l| other.contains(&l.not()))
+}
+
+/// 探索前に論理式を簡略化する
+/// 根での単位伝播, 包摂, 等価リテラルの置き換え, failed literal probing,
+/// bounded variable elimination, blocked clause elimination を行う
+/// 充足可能性を変えるだけの変形で削除した節は ReconstructionStack に積む
+pub struct Preprocessor {
+    clauses: Clauses,                       // 削除した節もそのまま残す
+    alive: Vec<bool>,                       // clauses の各節がまだ削除されていないか
+    occurs: HashMap<Literal, Vec<usize>>,   // occurs[lit] = lit を含む(含んでいた)節の clauses 上のインデックス
+    assigns: Vec<Option<bool>>,             // 根での割り当て
+    eliminated: Vec<bool>,                  // 変数除去, 等価リテラルの置き換えで論理式から消えた変数
+    frozen: Vec<bool>,                      // 除去, 置き換えをしない変数 (仮定に使う変数など)
+    units: VecDeque<Literal>,               // まだ伝播していない根での割り当て
+    stack: ReconstructionStack,
+    ok: bool,                               // false なら根で矛盾した
+
+    pub use_elim: bool,                     // 変数除去と blocked clause elimination を行うか
+    pub probe_budget: usize,                // failed literal probing で見る節の数の上限
+    pub elim_occurs_limit: usize,           // 変数除去を試す変数の, 片方の極性の出現数の上限
+
+    // ログ等
+    pub subsumed_clauses: usize,
+    pub strengthened_clauses: usize,
+    pub substituted_vars: usize,
+    pub failed_literals: usize,
+    pub eliminated_vars: usize,
+    pub eliminated_clauses: usize,
+}
+
+impl Preprocessor {
+    /// assigns は根での割り当て, 伝播していなくてもよい
+    pub fn new(assigns: &[Option<bool>]) -> Self {
+        let units = assigns.iter().enumerate()
+            .filter_map(|(var, assign)| assign.map(|value| {
+                if value { Literal::Pos(var) } else { Literal::Neg(var) }
+            }))
+            .collect();
+        Preprocessor {
+            clauses: Vec::new(),
+            alive: Vec::new(),
+            occurs: HashMap::new(),
+            assigns: assigns.to_vec(),
+            eliminated: vec![false; assigns.len()],
+            frozen: vec![false; assigns.len()],
+            units,
+            stack: ReconstructionStack::new(),
+            ok: true,
+
+            use_elim: true,
+            probe_budget: 1_000_000,
+            elim_occurs_limit: 16,
+
+            subsumed_clauses: 0,
+            strengthened_clauses: 0,
+            substituted_vars: 0,
+            failed_literals: 0,
+            eliminated_vars: 0,
+            eliminated_clauses: 0,
+        }
+    }
+
+    /// var を除去, 置き換えしないようにする
+    pub fn freeze(&mut self, var: usize) {
+        if var < self.frozen.len() {
+            self.frozen[var] = true;
+        }
+    }
+
+    /// 根での割り当て
+    pub fn assigns(&self) -> &[Option<bool>] {
+        &self.assigns
+    }
+
+    /// 簡略化した後の節, 単位節は含まない
+    pub fn clauses(&self) -> Clauses {
+        self.clauses.iter().zip(&self.alive)
+            .filter(|(_, &alive)| alive)
+            .map(|(clause, _)| clause.clone())
+            .collect()
+    }
+
+    pub fn into_stack(self) -> ReconstructionStack {
+        self.stack
+    }
+
+    fn value(&self, lit: &Literal) -> Option<bool> {
+        self.assigns[lit.var()].map(|value| value == lit.is_pos())
+    }
+
+    /// 根で lit を真にする
+    fn assign(&mut self, lit: Literal) {
+        match self.value(&lit) {
+            Some(true) => {},
+            Some(false) => { self.ok = false; },
+            None => {
+                self.assigns[lit.var()] = Some(lit.is_pos());
+                self.units.push_back(lit);
+            },
+        }
+    }
+
+    /// 根での割り当てで簡略化してから節を追加する
+    pub fn add_clause(&mut self, clause: &Clause) {
+        if clause.iter().any(|lit| self.value(lit) == Some(true)) {
+            return;
+        }
+        let mut clause: Clause = clause.iter()
+            .filter(|lit| self.value(lit).is_none())
+            .copied()
+            .collect();
+        match normalize_clause(&mut clause) {
+            Ok(c) => {
+                if c.len() == 1 {
+                    self.assign(c[0]);
+                    return;
+                }
+                let i = self.clauses.len();
+                for &lit in &c {
+                    self.occurs.entry(lit).or_default().push(i);
+                }
+                self.clauses.push(c);
+                self.alive.push(true);
+            },
+            Err(NormalizeError::EmptyClause) => {
+                self.ok = false;
+            },
+            Err(NormalizeError::TautologyClause) => {},
+        }
+    }
+
+    /// lit を含む, 削除されていない節のインデックス
+    fn occurrences(&self, lit: Literal) -> Vec<usize> {
+        match self.occurs.get(&lit) {
+            Some(indices) => indices.iter()
+                .copied()
+                .filter(|&i| self.alive[i] && self.clauses[i].binary_search(&lit).is_ok())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// 節を削除して, 代わりに clause を追加する
+    fn replace_clause(&mut self, i: usize, clause: &Clause) {
+        self.alive[i] = false;
+        self.add_clause(clause);
+    }
+
+    /// 根での割り当てで, 真になる節を削除し, 偽になるリテラルを節から除く
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn propagate_units(&mut self) -> bool {
+        while self.ok {
+            let lit = match self.units.pop_front() {
+                Some(lit) => lit,
+                None => break,
+            };
+            for i in self.occurrences(lit) {
+                self.alive[i] = false;
+            }
+            for i in self.occurrences(lit.not()) {
+                let clause = self.clauses[i].clone();
+                self.replace_clause(i, &clause);
+            }
+        }
+        self.ok
+    }
+
+    /// 他の節に包摂される節を削除し, self-subsuming resolution で節を縮める
+    fn subsume(&mut self) {
+        let mut order: Vec<usize> = (0..self.clauses.len()).filter(|&i| self.alive[i]).collect();
+        order.sort_by_key(|&i| self.clauses[i].len());
+
+        for i in order {
+            if !self.alive[i] {
+                continue;
+            }
+            let clause = self.clauses[i].clone();
+            // clause を部分集合に持つ節は, clause のどのリテラルも含むので1つ調べればよい
+            let min_lit = *clause.iter().min_by_key(|lit| self.occurrences(**lit).len()).unwrap();
+            for j in self.occurrences(min_lit) {
+                if j != i && is_subset(&clause, &self.clauses[j], None) {
+                    self.alive[j] = false;
+                    self.subsumed_clauses += 1;
+                }
+            }
+            for &lit in &clause {
+                for j in self.occurrences(lit.not()) {
+                    if j != i && self.alive[i] && is_subset(&clause, &self.clauses[j], Some(lit)) {
+                        // clause と clauses[j] の導出節が clauses[j] から ¬lit を除いたものになる
+                        let strengthened: Clause = self.clauses[j].iter()
+                            .filter(|&&l| l != lit.not())
+                            .copied()
+                            .collect();
+                        self.replace_clause(j, &strengthened);
+                        self.strengthened_clauses += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// 2リテラル節からなる含意グラフの強連結成分を求め, 同じ成分のリテラルを代表リテラルに置き換える
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - x と ¬x が同じ成分に入った, UNSAT
+    fn substitute_equivalences(&mut self) -> bool {
+        // ¬a -> b, ¬b -> a
+        let mut graph: HashMap<Literal, Vec<Literal>> = HashMap::new();
+        for (clause, &alive) in self.clauses.iter().zip(&self.alive) {
+            if alive && clause.len() == 2 {
+                graph.entry(clause[0].not()).or_default().push(clause[1]);
+                graph.entry(clause[1].not()).or_default().push(clause[0]);
+            }
+        }
+
+        let mut repr: HashMap<Literal, Literal> = HashMap::new();
+        for component in strongly_connected_components(&graph) {
+            let representative = *component.iter().min().unwrap();
+            for &lit in &component {
+                if component.contains(&lit.not()) {
+                    self.ok = false;
+                    return false;
+                }
+                if !self.frozen[lit.var()] {
+                    repr.insert(lit, representative);
+                }
+            }
+        }
+
+        let mut substituted: Vec<usize> = repr.iter()
+            .filter(|(lit, representative)| lit.is_pos() && lit != representative)
+            .map(|(lit, _)| lit.var())
+            .collect();
+        substituted.sort_unstable();
+        for var in substituted {
+            let lit = Literal::Pos(var);
+            let representative = repr[&lit];
+            // lit = representative となるように復元する
+            self.stack.push(lit, vec![lit, representative.not()]);
+            self.stack.push(lit.not(), vec![lit.not(), representative]);
+            self.eliminated[var] = true;
+            self.substituted_vars += 1;
+        }
+
+        for i in 0..self.clauses.len() {
+            if !self.alive[i] {
+                continue;
+            }
+            if self.clauses[i].iter().any(|lit| repr.get(lit).is_some_and(|r| r != lit)) {
+                let clause: Clause = self.clauses[i].iter()
+                    .map(|lit| *repr.get(lit).unwrap_or(lit))
+                    .collect();
+                self.replace_clause(i, &clause);
+            }
+        }
+        self.propagate_units()
+    }
+
+    /// 根で lit を仮定して単位伝播し, 割り当てを元に戻す
+    /// # Returns
+    /// * `Some(lits)` - 伝播で真になったリテラル (lit 自身を含む)
+    /// * `None` - 矛盾した, ¬lit は根で真
+    fn probe(&mut self, lit: Literal, budget: &mut usize) -> Option<Vec<Literal>> {
+        let mut trail = vec![lit];
+        self.assigns[lit.var()] = Some(lit.is_pos());
+
+        let mut conflict = false;
+        let mut head = 0;
+        'propagate: while head < trail.len() {
+            let false_lit = trail[head].not();
+            head += 1;
+            for i in self.occurrences(false_lit) {
+                *budget = budget.saturating_sub(1);
+                let mut unit = None;
+                let mut unassigned = 0;
+                for l in &self.clauses[i] {
+                    match self.value(l) {
+                        Some(true) => {
+                            unassigned = 2;
+                            break;
+                        },
+                        Some(false) => {},
+                        None => {
+                            unit = Some(*l);
+                            unassigned += 1;
+                        },
+                    }
+                }
+                match (unassigned, unit) {
+                    (0, _) => {
+                        conflict = true;
+                        break 'propagate;
+                    },
+                    (1, Some(unit)) => {
+                        self.assigns[unit.var()] = Some(unit.is_pos());
+                        trail.push(unit);
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        for l in &trail {
+            self.assigns[l.var()] = None;
+        }
+        if conflict {
+            None
+        } else {
+            Some(trail)
+        }
+    }
+
+    /// failed literal probing を行う
+    /// lit を仮定して矛盾すれば ¬lit が, lit と ¬lit のどちらを仮定しても真になるリテラルがあればそれが根で真になる
+    fn probe_failed_literals(&mut self) -> bool {
+        let mut budget = self.probe_budget;
+        for var in 0..self.assigns.len() {
+            if budget == 0 || !self.ok {
+                break;
+            }
+            if self.assigns[var].is_some() || self.eliminated[var] {
+                continue;
+            }
+            let pos = Literal::Pos(var);
+            let implied_by_pos = match self.probe(pos, &mut budget) {
+                Some(implied) => implied,
+                None => {
+                    self.failed_literals += 1;
+                    self.assign(pos.not());
+                    self.propagate_units();
+                    continue;
+                },
+            };
+            match self.probe(pos.not(), &mut budget) {
+                Some(implied) => {
+                    let implied_by_pos: HashSet<Literal> = implied_by_pos.into_iter().collect();
+                    for lit in implied {
+                        if implied_by_pos.contains(&lit) {
+                            self.assign(lit);
+                        }
+                    }
+                },
+                None => {
+                    self.failed_literals += 1;
+                    self.assign(pos);
+                },
+            }
+            self.propagate_units();
+        }
+        self.ok
+    }
+
+    /// var を含む節を全て導出節に置き換えて変数を除去する
+    /// 導出節の数が元の節の数を超えない場合だけ行う
+    fn eliminate_var(&mut self, var: usize) {
+        let pos = Literal::Pos(var);
+        let pos_clauses = self.occurrences(pos);
+        let neg_clauses = self.occurrences(pos.not());
+        if pos_clauses.is_empty() && neg_clauses.is_empty() {
+            return;
+        }
+        if pos_clauses.len() > self.elim_occurs_limit || neg_clauses.len() > self.elim_occurs_limit {
+            return;
+        }
+
+        let mut resolvents: Clauses = Vec::new();
+        for &p in &pos_clauses {
+            for &n in &neg_clauses {
+                if is_tautological_resolvent(&self.clauses[p], &self.clauses[n], pos) {
+                    continue;
+                }
+                if resolvents.len() >= pos_clauses.len() + neg_clauses.len() {
+                    return;
+                }
+                let resolvent: Clause = self.clauses[p].iter()
+                    .chain(self.clauses[n].iter())
+                    .filter(|lit| lit.var() != var)
+                    .copied()
+                    .collect();
+                resolvents.push(resolvent);
+            }
+        }
+
+        for (witness, indices) in [(pos, pos_clauses), (pos.not(), neg_clauses)] {
+            for i in indices {
+                self.alive[i] = false;
+                self.stack.push(witness, self.clauses[i].clone());
+                self.eliminated_clauses += 1;
+            }
+        }
+        self.eliminated[var] = true;
+        self.eliminated_vars += 1;
+        for resolvent in resolvents {
+            self.add_clause(&resolvent);
+        }
+    }
+
+    /// bounded variable elimination を出現数の少ない変数から行う
+    fn eliminate_vars(&mut self) -> bool {
+        let mut order: Vec<(usize, usize)> = (0..self.assigns.len())
+            .filter(|&var| self.assigns[var].is_none() && !self.eliminated[var])
+            .map(|var| {
+                let pos = self.occurrences(Literal::Pos(var)).len();
+                let neg = self.occurrences(Literal::Neg(var)).len();
+                (pos * neg, var)
+            })
+            .collect();
+        order.sort_unstable();
+
+        for (_, var) in order {
+            if !self.propagate_units() {
+                return false;
+            }
+            if self.assigns[var].is_none() && !self.eliminated[var] && !self.frozen[var] {
+                self.eliminate_var(var);
+            }
+        }
+        self.propagate_units()
+    }
+
+    /// blocked clause elimination を行う
+    /// 節 C のリテラル l について, ¬l を含む全ての節との導出節がトートロジーなら C は blocked で,
+    /// 削除しても充足可能性は変わらない
+    /// 純リテラルを含む節は導出相手がいないので blocked になり, 純リテラル除去も兼ねる
+    fn eliminate_blocked_clauses(&mut self) {
+        let mut queue: VecDeque<Literal> = self.occurs.keys().copied().collect();
+        while let Some(lit) = queue.pop_front() {
+            if self.assigns[lit.var()].is_some() || self.frozen[lit.var()] {
+                continue;
+            }
+            let others = self.occurrences(lit.not());
+            for i in self.occurrences(lit) {
+                let is_blocked = others.iter()
+                    .filter(|&&k| self.alive[k])
+                    .all(|&k| is_tautological_resolvent(&self.clauses[i], &self.clauses[k], lit));
+                if !is_blocked {
+                    continue;
+                }
+
+                self.alive[i] = false;
+                self.eliminated_clauses += 1;
+                self.stack.push(lit, self.clauses[i].clone());
+                // この節が導出相手だったリテラルは blocked になっているかもしれない
+                for &l in &self.clauses[i] {
+                    if l != lit {
+                        queue.push_back(l.not());
+                    }
+                }
+            }
+        }
+    }
+
+    /// 前処理を全て行う
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    pub fn run(&mut self) -> bool {
+        if !self.propagate_units() {
+            return false;
+        }
+        self.subsume();
+        if !self.propagate_units()
+            || !self.substitute_equivalences()
+            || !self.probe_failed_literals() {
+            return false;
+        }
+        self.subsume();
+        if !self.propagate_units() {
+            return false;
+        }
+        if self.use_elim {
+            if !self.eliminate_vars() {
+                return false;
+            }
+            self.eliminate_blocked_clauses();
+        }
+        self.ok
+    }
+}
+
+/// 含意グラフの強連結成分を Tarjan のアルゴリズムで求める
+/// 深いグラフでスタックが溢れないよう, 再帰を使わずに書いている
+fn strongly_connected_components(graph: &HashMap<Literal, Vec<Literal>>) -> Vec<Vec<Literal>> {
+    let empty = Vec::new();
+    let mut index: HashMap<Literal, usize> = HashMap::new();
+    let mut lowlink: HashMap<Literal, usize> = HashMap::new();
+    let mut on_stack: HashSet<Literal> = HashSet::new();
+    let mut stack: Vec<Literal> = Vec::new();
+    let mut components: Vec<Vec<Literal>> = Vec::new();
+
+    let mut roots: Vec<&Literal> = graph.keys().collect();
+    roots.sort_unstable();
+    for &root in roots {
+        if index.contains_key(&root) {
+            continue;
+        }
+        // (頂点, 次に見る辺の位置)
+        let mut call_stack: Vec<(Literal, usize)> = vec![(root, 0)];
+        while let Some(&mut (v, ref mut edge)) = call_stack.last_mut() {
+            if *edge == 0 && !index.contains_key(&v) {
+                let n = index.len();
+                index.insert(v, n);
+                lowlink.insert(v, n);
+                stack.push(v);
+                on_stack.insert(v);
+            }
+            let succs = graph.get(&v).unwrap_or(&empty);
+            if let Some(&w) = succs.get(*edge) {
+                *edge += 1;
+                if !index.contains_key(&w) {
+                    call_stack.push((w, 0));
+                } else if on_stack.contains(&w) {
+                    let low = lowlink[&v].min(index[&w]);
+                    lowlink.insert(v, low);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                let low = lowlink[&parent].min(lowlink[&v]);
+                lowlink.insert(parent, low);
+            }
+            if lowlink[&v] == index[&v] {
+                let mut component = Vec::new();
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack.remove(&w);
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::{Preprocessor, ReconstructionStack};
+    use crate::literal::Literal;
+    use crate::solver::Clauses;
+
+    /// 割り当てのない変数は偽として, 全ての節が充足されるか
+    fn is_satisfied(clauses: &Clauses, model: &[Option<bool>]) -> bool {
+        clauses.iter().all(|clause| clause.iter().any(|lit| model[lit.var()].unwrap_or(false) == lit.is_pos()))
+    }
+
+    #[test]
+    fn extend_model_after_substitution() {
+        // x0 = x1, x1 = x2 を含む論理式
+        let clauses = vec![
+            vec![Literal::Pos(0), Literal::Neg(1)],
+            vec![Literal::Neg(0), Literal::Pos(1)],
+            vec![Literal::Pos(1), Literal::Neg(2)],
+            vec![Literal::Neg(1), Literal::Pos(2)],
+            vec![Literal::Pos(0), Literal::Pos(2), Literal::Pos(3)],
+        ];
+        let mut preprocessor = Preprocessor::new(&[None; 4]);
+        for clause in &clauses {
+            preprocessor.add_clause(clause);
+        }
+        assert!(preprocessor.run());
+
+        let mut data = Vec::new();
+        preprocessor.into_stack().write(4, &mut data).unwrap();
+        let (size_vars, stack) = ReconstructionStack::parse(&String::from_utf8(data).unwrap()).unwrap();
+        assert_eq!(size_vars, 4);
+
+        let mut model = vec![Some(true), None, None, None];
+        stack.extend_model(&mut model);
+        for clause in &clauses {
+            assert!(clause.iter().any(|lit| model[lit.var()] == Some(lit.is_pos())));
+        }
+    }
+
+    #[test]
+    fn blocked_clause_elimination() {
+        // x0 ∨ x1 は ¬x0 ∨ ¬x1 との x0 での導出節が恒真なので blocked
+        // x2, x3 は凍結しているので, それらの節は残る
+        let clauses = vec![
+            vec![Literal::Pos(0), Literal::Pos(1)],
+            vec![Literal::Neg(0), Literal::Neg(1)],
+            vec![Literal::Pos(2), Literal::Pos(3)],
+            vec![Literal::Neg(2), Literal::Neg(3)],
+        ];
+        let mut preprocessor = Preprocessor::new(&[None; 4]);
+        preprocessor.freeze(2);
+        preprocessor.freeze(3);
+        for clause in &clauses {
+            preprocessor.add_clause(clause);
+        }
+        preprocessor.eliminate_blocked_clauses();
+        assert_eq!(preprocessor.eliminated_clauses, 2);
+        assert_eq!(preprocessor.clauses(), clauses[2..].to_vec());
+
+        // 残った節のモデルは, 削除した x0 ∨ x1 を偽にするが, 復元すれば元の論理式のモデルになる
+        let mut model = vec![None, None, Some(true), Some(false)];
+        assert!(is_satisfied(&preprocessor.clauses(), &model));
+        assert!(!is_satisfied(&clauses, &model));
+        preprocessor.into_stack().extend_model(&mut model);
+        assert!(is_satisfied(&clauses, &model));
+    }
+
+    /// DIMACS 形式のリテラルの列から論理式を作る
+    fn clauses_from(clauses: &[&[i32]]) -> Clauses {
+        clauses.iter()
+            .map(|clause| clause.iter().map(|&lit| Literal::from_dimacs(lit)).collect())
+            .collect()
+    }
+
+    fn preprocessor_from(size_vars: usize, clauses: &Clauses) -> Preprocessor {
+        let mut preprocessor = Preprocessor::new(&vec![None; size_vars]);
+        for clause in clauses {
+            preprocessor.add_clause(clause);
+        }
+        preprocessor
+    }
+
+    #[test]
+    fn eliminate_var_within_clause_count_bound() {
+        // x1 の導出節は 2 つで, 元の 3 つの節を超えないので除去する
+        let clauses = clauses_from(&[&[1, 2], &[1, 3], &[-1, 4]]);
+        let mut preprocessor = preprocessor_from(4, &clauses);
+        preprocessor.eliminate_var(0);
+        assert_eq!(preprocessor.eliminated_vars, 1);
+        assert_eq!(preprocessor.eliminated_clauses, 3);
+        assert_eq!(preprocessor.clauses(), clauses_from(&[&[2, 4], &[3, 4]]));
+
+        let mut model = vec![None, Some(false), Some(false), Some(true)];
+        assert!(is_satisfied(&preprocessor.clauses(), &model));
+        preprocessor.into_stack().extend_model(&mut model);
+        assert!(is_satisfied(&clauses, &model));
+    }
+
+    #[test]
+    fn eliminate_var_beyond_clause_count_bound() {
+        // x1 の導出節は 3 * 3 = 9 つで, 元の 6 つの節を超えるので除去しない
+        let clauses = clauses_from(&[&[1, 2], &[1, 3], &[1, 4], &[-1, 5], &[-1, 6], &[-1, 7]]);
+        let mut preprocessor = preprocessor_from(7, &clauses);
+        preprocessor.eliminate_var(0);
+        assert_eq!(preprocessor.eliminated_vars, 0);
+        assert_eq!(preprocessor.clauses(), clauses);
+        assert!(preprocessor.into_stack().is_empty());
+    }
+
+    #[test]
+    fn subsume_and_strengthen() {
+        // (x1 ∨ x2) は (x1 ∨ x2 ∨ x3) を包摂し, (¬x1 ∨ x2 ∨ x4) を (x2 ∨ x4) に縮める
+        let clauses = clauses_from(&[&[1, 2], &[1, 2, 3], &[-1, 2, 4]]);
+        let mut preprocessor = preprocessor_from(4, &clauses);
+        preprocessor.subsume();
+        assert_eq!(preprocessor.subsumed_clauses, 1);
+        assert_eq!(preprocessor.strengthened_clauses, 1);
+        assert_eq!(preprocessor.clauses(), clauses_from(&[&[1, 2], &[2, 4]]));
+    }
+
+    #[test]
+    fn probe_failed_literals() {
+        // x1 を仮定すると x2 と ¬x2 が導かれるので ¬x1 が根で真
+        // 続いて ¬x3 を仮定すると x4 と ¬x5 が導かれて矛盾するので x3 が根で真
+        // x4 と ¬x4 のどちらを仮定しても x5 が導かれるので x5 が根で真
+        let clauses = clauses_from(&[&[-1, 2], &[-1, -2], &[1, 3, 4], &[4, 5], &[-4, 5], &[3, -5, 1]]);
+        let mut preprocessor = preprocessor_from(5, &clauses);
+        assert!(preprocessor.probe_failed_literals());
+        assert_eq!(preprocessor.failed_literals, 2);
+        assert_eq!(preprocessor.assigns()[0], Some(false));
+        assert_eq!(preprocessor.assigns()[2], Some(true));
+        assert_eq!(preprocessor.assigns()[3], None);
+        assert_eq!(preprocessor.assigns()[4], Some(true));
+        assert!(preprocessor.clauses().is_empty());
+    }
+
+    #[test]
+    fn frozen_vars_are_not_eliminated() {
+        let clauses = clauses_from(&[&[1, 2], &[1, 3], &[-1, 4], &[2, -3]]);
+        let witnesses = |frozen: &[usize]| {
+            let mut preprocessor = preprocessor_from(4, &clauses);
+            for &var in frozen {
+                preprocessor.freeze(var);
+            }
+            assert!(preprocessor.run());
+            let remaining = preprocessor.clauses();
+            let witnesses: Vec<usize> = preprocessor.into_stack().iter().map(|(witness, _)| witness.var()).collect();
+            (remaining, witnesses)
+        };
+
+        // x1 以外を凍結すると x1 が除去される
+        let (_, eliminated) = witnesses(&[1, 2, 3]);
+        assert!(eliminated.contains(&0));
+        // 全て凍結すると, 何も除去されず節もそのまま残る
+        let (remaining, eliminated) = witnesses(&[0, 1, 2, 3]);
+        assert!(eliminated.is_empty());
+        assert_eq!(remaining, clauses);
+    }
+}
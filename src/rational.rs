@@ -0,0 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::bigint::BigUint;
+
+/// 非負の有理数, 重み付きモデル数を厳密に計算するのに使う
+/// 常に既約分数で持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rational {
+    num: BigUint,
+    den: BigUint,
+}
+
+impl Rational {
+    pub fn new(num: BigUint, den: BigUint) -> Self {
+        assert!(!den.is_zero(), "zero denominator");
+        let g = num.gcd(&den);
+        if g.is_zero() || g == BigUint::one() {
+            return Rational { num, den };
+        }
+        Rational {
+            num: num.div_rem(&g).0,
+            den: den.div_rem(&g).0,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Rational { num: BigUint::zero(), den: BigUint::one() }
+    }
+
+    pub fn one() -> Self {
+        Rational { num: BigUint::one(), den: BigUint::one() }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num.is_zero()
+    }
+
+    pub fn add(&self, other: &Rational) -> Rational {
+        if self.den == other.den {
+            return Rational::new(&self.num + &other.num, self.den.clone());
+        }
+        Rational::new(
+            &(&self.num * &other.den) + &(&other.num * &self.den),
+            &self.den * &other.den,
+        )
+    }
+
+    pub fn mul(&self, other: &Rational) -> Rational {
+        if self.is_zero() || other.is_zero() {
+            return Rational::zero();
+        }
+        Rational::new(&self.num * &other.num, &self.den * &other.den)
+    }
+
+    /// 近似値
+    pub fn to_f64(&self) -> f64 {
+        // 分子と分母が大きくても inf / inf にならないように, 同じだけ縮めてから割る
+        let shift = self.num.bits().max(self.den.bits()).saturating_sub(1000);
+        self.num.shr(shift).to_f64() / self.den.shr(shift).to_f64()
+    }
+}
+
+/// 指数表記の指数の絶対値の上限
+const MAX_EXPONENT: u64 = 4096;
+
+/// `3`, `0.25`, `1.5e-3`, `1/3` の形の非負の数を読む
+impl FromStr for Rational {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid number: {}", s);
+        if let Some((num, den)) = s.split_once('/') {
+            let num: Rational = num.parse()?;
+            let den: Rational = den.parse()?;
+            if den.is_zero() {
+                return Err(invalid());
+            }
+            return Ok(num.mul(&Rational::new(den.den, den.num)));
+        }
+
+        // 10^exp を作るので, 指数は入力の長さに比べて大きすぎないものに限る
+        let (mantissa, exp) = match s.split_once(['e', 'E']) {
+            Some((mantissa, exp)) => {
+                let exp = exp.parse::<i64>().map_err(|_| invalid())?;
+                if exp.unsigned_abs() > MAX_EXPONENT {
+                    return Err(format!("exponent is too large: {}", s));
+                }
+                (mantissa, exp)
+            },
+            None => (s, 0),
+        };
+        let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(invalid());
+        }
+        let num: BigUint = format!("{}{}", int_part, frac_part).parse().map_err(|_| invalid())?;
+        let exp = exp - frac_part.len() as i64;
+        let pow = BigUint::from_u64(10).pow(u32::try_from(exp.unsigned_abs()).map_err(|_| invalid())?);
+        Ok(if exp >= 0 {
+            Rational::new(&num * &pow, BigUint::one())
+        } else {
+            Rational::new(num, pow)
+        })
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.den == BigUint::one() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(num: u64, den: u64) -> Rational {
+        Rational::new(BigUint::from_u64(num), BigUint::from_u64(den))
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(ratio(6, 4), ratio(3, 2));
+        assert_eq!(ratio(0, 5), Rational::zero());
+        assert_eq!(ratio(7, 7), Rational::one());
+        assert_eq!(ratio(6, 4).to_string(), "3/2");
+        assert_eq!(ratio(0, 5).to_string(), "0");
+        assert_eq!(ratio(8, 4).to_string(), "2");
+    }
+
+    #[test]
+    fn add_and_mul() {
+        assert_eq!(ratio(1, 3).add(&ratio(1, 6)), ratio(1, 2));
+        assert_eq!(ratio(1, 4).add(&ratio(3, 4)), Rational::one());
+        assert_eq!(ratio(2, 3).mul(&ratio(3, 4)), ratio(1, 2));
+        assert_eq!(Rational::zero().mul(&ratio(5, 3)), Rational::zero());
+        assert_eq!(ratio(5, 3).add(&Rational::zero()), ratio(5, 3));
+        assert_eq!(ratio(1, 3).to_f64(), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("3".parse::<Rational>(), Ok(ratio(3, 1)));
+        assert_eq!("0.25".parse::<Rational>(), Ok(ratio(1, 4)));
+        assert_eq!("1.5e-3".parse::<Rational>(), Ok(ratio(3, 2000)));
+        assert_eq!("2E2".parse::<Rational>(), Ok(ratio(200, 1)));
+        assert_eq!(".5".parse::<Rational>(), Ok(ratio(1, 2)));
+        assert_eq!("2/6".parse::<Rational>(), Ok(ratio(1, 3)));
+        assert_eq!("0.5/0.25".parse::<Rational>(), Ok(ratio(2, 1)));
+        assert!("1/0".parse::<Rational>().is_err());
+        assert!(".".parse::<Rational>().is_err());
+        assert!("-1".parse::<Rational>().is_err());
+        assert!("abc".parse::<Rational>().is_err());
+        // 大きすぎる指数は 10^e を作らずにエラーにする
+        assert_eq!("1e4096".parse::<Rational>().map(|r| r.num.bits()), Ok(13607));
+        assert!("1e4097".parse::<Rational>().is_err());
+        assert!("1e-1000000000".parse::<Rational>().is_err());
+        assert!("1e99999999999999999999".parse::<Rational>().is_err());
+    }
+}
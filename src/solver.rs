@@ -1,19 +1,30 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use log::{debug, error, info};
 
 use crate::literal::Literal;
 use crate::clause::{Clause, NormalizeError, normalize_clause};
+use crate::preprocess::{Preprocessor, ReconstructionStack};
+use crate::local_search::LocalSearch;
+use crate::portfolio::ClauseExchange;
+use crate::random::Random;
+use crate::unsat_core::{Origin, Reason};
+use crate::lrat::LratWriter;
+use crate::xor::{XorAdd, XorEngine, find_xors, xor_to_clauses};
 
 /// 所有権の関係で探索により変更される変数を分離
 /// Solver から Searcher にある比較的単純な関数を呼んで操作する
-struct Searcher {
+pub(crate) struct Searcher {
     pub size_vars: usize,               // 変数の数
     pub assigns: Vec<Option<bool>>,     // 各変数の暫定的な割り当てを保持, 変数の数と同じ長さ
     pub levels: Vec<usize>,             // 各変数の決定レベルを保持, 変数の数と同じ長さ
     pub trail: Vec<Option<Literal>>,    // 探索, 割り当ての履歴を記録, (もしかしたらOption外せるかも)
     pub trail_tail: usize,              // trail の末尾を保持(いちいちリサイズしていたら大変)
     pub trail_lim: Vec<usize>,          // 決定変数のtrail上のindexを持つ, 末尾が直近の決定変数
+    pub qhead: usize,                   // trail 上で次に単位伝播するリテラルの位置
+    pub polarity: Vec<bool>,            // 各変数を決定変数にするときの真偽値, 最後に割り当てた値を保存しておく
 }
 
 impl Searcher {
@@ -25,6 +36,8 @@ impl Searcher {
             trail: Vec::new(),
             trail_tail: 0,
             trail_lim: Vec::new(),
+            qhead: 0,
+            polarity: Vec::new(),
         }
     }
 
@@ -38,11 +51,7 @@ impl Searcher {
     /// * Some(flag) - flag が true なら成立, flag が false なら矛盾
     /// * None - まだ値が割り当てられていない
     pub fn is_satisfied(&self, lit: &Literal) -> Option<bool> {
-        if let Some(value) = self.assigns[lit.var()] {
-            return Some(value == lit.is_pos());
-        } else {
-            return None;
-        }
+        self.assigns[lit.var()].map(|value| value == lit.is_pos())
     }
 
     /// 入力された節の番号の大きい節を見て, 変数のサイズを調節する
@@ -53,6 +62,7 @@ impl Searcher {
             self.assigns.resize(self.size_vars, None);
             self.levels.resize(self.size_vars, 0);
             self.trail.resize(self.size_vars, None);
+            self.polarity.resize(self.size_vars, false);
         }
     }
 
@@ -83,7 +93,7 @@ impl Searcher {
             self.trail[self.trail_tail] = Some(lit);
             self.trail_tail += 1;
         }
-        return true;
+        true
     }
 
     /// 値を割り当てる
@@ -93,26 +103,6 @@ impl Searcher {
         self.assign_bool(lit);
     }
 
-    /// 与えられた Clause が現在の割り当てで真かどうか判定する
-    /// # Returns
-    /// * `true` - 現在の割り当てで Clause が偽になる場合
-    /// * `false` - 真になる場合 || 真になるか偽になるか分からない場合
-    pub fn is_falsified_clause(&self, clause: &Clause) -> bool {
-        for lit in clause {
-            if let Some(is_satisfied) = self.is_satisfied(lit) {
-                // 1つでも満たせば真(偽にならない)
-                if is_satisfied {
-                    return false;
-                }
-            } else {
-                // 真になるか偽になるか分からない
-                return false;
-            }
-        }
-        // 全部偽になる
-        return true;
-    }
-
     pub fn canceluntil(&mut self, level: usize) {
         if self.dlevel() <= level {
             return;
@@ -121,26 +111,28 @@ impl Searcher {
         for c in bound..self.trail_tail {
             if let Some(lit) = self.trail[c] {
                 self.assigns[lit.var()] = None;
+                self.polarity[lit.var()] = lit.is_pos();
             } else {
                 error!("trail: 1度も初期化されてない部分にアクセス");
             }
         }
         self.trail_tail = bound;
         self.trail_lim.resize(level, 0);
+        self.qhead = self.qhead.min(bound);
     }
 
     /// # Returns
     /// * `true` - 成功
     /// * `false` - バックトラック失敗, UNSAT
     pub fn backtrack(&mut self) -> bool {
-        if self.dlevel() <= 0 {
+        if self.dlevel() == 0 {
             return false;
         }
         let blevel = self.dlevel() - 1;
         let lit = self.trail[self.trail_lim[blevel]].unwrap();
         self.canceluntil(blevel);
         self.assign_bool(lit.not());
-        return true;
+        true
     }
 }
 
@@ -149,6 +141,20 @@ impl Searcher {
 pub struct Stats {
     pub conflicts: usize,
     pub decisions: usize,
+    pub propagations: usize,
+    pub restarts: usize,
+    pub rephases: usize,
+    pub subsumed_clauses: usize,
+    pub substituted_vars: usize,
+    pub failed_literals: usize,
+    pub eliminated_vars: usize,
+    pub eliminated_clauses: usize,
+    pub vivified_literals: usize,
+    pub learnt_clauses: usize,
+    pub deleted_clauses: usize,
+    pub lookaheads: usize,
+    pub imported_clauses: usize,
+    pub xors: usize,
     clauses: usize,
     clauses_literals: usize,
 }
@@ -158,27 +164,116 @@ impl Stats {
         Stats {
             conflicts: 0,
             decisions: 0,
+            propagations: 0,
+            restarts: 0,
+            rephases: 0,
+            subsumed_clauses: 0,
+            substituted_vars: 0,
+            failed_literals: 0,
+            eliminated_vars: 0,
+            eliminated_clauses: 0,
+            vivified_literals: 0,
+            learnt_clauses: 0,
+            deleted_clauses: 0,
+            lookaheads: 0,
+            imported_clauses: 0,
+            xors: 0,
             clauses: 0,
             clauses_literals: 0,
         }
     }
 }
 
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Clause を && でつないだもの
 pub type Clauses = Vec<Clause>;
+
+/// 矛盾の解析で学習した節の情報
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Learnt {
+    pub lbd: usize,         // 学習したときに節のリテラルが属していた決定レベルの数 (LBD), 小さいほど有用
+    pub vivified: bool,     // vivification を済ませたか
+}
+
 pub struct Solver {
-    // 探索する論理式
-    clauses: Clauses,
+    // 探索する論理式, 元の節と学習節
+    pub(crate) clauses: Clauses,
+    pub(crate) learnts: Vec<Option<Learnt>>, // clauses の各節が学習節 (他の Solver から取り込んだ節を含む) ならその情報
     // 見つかった解
     pub model: Vec<Option<bool>>,
 
     // 探索に使う変数
     root_level: usize,
-    searcher: Searcher,
+    pub(crate) searcher: Searcher,
+    assumptions: Vec<Literal>,              // 今回の探索で仮定するリテラル, 決定レベル 1.. の決定変数にする
+    assumption_index: usize,                // 次に割り当てる仮定の assumptions 上のインデックス
+    assumption_levels: usize,               // 仮定を割り当てた決定レベルの数
+    conflict: Vec<Literal>,                 // 仮定の下で UNSAT になったときの, 原因になった仮定
+    pub interrupt: Arc<AtomicBool>,         // true になったら探索を打ち切る, 他のスレッドから止めるのに使う
 
     // 監視リテラルによる単位伝播に使う変数
     watched_lit_indices: HashMap<Literal, HashSet<usize>>,  // focused_lit[literal] = ~literalを監視リテラルに持つ, Clauseのclauses上のインデックス
 
+    // XOR 制約に使う変数
+    pub(crate) xor: XorEngine,              // Gauss-Jordan 消去した XOR 制約, 伝播に使う
+    pub(crate) xors: Vec<(Vec<usize>, bool)>, // 加えた XOR 制約, simplified_clauses で節に戻すのに使う
+    pub use_xor_detection: bool,            // 前処理で節で符号化された XOR 制約を見つけるか
+    pub xor_detection_size: usize,          // 見つける XOR 制約の変数の数の上限
+
+    // UNSAT core と証明に使う変数
+    pub track_core: bool,                   // 元の節の番号を記録し, UNSAT のときに使った元の節 (UNSAT core) を求めるか, 節を加える前に設定する
+    pub(crate) reasons: Vec<Reason>,        // 各変数の割り当ての理由, 矛盾の解析に使う, 根での割り当ては track_core か証明を書き出すときだけ記録する
+    pub(crate) origins: Vec<Origin>,        // clauses の各節の導き方
+    pub(crate) proof: Option<LratWriter>,   // LRAT 形式の証明の書き出し先
+    pub(crate) conflict_clause: Option<usize>, // 直前の矛盾で偽になった節の clauses 上のインデックス, XOR 制約なら None
+    xor_conflict: Clause,                   // 直前の矛盾が XOR 制約のときに, 全てのリテラルが偽になった節
+    pub(crate) core: Option<Vec<usize>>,    // 直前に UNSAT になったときの UNSAT core
+    pub(crate) added_clauses: usize,       // add_clause を呼んだ回数, 元の節の番号にする
+    pub(crate) untracked: bool,             // 理由を記録せずに節か XOR 制約を加えたか, 後から track_core を設定しても追跡しない
+
+    // 前処理に使う変数
+    pub use_preprocess: bool,               // 探索の前に前処理を行うか
+    pub use_elim: bool,                     // 前処理で変数除去と blocked clause elimination を行うか
+    frozen: Vec<bool>,                      // 前処理で除去しない変数
+    preprocessed: bool,                     // 前処理を済ませたか
+    ok: bool,                               // false なら根で矛盾している
+    pub(crate) elim_stack: ReconstructionStack, // 削除した節, モデルの復元に使う
+
+    // リスタートと inprocessing に使う変数
+    // リスタートでは根まで戻り, 学習節の削除と inprocessing を行ってから探索し直す
+    pub restart_first: usize,               // 最初のリスタートまでの conflict 数
+    pub restart_inc: f64,                   // リスタートごとに conflict 数の上限を何倍にするか
+    pub reduce_first: usize,                // 学習節の数がこれを超えたら, リスタートで学習節を削除する
+    pub reduce_inc: f64,                    // 削除するごとに学習節の数の上限を何倍にするか
+    reductions: usize,                      // 学習節を削除した回数
+    pub use_vivify: bool,                   // リスタートの合間に学習節と元の節の vivification を行うか
+    pub vivify_effort: f64,                 // 直前の探索での伝播回数に対する, vivification の伝播回数の割合
+    vivify_next: usize,                     // 次に vivification する節の clauses 上のインデックス
+    pub use_rephase: bool,                  // リスタートの合間に局所探索を行い, その結果を決定変数の真偽値にするか
+    pub rephase_interval: usize,            // 何回のリスタートごとに局所探索を行うか
+    pub rephase_flips: usize,               // 1回の局所探索での反転回数の上限
+    pub seed: u64,                          // 局所探索と決定変数の選択に使う乱数のシード
+    pub random_var_freq: f64,               // 決定変数をランダムに選ぶ確率
+    pub initial_phase: bool,                // 最初に決定変数にするときの真偽値
+    rng: Random,
+
+    // 並列探索での節の共有に使う変数
+    exchange: Option<Arc<ClauseExchange>>,  // 他の Solver と節をやり取りする置き場
+    exchange_id: usize,                     // exchange 上でのこの Solver の番号
+    exchange_cursor: usize,                 // exchange から次に読む節の通し番号
+    exported_units: usize,                  // 共有済みの, 根での割り当ての trail 上の数
+    exports: Clauses,                       // 次のリスタートで共有する節
+    pub share_lbd_limit: usize,             // LBD がこれ以下の学習節を共有する
+
+    // 先読みによる探索に使う変数
+    pub lookahead_candidates: usize,        // 1つの節点で先読みする変数の数の上限
+    pub double_lookahead_candidates: usize, // 2段目の先読みで調べる変数の数の上限
+
     // ログ等
     pub stats: Stats,
 }
@@ -187,28 +282,275 @@ impl Solver {
     pub fn new() -> Self {
         Solver {
             clauses: Vec::new(),
+            learnts: Vec::new(),
             model: Vec::new(),
 
             watched_lit_indices: HashMap::new(),
 
+            xor: XorEngine::new(),
+            xors: Vec::new(),
+            use_xor_detection: true,
+            xor_detection_size: 5,
+
             root_level: 0,
             searcher: Searcher::new(),
+            assumptions: Vec::new(),
+            assumption_index: 0,
+            assumption_levels: 0,
+            conflict: Vec::new(),
+            interrupt: Arc::new(AtomicBool::new(false)),
+
+            track_core: false,
+            reasons: Vec::new(),
+            origins: Vec::new(),
+            proof: None,
+            conflict_clause: None,
+            xor_conflict: Vec::new(),
+            core: None,
+            added_clauses: 0,
+            untracked: false,
+
+            use_preprocess: true,
+            use_elim: true,
+            frozen: Vec::new(),
+            preprocessed: false,
+            ok: true,
+            elim_stack: ReconstructionStack::new(),
+
+            restart_first: 100,
+            restart_inc: 1.5,
+            reduce_first: 2000,
+            reduce_inc: 1.1,
+            reductions: 0,
+            use_vivify: true,
+            vivify_effort: 0.1,
+            vivify_next: 0,
+            use_rephase: true,
+            rephase_interval: 2,
+            rephase_flips: 50_000,
+            seed: 0,
+            random_var_freq: 0.0,
+            initial_phase: false,
+            rng: Random::new(0),
+
+            exchange: None,
+            exchange_id: 0,
+            exchange_cursor: 0,
+            exported_units: 0,
+            exports: Vec::new(),
+            share_lbd_limit: 4,
+
+            lookahead_candidates: 64,
+            double_lookahead_candidates: 8,
 
             stats: Stats::new(),
         }
     }
 
+    /// clauses の [0], [1] を監視リテラルとして付け直す
+    /// 前処理で clauses 上のインデックスが変わったときに呼ぶ
+    fn attach_all(&mut self) {
+        for watcher in self.watched_lit_indices.values_mut() {
+            watcher.clear();
+        }
+        for (i, clause) in self.clauses.iter().enumerate() {
+            self.watched_lit_indices.get_mut(&clause[0].not()).unwrap().insert(i);
+            self.watched_lit_indices.get_mut(&clause[1].not()).unwrap().insert(i);
+        }
+    }
+
+    /// 探索の前に1度だけ行う前処理
     /// # Returns
-    /// * `true` - 現在の割り当てで, Clauses が偽になる場合
-    /// * `false` - 真になる場合 || 真になるか偽になるか分からない場合
-    fn is_falsified(&self) -> bool {
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn preprocess(&mut self) -> bool {
+        self.preprocessed = true;
+        // UNSAT core や証明を求めるときは, 節を書き換えずに元の節の番号との対応を保つ
+        if !self.use_preprocess || self.tracking() {
+            return true;
+        }
+        if self.use_xor_detection && !self.detect_xors() {
+            return false;
+        }
+
+        let mut preprocessor = Preprocessor::new(&self.searcher.assigns);
+        preprocessor.use_elim = self.use_elim;
+        for (var, &frozen) in self.frozen.iter().enumerate() {
+            if frozen {
+                preprocessor.freeze(var);
+            }
+        }
         for clause in &self.clauses {
-            if self.searcher.is_falsified_clause(clause) {
-                // 1つでも偽なら falsifed
-                return true;
+            preprocessor.add_clause(clause);
+        }
+        if !preprocessor.run() {
+            return false;
+        }
+
+        self.stats.subsumed_clauses += preprocessor.subsumed_clauses + preprocessor.strengthened_clauses;
+        self.stats.substituted_vars += preprocessor.substituted_vars;
+        self.stats.failed_literals += preprocessor.failed_literals;
+        self.stats.eliminated_vars += preprocessor.eliminated_vars;
+        self.stats.eliminated_clauses += preprocessor.eliminated_clauses;
+        info!("preprocess: {} subsumed, {} substituted, {} failed literals, {} eliminated vars, {} eliminated clauses",
+            self.stats.subsumed_clauses,
+            self.stats.substituted_vars,
+            self.stats.failed_literals,
+            self.stats.eliminated_vars,
+            self.stats.eliminated_clauses,
+        );
+
+        for (var, assign) in preprocessor.assigns().iter().enumerate() {
+            if self.searcher.assigns[var].is_none() {
+                if let Some(value) = assign {
+                    let lit = if *value { Literal::Pos(var) } else { Literal::Neg(var) };
+                    self.searcher.assign_bool(lit);
+                }
             }
         }
-        false
+        self.clauses = preprocessor.clauses();
+        self.learnts = vec![None; self.clauses.len()];
+        self.elim_stack = preprocessor.into_stack();
+        self.attach_all();
+        true
+    }
+
+    /// 探索をせずに前処理だけ行う
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - UNSAT
+    pub fn simplify(&mut self) -> bool {
+        if self.ok && !self.preprocessed {
+            self.rng = Random::new(self.seed);
+            self.searcher.polarity.fill(self.initial_phase);
+            self.ok = self.preprocess();
+        }
+        self.ok
+    }
+
+    /// 前処理で var を除去しないようにする
+    /// 解いた後に節や仮定に使う変数は, 最初に解く前に freeze しておくと除去した節を戻さずに済む
+    pub fn freeze(&mut self, var: usize) {
+        if self.frozen.len() <= var {
+            self.frozen.resize(var + 1, false);
+        }
+        self.frozen[var] = true;
+    }
+
+    /// 前処理で除去した変数を lits が含んでいれば, その変数を witness に持つ節を論理式に戻す
+    /// 除去した変数を含む節や仮定を, 解いた後に加えても正しく扱えるようにする
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn restore_eliminated(&mut self, lits: &[Literal]) -> bool {
+        if self.elim_stack.is_empty() {
+            return true;
+        }
+        let restored = self.elim_stack.restore(lits.iter().map(|lit| lit.var()));
+        if !restored.is_empty() {
+            debug!("restore {} eliminated clauses", restored.len());
+        }
+        restored.into_iter().all(|mut clause| self.add_clause(&mut clause))
+    }
+
+    /// 他の Solver と exchange を通して節を共有する, id は exchange 上でこの Solver を区別する番号
+    pub fn attach_exchange(&mut self, exchange: Arc<ClauseExchange>, id: usize) {
+        self.exchange = Some(exchange);
+        self.exchange_id = id;
+    }
+
+    /// 根での新しい割り当てと探索で導いた節を exchange に出し, 他の Solver が出した節を取り込む
+    /// 共有する節は全て元の論理式から導かれるので, 取り込んでも解は変わらない
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn exchange_clauses(&mut self) -> bool {
+        let exchange = match &self.exchange {
+            Some(exchange) => Arc::clone(exchange),
+            None => return true,
+        };
+
+        let root_tail = self.searcher.trail_lim.first().copied().unwrap_or(self.searcher.trail_tail);
+        let mut exports = std::mem::take(&mut self.exports);
+        exports.extend(self.searcher.trail[self.exported_units..root_tail].iter().map(|lit| vec![lit.unwrap()]));
+        self.exported_units = root_tail;
+        if !exports.is_empty() {
+            exchange.export(self.exchange_id, exports);
+        }
+
+        let imports = exchange.import(self.exchange_id, &mut self.exchange_cursor);
+        for clause in imports {
+            if clause.iter().any(|lit| self.searcher.is_satisfied(lit) == Some(true)) {
+                continue;
+            }
+            // 根で偽のリテラルを除いてから監視する
+            let mut clause: Clause = clause.into_iter()
+                .filter(|lit| self.searcher.is_satisfied(lit).is_none())
+                .collect();
+            match normalize_clause(&mut clause) {
+                Ok(c) if c.len() == 1 => {
+                    self.searcher.assign_bool(c[0]);
+                },
+                Ok(c) => {
+                    // 取り込んだ節は学習節と同じく削除してよい
+                    let lbd = c.len();
+                    self.attach_clause(c, Some(Learnt { lbd, vivified: false }));
+                },
+                Err(NormalizeError::TautologyClause) => {},
+                Err(NormalizeError::EmptyClause) => return false,
+            }
+            self.stats.imported_clauses += 1;
+        }
+        !self.propagate()
+    }
+
+    fn is_interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::Relaxed)
+    }
+
+    /// 変数の数
+    pub fn size_vars(&self) -> usize {
+        self.searcher.size_vars
+    }
+
+    /// 新しい変数を作る, 補助変数を使う符号化などで使う
+    /// # Returns
+    /// * 作った変数の番号
+    pub fn new_var(&mut self) -> usize {
+        let var = self.searcher.size_vars;
+        let lit = Literal::Pos(var);
+        self.searcher.update_size_vars(&vec![lit]);
+        self.register_lits(&[lit]);
+        var
+    }
+
+    /// 根での割り当てを単位節にしたものと元の節, 学習節と XOR 制約は含まない
+    fn root_clauses(&self) -> Clauses {
+        let root_tail = self.searcher.trail_lim.first().copied().unwrap_or(self.searcher.trail_tail);
+        let mut clauses: Clauses = self.searcher.trail[..root_tail]
+            .iter()
+            .map(|lit| vec![lit.unwrap()])
+            .collect();
+        clauses.extend(self.clauses.iter()
+            .zip(&self.learnts)
+            .filter(|(_, learnt)| learnt.is_none())
+            .map(|(clause, _)| clause.clone()));
+        clauses
+    }
+
+    /// 現在の論理式, 根での割り当ては単位節にする
+    /// XOR 制約は節で符号化するので, 長い XOR 制約を加えた場合は節の数が大きくなる
+    pub fn simplified_clauses(&self) -> Clauses {
+        let mut clauses = self.root_clauses();
+        for (vars, parity) in &self.xors {
+            clauses.extend(xor_to_clauses(vars, *parity));
+        }
+        clauses
+    }
+
+    /// 前処理で削除した節, 元の論理式のモデルを作るのに使う
+    pub fn reconstruction_stack(&self) -> &ReconstructionStack {
+        &self.elim_stack
     }
 
     /// watched[0] か [1] のどちらかは false_litであるwatchedを受け取り
@@ -225,11 +567,13 @@ impl Solver {
     /// # Returns
     /// * `true` - backtrack する必要あり (現在の割り当てで, Clauses が偽になる場合)
     /// * `false` - backtrack する必要なし (真になる場合 || 真になるか偽になるか分からない場合)
-    fn propagate(&mut self) -> bool {
-        let mut c = 0;
-        while let Some(lit) = self.searcher.trail[c] {
+    pub(crate) fn propagate(&mut self) -> bool {
+        while self.searcher.qhead < self.searcher.trail_tail {
+            let lit = self.searcher.trail[self.searcher.qhead].unwrap();
+            self.stats.propagations += 1;
             let false_lit = lit.not();
-            let watcher = self.watched_lit_indices.get(&lit).unwrap().clone();
+            // どの節にも現れないリテラルは監視リストを持たない
+            let watcher = self.watched_lit_indices.get(&lit).cloned().unwrap_or_default();
             'clause: for &i in watcher.iter() {
                 let clause= &mut self.clauses[i];
                 Solver::align_clause(clause, false_lit);
@@ -242,7 +586,7 @@ impl Solver {
                 for k in 2..clause.len() {
                     // 未割り当てか, true となっているリテラルを探す
                     let is_satisfied = self.searcher.is_satisfied(&clause[k]);
-                    if is_satisfied == Some(true) || is_satisfied == None {
+                    if is_satisfied == Some(true) || is_satisfied.is_none() {
                         // 変数の取替操作
                         clause[1] = clause[k];
                         clause[k] = false_lit;
@@ -257,50 +601,504 @@ impl Solver {
                     // 矛盾!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
                     // clause[0] も false 確定
                     // backtrack させる
+                    self.conflict_clause = Some(i);
                     return true;
 
                 } else {
                     // clause[0] は未割り当て (true の場合はもう見た)
                     // clause[0] を true に割当
-                    self.searcher.assign_bool(clause[0]);
+                    let implied = clause[0];
+                    self.searcher.assign_bool(implied);
+                    self.set_reason(implied.var(), Reason::Clause(i));
                 }
             }
 
-            c += 1;
-            if c == self.searcher.trail_tail {
-                break;
+            if !self.xor.is_empty() {
+                match self.xor.assign(lit.var(), &self.searcher.assigns) {
+                    Ok(implied) => {
+                        for (lit, reason) in implied {
+                            match self.searcher.is_satisfied(&lit) {
+                                Some(true) => {},
+                                // 2つの行から同じ変数に異なる値が導かれた, 後の行の理由の節が偽になる
+                                Some(false) => {
+                                    self.conflict_clause = None;
+                                    self.xor_conflict = reason;
+                                    return true;
+                                },
+                                None => {
+                                    self.searcher.assign_bool(lit);
+                                    self.set_reason(lit.var(), Reason::Xor(reason));
+                                },
+                            }
+                        }
+                    },
+                    // 未割り当ての変数がなくなった XOR 制約が偽になった
+                    Err(clause) => {
+                        self.conflict_clause = None;
+                        self.xor_conflict = clause;
+                        return true;
+                    },
+                }
             }
+
+            self.searcher.qhead += 1;
         }
 
         // 1度も矛盾が起きなければ backtrack しなくていい
-        return false;
+        false
+    }
+
+    /// 根で節 clauses[i] を vivification する
+    /// 節のリテラルの否定を順に仮定して伝播し, 矛盾したり真になるリテラルが見つかれば
+    /// そこまでのリテラルだけに節を縮める, 偽になったリテラルは節から除く
+    /// # Returns
+    /// * `Some(clause)` - 縮めた節, 元の節と変わらない場合もある
+    /// * `None` - 根で真になっている節, 削除してよい
+    fn vivify_clause(&mut self, i: usize) -> Option<Clause> {
+        let clause = self.clauses[i].clone();
+        if clause.iter().any(|lit| self.searcher.is_satisfied(lit) == Some(true)) {
+            return None;
+        }
+
+        // 自身による伝播を避けるため, 監視を外しておく
+        self.watched_lit_indices.get_mut(&clause[0].not()).unwrap().remove(&i);
+        self.watched_lit_indices.get_mut(&clause[1].not()).unwrap().remove(&i);
+
+        let mut vivified: Clause = Vec::new();
+        for &lit in &clause {
+            match self.searcher.is_satisfied(&lit) {
+                Some(true) => {
+                    // 仮定したリテラルの否定から lit が導かれる
+                    vivified.push(lit);
+                    break;
+                },
+                Some(false) => {
+                    // 根で偽, または仮定したリテラルの否定から ¬lit が導かれる
+                    continue;
+                },
+                None => {
+                    vivified.push(lit);
+                    self.searcher.assume(lit.not());
+                    if self.propagate() {
+                        // 仮定したリテラルの否定だけで矛盾する
+                        break;
+                    }
+                },
+            }
+        }
+        self.searcher.canceluntil(0);
+
+        self.watched_lit_indices.get_mut(&clause[0].not()).unwrap().insert(i);
+        self.watched_lit_indices.get_mut(&clause[1].not()).unwrap().insert(i);
+        Some(vivified)
+    }
+
+    /// リスタートの合間に, 伝播回数の予算内で節を vivification する
+    /// まだ済ませていない学習節を先に, 残りの予算で元の節を前回の続きから行う
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn vivify(&mut self, budget: usize) -> bool {
+        if self.propagate() {
+            return false;
+        }
+        if self.vivify_next >= self.clauses.len() {
+            self.vivify_next = 0;
+        }
+
+        let limit = self.stats.propagations + budget;
+        let mut learnts = (0..self.clauses.len())
+            .filter(|&i| matches!(self.learnts[i], Some(Learnt { vivified: false, .. })))
+            .collect::<Vec<usize>>()
+            .into_iter();
+        let mut vivified: Vec<(usize, Option<Clause>)> = Vec::new();
+        while self.stats.propagations < limit {
+            let i = if let Some(i) = learnts.next() {
+                if let Some(learnt) = &mut self.learnts[i] {
+                    learnt.vivified = true;
+                }
+                i
+            } else if self.vivify_next < self.clauses.len() {
+                let i = self.vivify_next;
+                self.vivify_next += 1;
+                if self.learnts[i].is_some() {
+                    continue;
+                }
+                i
+            } else {
+                break;
+            };
+            let clause = self.vivify_clause(i);
+            match &clause {
+                Some(c) if c.len() == self.clauses[i].len() => continue,
+                _ => vivified.push((i, clause)),
+            }
+        }
+        if vivified.is_empty() {
+            return true;
+        }
+
+        let mut units: Vec<Literal> = Vec::new();
+        let mut removed = vec![false; self.clauses.len()];
+        for (i, clause) in vivified {
+            match clause {
+                None => {
+                    removed[i] = true;
+                },
+                Some(c) => {
+                    self.stats.vivified_literals += self.clauses[i].len() - c.len();
+                    if c.len() <= 1 {
+                        removed[i] = true;
+                        match c.first() {
+                            Some(&lit) => units.push(lit),
+                            None => return false,
+                        }
+                    } else {
+                        if let Some(learnt) = &mut self.learnts[i] {
+                            learnt.lbd = learnt.lbd.min(c.len());
+                        }
+                        self.clauses[i] = c;
+                    }
+                },
+            }
+        }
+        self.remove_clauses(&removed);
+
+        for lit in units {
+            if !self.searcher.assign_bool(lit) {
+                return false;
+            }
+        }
+        !self.propagate()
+    }
+
+    /// 学習節が reduce_first * reduce_inc^(削除した回数) より多くなったら,
+    /// LBD が 2 より大きい学習節を LBD の大きい順に, 学習節の半分の数だけ削除する
+    /// 根でだけ呼ぶ
+    fn reduce_learnts(&mut self) {
+        let count = self.learnts.iter().flatten().count();
+        if (count as f64) < self.reduce_first as f64 * self.reduce_inc.powi(self.reductions as i32) {
+            return;
+        }
+        self.reductions += 1;
+
+        // LBD が同じなら長い節から削除する
+        let mut candidates: Vec<(usize, usize, usize)> = self.learnts.iter()
+            .enumerate()
+            .filter_map(|(i, learnt)| learnt.filter(|learnt| learnt.lbd > 2).map(|learnt| (learnt.lbd, self.clauses[i].len(), i)))
+            .collect();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+        let mut removed = vec![false; self.clauses.len()];
+        for &(_, _, i) in candidates.iter().take(count / 2) {
+            removed[i] = true;
+        }
+        let deleted = candidates.len().min(count / 2);
+        self.stats.deleted_clauses += deleted;
+        debug!("reduce: {} of {} learnt clauses", deleted, count);
+        self.remove_clauses(&removed);
+    }
+
+    /// removed が true の節を clauses から削除し, 残った節のインデックスを詰める
+    /// 根での割り当ての理由になっている節は Derived に書き換え, 削除した後も理由をたどれるようにする
+    /// 根でだけ呼ぶ
+    fn remove_clauses(&mut self, removed: &[bool]) {
+        let mut indices: Vec<Option<usize>> = Vec::with_capacity(removed.len());
+        let mut next = 0;
+        for &r in removed {
+            if r {
+                indices.push(None);
+            } else {
+                indices.push(Some(next));
+                next += 1;
+            }
+        }
+
+        let tracking = self.tracking();
+        for var in 0..self.reasons.len() {
+            if let Reason::Clause(i) = self.reasons[var] {
+                self.reasons[var] = match indices.get(i).copied().flatten() {
+                    Some(j) => Reason::Clause(j),
+                    None if tracking && self.searcher.assigns[var].is_some() => {
+                        let origin = &self.origins[i];
+                        Reason::Derived(Origin {
+                            id: origin.id,
+                            lits: self.clauses[i].iter().chain(origin.lits.iter()).copied().collect(),
+                            used: origin.used.clone(),
+                        })
+                    },
+                    None => Reason::Derived(Origin::default()),
+                };
+            }
+        }
+
+        let next = self.vivify_next.min(removed.len());
+        self.vivify_next -= removed[..next].iter().filter(|&&r| r).count();
+        let mut keep = removed.iter().map(|&r| !r);
+        self.clauses.retain(|_| keep.next().unwrap());
+        let mut keep = removed.iter().map(|&r| !r);
+        self.learnts.retain(|_| keep.next().unwrap());
+        if tracking {
+            let mut keep = removed.iter().map(|&r| !r);
+            self.origins.retain(|_| keep.next().unwrap());
+        }
+        self.attach_all();
+    }
+
+    /// 保存している真偽値を初期割り当てにして局所探索を行い,
+    /// 偽になる節が最も少なかった割り当てを新しい真偽値にする
+    /// # Returns
+    /// * `true` - 局所探索で解が見つかった, model に解が入る
+    /// * `false` - 見つからなかった
+    fn rephase(&mut self) -> bool {
+        let seed = self.seed.wrapping_add(self.stats.rephases as u64);
+        self.stats.rephases += 1;
+
+        // XOR 制約は節にすると大きくなりうるので, 局所探索では節だけを見て後で確かめる
+        let mut clauses = self.root_clauses();
+        clauses.extend(self.assumptions.iter().map(|&lit| vec![lit]));
+        let mut searcher = LocalSearch::new(self.searcher.size_vars, &clauses, seed);
+        let found = searcher.search(&self.searcher.polarity, self.rephase_flips);
+        self.searcher.polarity.copy_from_slice(&searcher.best_assigns);
+        debug!("rephase: {} unsatisfied clauses after {} flips", searcher.best_unsat, searcher.flips);
+
+        let found = found && self.xors.iter().all(|(vars, parity)| {
+            vars.iter().fold(false, |acc, &var| acc ^ searcher.best_assigns[var]) == *parity
+        });
+        if found {
+            self.model = searcher.best_assigns.iter().map(|&assign| Some(assign)).collect();
+            self.elim_stack.extend_model(&mut self.model);
+        }
+        found
+    }
+
+    /// 直前の矛盾で全てのリテラルが偽になった節
+    fn conflict_lits(&self) -> Clause {
+        match self.conflict_clause {
+            Some(i) => self.clauses[i].clone(),
+            None => self.xor_conflict.clone(),
+        }
+    }
+
+    /// 単位伝播で var を割り当てた節, 先頭以外のリテラルは偽
+    /// # Returns
+    /// * `None` - 決定変数か, 根での割り当て
+    fn reason_lits(&self, var: usize) -> Option<&[Literal]> {
+        match &self.reasons[var] {
+            Reason::Clause(i) => Some(&self.clauses[*i]),
+            Reason::Xor(clause) => Some(clause),
+            Reason::Decision | Reason::Derived(_) => None,
+        }
+    }
+
+    /// 直前の矛盾を解析して節を学習する (1UIP)
+    /// 矛盾した節と理由の節の導出 (resolution) を, 現在の決定レベルのリテラルが1つになるまで trail を遡って繰り返す
+    /// 理由の節のリテラルが全て学習節に含まれるリテラルは除く (局所的な最小化)
+    /// 呼ぶ前に, 矛盾した節のリテラルで最も高い決定レベルまで戻しておく
+    /// # Returns
+    /// * (学習節, 戻る決定レベル, LBD, 学習節の導き方)
+    ///   学習節の [0] は戻った後に単位伝播で真になるリテラル, [1] は残りで決定レベルが最も高いリテラル
+    fn analyze(&mut self) -> (Clause, usize, usize, Origin) {
+        let dlevel = self.searcher.dlevel();
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut learnt: Clause = Vec::new();
+        let mut antecedents: Vec<usize> = Vec::new();   // 理由の節を導出に使った変数
+        let mut clause = self.conflict_lits();
+        let mut counter = 0;                            // 見つけたが導出していない, 現在の決定レベルのリテラルの数
+        let mut index = self.searcher.trail_tail;
+        let uip = loop {
+            for &lit in &clause {
+                let var = lit.var();
+                if !seen.insert(var) || self.searcher.levels[var] == 0 {
+                    continue;
+                }
+                if self.searcher.levels[var] == dlevel {
+                    counter += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+            // trail を遡って, 次に導出する現在の決定レベルのリテラルを探す
+            let lit = loop {
+                index -= 1;
+                let lit = self.searcher.trail[index].unwrap();
+                if seen.contains(&lit.var()) {
+                    break lit;
+                }
+            };
+            counter -= 1;
+            if counter == 0 {
+                break lit;
+            }
+            antecedents.push(lit.var());
+            clause = self.reason_lits(lit.var()).unwrap().iter()
+                .filter(|l| l.var() != lit.var())
+                .copied()
+                .collect();
+        };
+
+        // 理由の節の他のリテラルが全て学習節か根で偽のリテラルなら, そこから導かれるので除ける
+        learnt.retain(|lit| {
+            let redundant = self.reason_lits(lit.var()).is_some_and(|reason| {
+                reason.iter().all(|l| l.var() == lit.var() || seen.contains(&l.var()) || self.searcher.levels[l.var()] == 0)
+            });
+            if redundant {
+                antecedents.push(lit.var());
+            }
+            !redundant
+        });
+        learnt.insert(0, uip.not());
+
+        let mut max = 1;
+        for k in 2..learnt.len() {
+            if self.searcher.levels[learnt[k].var()] > self.searcher.levels[learnt[max].var()] {
+                max = k;
+            }
+        }
+        let level = if learnt.len() > 1 {
+            learnt.swap(1, max);
+            self.searcher.levels[learnt[1].var()]
+        } else {
+            0
+        };
+        let lbd = learnt.iter().map(|lit| self.searcher.levels[lit.var()]).collect::<HashSet<usize>>().len();
+
+        let origin = if self.tracking() {
+            let (used, hints) = self.explain_learnt(&antecedents);
+            self.derive(learnt.clone(), used, &hints)
+        } else {
+            Origin::default()
+        };
+        (learnt, level, lbd, origin)
+    }
+
+    /// 仮定 lit が偽になったときに, 理由をたどって lit を偽にするのに使った仮定を集める (final conflict)
+    /// # Returns
+    /// * 使った仮定を決定レベルの順に並べ, 最後に lit を加えたもの
+    fn analyze_final(&self, lit: Literal) -> Vec<Literal> {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut stack = vec![lit.var()];
+        let mut decisions: Vec<usize> = Vec::new();
+        while let Some(var) = stack.pop() {
+            if self.searcher.levels[var] == 0 || !seen.insert(var) {
+                continue;
+            }
+            match self.reason_lits(var) {
+                Some(reason) => stack.extend(reason.iter().map(|l| l.var()).filter(|&v| v != var)),
+                None => decisions.push(var),
+            }
+        }
+        decisions.sort_unstable_by_key(|&var| self.searcher.levels[var]);
+        let mut conflict: Vec<Literal> = decisions.into_iter()
+            .map(|var| if self.searcher.assigns[var] == Some(true) { Literal::Pos(var) } else { Literal::Neg(var) })
+            .collect();
+        conflict.push(lit);
+        conflict
     }
 
     /// # Returns
     /// * `Some`
     ///   - `true` - SAT
     ///   - `false` - UNSAT
-    /// * `None` - 判定不能
-    fn search(&mut self) -> Option<bool> {
+    /// * `None` - 判定不能, conflict 数が nof_conflicts に達したのでリスタートする
+    fn search(&mut self, nof_conflicts: usize) -> Option<bool> {
+        let mut conflicts = 0;
+        self.assumption_index = 0;
+        self.assumption_levels = 0;
         loop {
             if self.propagate() {
                 self.stats.conflicts += 1;
-                if !self.searcher.backtrack() {
-                    // バックトラックできなくなった
-                    // UNSAT
+                conflicts += 1;
+                let level = self.conflict_lits().iter().map(|lit| self.searcher.levels[lit.var()]).max().unwrap_or(0);
+                if level == 0 {
+                    // 根で矛盾した, 仮定によらず UNSAT
+                    if self.tracking() {
+                        let (used, hints) = self.explain_conflict();
+                        self.derive(Vec::new(), Vec::new(), &hints);
+                        self.core = Some(used);
+                    }
+                    self.ok = false;
+                    self.conflict.clear();
+                    self.searcher.canceluntil(self.root_level);
                     return Some(false);
                 }
+                // 矛盾した節のリテラルが全てより低い決定レベルなら, そこまで戻して解析する
+                self.searcher.canceluntil(level);
+                let (learnt, level, lbd, origin) = self.analyze();
+                self.searcher.canceluntil(level);
+                if level < self.assumption_levels {
+                    // 戻した仮定を割り当て直す
+                    self.assumption_levels = level;
+                    self.assumption_index = 0;
+                }
+
+                self.stats.learnt_clauses += 1;
+                let asserting = learnt[0];
+                if learnt.len() == 1 {
+                    // 根での割り当てになる, 共有はリスタートで根での割り当てと一緒に行う
+                    self.searcher.assign_bool(asserting);
+                    self.set_reason(asserting.var(), Reason::Derived(origin));
+                } else {
+                    if self.exchange.is_some() && lbd <= self.share_lbd_limit {
+                        self.exports.push(learnt.clone());
+                    }
+                    let i = self.clauses.len();
+                    self.attach_clause(learnt, Some(Learnt { lbd, vivified: false }));
+                    if self.tracking() {
+                        self.origins.push(origin);
+                    }
+                    self.searcher.assign_bool(asserting);
+                    self.set_reason(asserting.var(), Reason::Clause(i));
+                }
+            } else if conflicts >= nof_conflicts || self.is_interrupted() {
+                self.searcher.canceluntil(self.root_level);
+                return None;
+            } else if self.assumption_index < self.assumptions.len() {
+                // 仮定を決定変数として順に割り当てる
+                let lit = self.assumptions[self.assumption_index];
+                self.assumption_index += 1;
+                match self.searcher.is_satisfied(&lit) {
+                    Some(true) => {},
+                    Some(false) => {
+                        // 仮定が既に偽になっている, それまでの仮定から偽が導かれた
+                        self.conflict = self.analyze_final(lit);
+                        if self.tracking() {
+                            let (used, hints) = self.explain(&[lit]);
+                            let lemma: Clause = self.conflict.iter().map(|lit| lit.not()).collect();
+                            self.derive(lemma, Vec::new(), &hints);
+                            self.core = Some(used);
+                        }
+                        self.searcher.canceluntil(self.root_level);
+                        return Some(false);
+                    },
+                    None => {
+                        self.searcher.assume(lit);
+                        self.assumption_levels += 1;
+                        self.set_reason(lit.var(), Reason::Decision);
+                    },
+                }
             } else {
-                let next = self.searcher.select_var();
+                let next = if self.searcher.size_vars > 0 && self.random_var_freq > 0.0 && self.rng.gen_bool(self.random_var_freq) {
+                    let var = self.rng.gen_range(self.searcher.size_vars);
+                    match self.searcher.assigns[var] {
+                        Some(_) => self.searcher.select_var(),
+                        None => Some(var),
+                    }
+                } else {
+                    self.searcher.select_var()
+                };
                 self.stats.decisions += 1;
 
                 if let Some(next) = next {
-                    self.searcher.assume(Literal::Neg(next));
+                    if self.searcher.polarity[next] {
+                        self.searcher.assume(Literal::Pos(next));
+                    } else {
+                        self.searcher.assume(Literal::Neg(next));
+                    }
+                    self.set_reason(next, Reason::Decision);
                 } else {
                     // UNSAT にならずに全ての変数を見終わった
-                    for assign in &self.searcher.assigns {
-                        self.model.push(*assign);
-                    }
+                    self.model = self.searcher.assigns.clone();
+                    self.elim_stack.extend_model(&mut self.model);
                     self.searcher.canceluntil(self.root_level);
                     return Some(true);
                 }
@@ -308,64 +1106,557 @@ impl Solver {
         }
     }
 
+    /// 仮定の下で UNSAT になったときに, 同時に真にすると UNSAT になる仮定の部分集合 (final conflict)
+    /// 偽になった仮定から理由をたどって集めたもので, 最小とは限らない, 空なら仮定によらず UNSAT
+    pub fn failed_assumptions(&self) -> &[Literal] {
+        &self.conflict
+    }
+
+    /// 節や仮定に現れたリテラルの監視リストを用意する
+    pub(crate) fn register_lits(&mut self, lits: &[Literal]) {
+        for &lit in lits {
+            self.watched_lit_indices.entry(lit).or_default();
+            self.watched_lit_indices.entry(lit.not()).or_default();
+        }
+    }
+
+    /// 2リテラル以上の節を clauses に加え, [0], [1] を監視リテラルにする
+    fn attach_clause(&mut self, c: Clause, learnt: Option<Learnt>) {
+        let i = self.clauses.len();
+        self.watched_lit_indices.get_mut(&c[0].not()).unwrap().insert(i);
+        self.watched_lit_indices.get_mut(&c[1].not()).unwrap().insert(i);
+        self.clauses.push(c);
+        self.learnts.push(learnt);
+    }
+
+    /// 節を加える, 解いた後に加えてもよい
+    /// 前処理で除去した変数を含む場合は, その変数の除去した節を論理式に戻してから加える
+    /// 加えた順に 0 から節に番号を付け, unsat_core はその番号を返す
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
     pub fn add_clause(&mut self, unnormalized_clause: &mut Clause) -> bool {
+        let id = self.added_clauses;
+        self.added_clauses += 1;
+        if !self.tracking() {
+            self.untracked = true;
+        }
         let clause= normalize_clause(unnormalized_clause);
         match clause {
             Ok(c) => {
                 self.searcher.update_size_vars(&c);
+                self.register_lits(&c);
+                if !self.restore_eliminated(&c) {
+                    return false;
+                }
 
-                let literal_num = c.len();
+                // 根で真の節は加えない, 根で偽のリテラルは除く
+                // 解いた後に加えた節が, 伝播済みの偽のリテラルを監視しないようにする
+                if c.iter().any(|lit| self.searcher.is_satisfied(lit) == Some(true)) {
+                    return true;
+                }
+                let (c, removed): (Clause, Clause) = c.into_iter()
+                    .partition(|lit| self.searcher.is_satisfied(lit).is_none());
+                // 除いたリテラルが偽になった理由も, 縮めた節を導くのに使う
+                let origin = if self.tracking() {
+                    let (mut used, hints) = self.explain(&removed);
+                    used.push(id);
+                    used.sort_unstable();
+                    if c.is_empty() && self.ok {
+                        self.derive(Vec::new(), Vec::new(), &[hints, vec![id as u64 + 1]].concat());
+                        self.core = Some(used.clone());
+                    }
+                    Origin {
+                        id: id as u64 + 1,
+                        lits: removed,
+                        used,
+                    }
+                } else {
+                    Origin::default()
+                };
+                if c.is_empty() {
+                    self.ok = false;
+                    return false;
+                }
 
+                let literal_num = c.len();
                 if literal_num == 1 {
                     // 単位節
-                    self.watched_lit_indices.entry(c[0]).or_insert(HashSet::new());
-                    self.watched_lit_indices.entry(c[0].not()).or_insert(HashSet::new());
+                    if self.tracking() {
+                        self.set_reason(c[0].var(), Reason::Derived(origin));
+                    }
                     return self.searcher.assign_bool(c[0]);
                 } else {
-                    for &lit in c.iter() {
-                        self.watched_lit_indices.entry(lit).or_insert(HashSet::new());
-                        self.watched_lit_indices.entry(lit.not()).or_insert(HashSet::new());
+                    self.attach_clause(c, None);
+                    if self.tracking() {
+                        self.origins.push(origin);
                     }
-                    self.watched_lit_indices.get_mut(&c[0].not()).unwrap().insert(self.stats.clauses);
-                    self.watched_lit_indices.get_mut(&c[1].not()).unwrap().insert(self.stats.clauses);
-
-                    self.clauses.push(c);
                 }
                 self.stats.clauses += 1;
                 self.stats.clauses_literals += literal_num;
-                return true;
+                true
             },
             Err(e) => match e {
                 NormalizeError::TautologyClause => {
                     debug!("Appear TautologyClause: {:?}", unnormalized_clause);
-                    return true
+                    true
                 },
                 NormalizeError::EmptyClause => {
                     debug!("Appear EmptyClause: {:?}", unnormalized_clause);
-                    return false;
+                    if self.ok && self.tracking() {
+                        self.derive(Vec::new(), Vec::new(), &[id as u64 + 1]);
+                        self.core = Some(vec![id]);
+                    }
+                    self.ok = false;
+                    false
+                },
+            },
+        }
+    }
+
+    /// XOR 制約 vars[0] ⊕ vars[1] ⊕ ... = parity を加える, add_clause と同じく解いた後に加えてもよい
+    /// 節で符号化せずに Gauss-Jordan 消去で伝播する, 変数は前処理で除去しないように freeze する
+    /// XOR 制約で導いた割り当てには理由の節がないので, UNSAT core や証明を求めるときは加えられない
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    /// # Panics
+    /// * track_core か set_proof を設定している場合
+    pub fn add_xor(&mut self, vars: &[usize], parity: bool) -> bool {
+        assert!(!self.tracking(), "XOR constraints cannot be used with track_core or set_proof");
+        self.untracked = true;
+        // 同じ変数は打ち消し合う
+        let mut sorted = vars.to_vec();
+        sorted.sort_unstable();
+        let mut normalized: Vec<usize> = Vec::new();
+        for var in sorted {
+            if normalized.last() == Some(&var) {
+                normalized.pop();
+            } else {
+                normalized.push(var);
+            }
+        }
+        if let Some(&max_var) = normalized.last() {
+            self.searcher.update_size_vars(&vec![Literal::Pos(max_var)]);
+        }
+        for &var in &normalized {
+            self.register_lits(&[Literal::Pos(var)]);
+            self.freeze(var);
+        }
+        let lits: Vec<Literal> = normalized.iter().map(|&var| Literal::Pos(var)).collect();
+        if !self.restore_eliminated(&lits) {
+            return false;
+        }
+        self.xors.push((normalized.clone(), parity));
+        self.stats.xors += 1;
+
+        // 根で割り当てた変数は右辺に移す
+        let mut parity = parity;
+        let unassigned: Vec<usize> = normalized.into_iter()
+            .filter(|&var| match self.searcher.assigns[var] {
+                Some(value) => {
+                    parity ^= value;
+                    false
                 },
+                None => true,
+            })
+            .collect();
+        match self.xor.add(&unassigned, parity) {
+            XorAdd::Redundant => true,
+            XorAdd::Conflict => {
+                self.ok = false;
+                false
+            },
+            XorAdd::Unit(lit) => {
+                if !self.searcher.assign_bool(lit) {
+                    self.ok = false;
+                }
+                self.ok
             },
+            XorAdd::Added => match self.xor.check_all(&self.searcher.assigns) {
+                // 消去で他の行が変わり, 新たに伝播できるようになった場合
+                Ok(implied) => {
+                    if !implied.into_iter().all(|(lit, _)| self.searcher.assign_bool(lit)) {
+                        self.ok = false;
+                    }
+                    self.ok
+                },
+                Err(_) => {
+                    self.ok = false;
+                    false
+                },
+            },
+        }
+    }
+
+    /// 節で符号化された XOR 制約を見つけ, それらの節の代わりに XOR 制約として持つ
+    /// # Returns
+    /// * `true` - 成功
+    /// * `false` - 根で矛盾した, UNSAT
+    fn detect_xors(&mut self) -> bool {
+        let (xors, used) = find_xors(&self.clauses, self.xor_detection_size);
+        if xors.is_empty() {
+            return true;
+        }
+        let clauses = std::mem::take(&mut self.clauses);
+        let removed = used.iter().filter(|&&used| used).count();
+        self.clauses = clauses.into_iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(clause, _)| clause)
+            .collect();
+        self.learnts = vec![None; self.clauses.len()];
+        self.attach_all();
+        info!("xor: {} xors from {} clauses", xors.len(), removed);
+        for (vars, parity) in xors {
+            if !self.add_xor(&vars, parity) {
+                return false;
+            }
         }
+        !self.propagate()
     }
 
     pub fn solve(&mut self) -> Option<bool> {
+        self.solve_with_assumptions(&[])
+    }
+
+    /// assumptions を全て真と仮定して解く
+    /// 解けなかった場合も, 仮定が原因なら同じ Solver でまた解くことができる
+    /// # Returns
+    /// * `Some`
+    ///   - `true` - SAT
+    ///   - `false` - 仮定の下で UNSAT
+    /// * `None` - interrupt により打ち切った
+    pub fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> Option<bool> {
+        for &lit in assumptions {
+            self.searcher.update_size_vars(&vec![lit]);
+            self.register_lits(&[lit]);
+            if !self.preprocessed {
+                self.freeze(lit.var());
+            }
+        }
+        if !self.restore_eliminated(assumptions) {
+            return Some(false);
+        }
+        self.assumptions = assumptions.to_vec();
+        self.conflict.clear();
+        if self.ok {
+            self.core = None;
+        }
+        if !self.simplify() {
+            return Some(false);
+        }
+
         info!("==========[MINIMUMSAT]==========");
         info!("| Conflicts |     ORIGINAL     |");
         info!("|           | Clauses Literals |");
         info!("================================");
 
         let mut search_status = None;
-        while search_status == None {
+        let mut nof_conflicts = self.restart_first as f64;
+        while search_status.is_none() {
+            if self.is_interrupted() {
+                break;
+            }
             info!("| {:9} | {:7} {:8} |",
                 self.stats.conflicts,
                 self.stats.clauses,
                 self.stats.clauses_literals,
             );
 
-            search_status = self.search();
+            let propagations = self.stats.propagations;
+            search_status = self.search(nof_conflicts as usize);
+            nof_conflicts *= self.restart_inc;
+
+            if search_status.is_none() {
+                self.stats.restarts += 1;
+                self.reduce_learnts();
+                // vivification は節を書き換えるので, UNSAT core や証明を求めるときは行わない
+                if self.use_vivify && !self.tracking() {
+                    let effort = (self.stats.propagations - propagations) as f64 * self.vivify_effort;
+                    if !self.vivify(effort as usize) {
+                        self.ok = false;
+                        search_status = Some(false);
+                        continue;
+                    }
+                }
+                if !self.exchange_clauses() {
+                    self.ok = false;
+                    search_status = Some(false);
+                    continue;
+                }
+                if self.use_rephase && self.stats.restarts.is_multiple_of(self.rephase_interval) && self.rephase() {
+                    search_status = Some(true);
+                }
+            }
         }
         info!("================================");
 
-        return search_status;
+        search_status
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{brute_force, is_model, is_xor_model, pigeonhole, random_clauses, random_lit};
+
+    /// 前処理をしない Solver に節を加える
+    fn solver_with(clauses: &Clauses) -> Solver {
+        let mut solver = Solver::new();
+        solver.use_preprocess = false;
+        for clause in clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        solver
+    }
+
+    fn solver_from(clauses: &[&[i32]]) -> Solver {
+        solver_with(&clauses.iter().map(|clause| clause.iter().map(|&lit| Literal::from_dimacs(lit)).collect()).collect())
+    }
+
+    #[test]
+    fn vivify_clause_shortens_and_deletes() {
+        // x1 -> x2 なので (¬x1 ∨ x2 ∨ x3) は (¬x1 ∨ x2) に縮み, (x4 ∨ x5) は根で x4 が真なので削除できる
+        let mut solver = solver_from(&[&[-1, 2, 3], &[-1, 2], &[4, 5], &[4]]);
+        assert_eq!(solver.clauses.len(), 3);
+
+        let mut vivified = solver.vivify_clause(0).unwrap();
+        vivified.sort_unstable();
+        assert_eq!(vivified, vec![Literal::Neg(0), Literal::Pos(1)]);
+        assert_eq!(solver.vivify_clause(2), None);
+        // 仮定は全て取り消されている
+        assert_eq!(solver.searcher.dlevel(), 0);
+        assert_eq!(solver.searcher.assigns[2], None);
+    }
+
+    #[test]
+    fn vivify_updates_clauses_and_stats() {
+        let mut solver = solver_from(&[&[-1, 2, 3], &[-1, 2], &[4, 5], &[4]]);
+        assert!(solver.vivify(1_000_000));
+        assert_eq!(solver.stats.vivified_literals, 1);
+        assert_eq!(solver.clauses.len(), 2);
+        assert!(solver.clauses.iter().all(|clause| clause.len() == 2));
+
+        assert_eq!(solver.solve(), Some(true));
+        assert_eq!(solver.model[3], Some(true));
+    }
+
+    #[test]
+    fn vivify_shortens_learnt_clauses_first() {
+        // 学習節 (¬x1 ∨ x2 ∨ x3) は x1 -> x2 から (¬x1 ∨ x2) に縮む, 予算が伝播1回でも学習節から行う
+        let mut solver = solver_from(&[&[-1, 2], &[4, 5, 6]]);
+        solver.attach_clause(vec![Literal::Neg(0), Literal::Pos(1), Literal::Pos(2)], Some(Learnt { lbd: 3, vivified: false }));
+        assert!(solver.vivify(1));
+        assert_eq!(solver.clauses[2], vec![Literal::Neg(0), Literal::Pos(1)]);
+        assert!(matches!(solver.learnts[2], Some(Learnt { lbd: 2, vivified: true })));
+        assert_eq!(solver.clauses[1].len(), 3);
+    }
+
+    #[test]
+    fn reduce_deletes_learnt_clauses_with_large_lbd() {
+        let mut solver = solver_from(&[&[1, 2, 3], &[-1, -2, -3]]);
+        solver.reduce_first = 4;
+        for (k, lbd) in [3, 2, 5, 4, 1].into_iter().enumerate() {
+            // 変数の割り当てを k のビットにすると偽になる節
+            let clause: Clause = (0..3).map(|v| if (k >> v) & 1 == 1 { Literal::Neg(v) } else { Literal::Pos(v) }).collect();
+            solver.attach_clause(clause, Some(Learnt { lbd, vivified: false }));
+        }
+        // 学習節 5 個の半分 2 個を, LBD が 5 と 4 のものから削除する
+        solver.reduce_learnts();
+        assert_eq!(solver.stats.deleted_clauses, 2);
+        let lbds: Vec<usize> = solver.learnts.iter().flatten().map(|learnt| learnt.lbd).collect();
+        assert_eq!(lbds, vec![3, 2, 1]);
+        assert_eq!(solver.clauses.len(), 5);
+        assert_eq!(solver.clauses[3], vec![Literal::Neg(0), Literal::Pos(1), Literal::Pos(2)]);
+        // 上限が reduce_inc 倍になったので, 学習節 3 個では削除しない
+        solver.reduce_learnts();
+        assert_eq!(solver.learnts.iter().flatten().count(), 3);
+        assert_eq!(solver.solve(), Some(true));
+    }
+
+    #[test]
+    fn restarts_keep_learnt_clauses() {
+        // 毎 conflict でリスタートしても, 学習節が残るので探索が進む
+        let mut solver = solver_with(&pigeonhole(4, 3));
+        solver.restart_first = 1;
+        solver.restart_inc = 1.0;
+        solver.use_rephase = false;
+        assert_eq!(solver.solve(), Some(false));
+        assert!(solver.stats.restarts > 0);
+        assert!(solver.stats.restarts < solver.stats.conflicts);
+        assert_eq!(solver.stats.learnt_clauses, solver.stats.conflicts - 1);
+    }
+
+    #[test]
+    fn restarts_keep_the_answer() {
+        for use_vivify in [false, true] {
+            let clauses = pigeonhole(4, 4);
+            let mut solver = solver_with(&clauses);
+            solver.restart_first = 1;
+            solver.use_vivify = use_vivify;
+            solver.use_rephase = false;
+            assert_eq!(solver.solve(), Some(true));
+            assert!(is_model(&clauses, &solver.model));
+        }
+    }
+
+    #[test]
+    fn failed_assumptions_follow_reasons() {
+        // x1 ∧ x2 -> x4, x4 -> ¬x5 なので, x3 は関係なく x1, x2, x5 が原因
+        let mut solver = solver_from(&[&[-1, -2, 4], &[-4, -5]]);
+        let assumptions: Vec<Literal> = [1, 3, 2, 5].into_iter().map(Literal::from_dimacs).collect();
+        assert_eq!(solver.solve_with_assumptions(&assumptions), Some(false));
+        assert_eq!(solver.failed_assumptions(), &[Literal::Pos(0), Literal::Pos(1), Literal::Pos(4)]);
+
+        // 根で偽の仮定は, それだけで原因になる
+        let mut solver = solver_from(&[&[-1]]);
+        assert_eq!(solver.solve_with_assumptions(&[Literal::Pos(1), Literal::Pos(0)]), Some(false));
+        assert_eq!(solver.failed_assumptions(), &[Literal::Pos(0)]);
+    }
+
+    #[test]
+    fn failed_assumptions_are_unsat() {
+        let mut rng = Random::new(4);
+        for _ in 0..500 {
+            let size_vars = 2 + rng.gen_range(9);
+            let size_clauses = rng.gen_range(size_vars * 3);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            let mut solver = solver_with(&clauses);
+            let assumptions: Vec<Literal> = (0..rng.gen_range(size_vars)).map(|_| random_lit(&mut rng, size_vars)).collect();
+            if solver.solve_with_assumptions(&assumptions) != Some(false) {
+                continue;
+            }
+            let failed = solver.failed_assumptions().to_vec();
+            assert!(failed.iter().all(|lit| assumptions.contains(lit)));
+            let units: Clauses = failed.iter().map(|&lit| vec![lit]).collect();
+            assert!(brute_force(size_vars, &[clauses.clone(), units].concat(), &[]).is_empty(), "{:?} {:?}", clauses, assumptions);
+        }
+    }
+
+    #[test]
+    fn rephase_takes_phases_from_local_search() {
+        let mut solver = solver_with(&pigeonhole(4, 3));
+        solver.seed = 3;
+        solver.rephase_flips = 100;
+        solver.searcher.polarity.fill(true);
+
+        // 同じシードと初期割り当てで局所探索した, 偽の節が最も少ない割り当てになる
+        let mut searcher = LocalSearch::new(solver.size_vars(), &solver.root_clauses(), 3);
+        assert!(!searcher.search(&solver.searcher.polarity, 100));
+        assert!(!solver.rephase());
+        assert_eq!(solver.stats.rephases, 1);
+        assert_eq!(solver.searcher.polarity, searcher.best_assigns);
+        assert_ne!(solver.searcher.polarity, vec![true; 12]);
+    }
+
+    #[test]
+    fn rephase_finds_model() {
+        // (¬x1 ∨ x2) ∧ (¬x2 ∨ x3) ∧ (x1 ∨ x3) は SAT, 局所探索で解が見つかり真偽値もその解になる
+        let clauses: &[&[i32]] = &[&[-1, 2], &[-2, 3], &[1, 3]];
+        let mut solver = solver_from(clauses);
+        assert!(solver.rephase());
+        for clause in clauses {
+            assert!(clause.iter().any(|&lit| solver.model[lit.unsigned_abs() as usize - 1] == Some(lit > 0)));
+        }
+        assert!(clauses.iter().all(|clause| clause.iter().any(|&lit| solver.searcher.polarity[lit.unsigned_abs() as usize - 1] == (lit > 0))));
+    }
+
+    #[test]
+    fn xors_agree_with_brute_force() {
+        let mut rng = Random::new(2);
+        for _ in 0..500 {
+            let size_vars = 2 + rng.gen_range(9);
+            let size_clauses = rng.gen_range(size_vars * 3);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            let xors: Vec<(Vec<usize>, bool)> = (0..1 + rng.gen_range(4))
+                .map(|_| ((0..1 + rng.gen_range(4)).map(|_| rng.gen_range(size_vars)).collect(), rng.gen_bool(0.5)))
+                .collect();
+
+            let mut solver = Solver::new();
+            solver.restart_first = 2;
+            // 仮定を変えて解き直すので, どの変数も除去しない
+            for var in 0..size_vars {
+                solver.freeze(var);
+            }
+            for clause in &clauses {
+                solver.add_clause(&mut clause.clone());
+            }
+            for (vars, parity) in &xors {
+                solver.add_xor(vars, *parity);
+            }
+
+            // 仮定を変えて解き直し, 探索を戻したときに XOR 制約の状態も戻るかを調べる
+            for _ in 0..3 {
+                let assumptions: Vec<Literal> = (0..rng.gen_range(3)).map(|_| random_lit(&mut rng, size_vars)).collect();
+                let units: Clauses = assumptions.iter().map(|&lit| vec![lit]).collect();
+                let expected = !brute_force(size_vars, &[clauses.clone(), units.clone()].concat(), &xors).is_empty();
+                assert_eq!(solver.solve_with_assumptions(&assumptions), Some(expected), "{:?} {:?} {:?}", clauses, xors, assumptions);
+                if expected {
+                    assert!(is_model(&[clauses.clone(), units].concat(), &solver.model));
+                    assert!(is_xor_model(&xors, &solver.model));
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn conflicting_xor_is_unsat() {
+        let mut solver = Solver::new();
+        assert!(solver.add_xor(&[0, 1], false));
+        assert!(solver.add_xor(&[1], true));
+        // x1 = x2 と x2 から x1 が真になるので, x1 ⊕ x2 = 1 は根で矛盾する
+        assert!(!solver.add_xor(&[0, 1], true));
+        assert_eq!(solver.solve(), Some(false));
+    }
+
+    #[test]
+    #[should_panic]
+    fn xor_is_rejected_when_tracking_core() {
+        let mut solver = Solver::new();
+        solver.track_core = true;
+        solver.add_xor(&[0, 1], true);
+    }
+
+    #[test]
+    fn add_clauses_on_eliminated_vars_after_solving() {
+        // freeze せずに解き, 前処理で除去したかもしれない変数を含む節と仮定を後から加える
+        let mut rng = Random::new(9);
+        let mut restored = 0;
+        for _ in 0..500 {
+            let size_vars = 2 + rng.gen_range(9);
+            let size_clauses = rng.gen_range(size_vars * 3);
+            let clauses = random_clauses(&mut rng, size_vars, size_clauses, 3);
+            let split = rng.gen_range(clauses.len() + 1);
+
+            let mut solver = Solver::new();
+            for clause in &clauses[..split] {
+                solver.add_clause(&mut clause.clone());
+            }
+            let expected = !brute_force(size_vars, &clauses[..split], &[]).is_empty();
+            assert_eq!(solver.solve(), Some(expected));
+            let eliminated = solver.elim_stack.len();
+
+            for clause in &clauses[split..] {
+                solver.add_clause(&mut clause.clone());
+            }
+            let assumptions: Vec<Literal> = (0..rng.gen_range(3)).map(|_| random_lit(&mut rng, size_vars)).collect();
+            let units: Clauses = assumptions.iter().map(|&lit| vec![lit]).collect();
+            let all = [clauses.clone(), units].concat();
+            let expected = !brute_force(size_vars, &all, &[]).is_empty();
+            assert_eq!(solver.solve_with_assumptions(&assumptions), Some(expected), "{:?} {:?}", clauses, assumptions);
+            if expected {
+                assert!(is_model(&all, &solver.model), "{:?} {:?}", clauses, assumptions);
+            }
+            restored += eliminated - solver.elim_stack.len();
+        }
+        assert!(restored > 0);
+    }
+}
@@ -0,0 +1,62 @@
+//! テストで使う, 小さなランダムな論理式と総当たりによる解
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::random::Random;
+use crate::solver::Clauses;
+
+/// 変数 size_vars 個, 長さ k の節 size_clauses 個のランダムな論理式
+pub fn random_clauses(rng: &mut Random, size_vars: usize, size_clauses: usize, k: usize) -> Clauses {
+    (0..size_clauses)
+        .map(|_| (0..k).map(|_| random_lit(rng, size_vars)).collect())
+        .collect()
+}
+
+pub fn random_lit(rng: &mut Random, size_vars: usize) -> Literal {
+    let var = rng.gen_range(size_vars);
+    if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) }
+}
+
+/// i 番目のビットを変数 i の値とする割り当てで clause が真になるか
+pub fn satisfies(bits: u64, clause: &Clause) -> bool {
+    clause.iter().any(|lit| ((bits >> lit.var()) & 1 == 1) == lit.is_pos())
+}
+
+/// 節と XOR 制約 (変数, パリティ) を全て充足する割り当てを, 総当たりで全て求める
+pub fn brute_force(size_vars: usize, clauses: &[Clause], xors: &[(Vec<usize>, bool)]) -> Vec<u64> {
+    (0..1u64 << size_vars)
+        .filter(|&bits| clauses.iter().all(|clause| satisfies(bits, clause)))
+        .filter(|&bits| xors.iter().all(|(vars, parity)| {
+            vars.iter().fold(false, |acc, &var| acc ^ ((bits >> var) & 1 == 1)) == *parity
+        }))
+        .collect()
+}
+
+/// model が全ての節を充足するか, 割り当てのない変数は偽とする
+pub fn is_model(clauses: &[Clause], model: &[Option<bool>]) -> bool {
+    clauses.iter().all(|clause| {
+        clause.iter().any(|lit| model.get(lit.var()).copied().flatten().unwrap_or(false) == lit.is_pos())
+    })
+}
+
+/// pigeons 羽の鳩を holes 個の巣に 1 羽ずつ入れる鳩の巣原理, pigeons > holes なら UNSAT
+pub fn pigeonhole(pigeons: usize, holes: usize) -> Clauses {
+    let var = |pigeon: usize, hole: usize| pigeon * holes + hole;
+    let mut clauses: Clauses = (0..pigeons)
+        .map(|p| (0..holes).map(|h| Literal::Pos(var(p, h))).collect())
+        .collect();
+    for h in 0..holes {
+        for p in 0..pigeons {
+            for q in p + 1..pigeons {
+                clauses.push(vec![Literal::Neg(var(p, h)), Literal::Neg(var(q, h))]);
+            }
+        }
+    }
+    clauses
+}
+
+/// model が全ての XOR 制約を充足するか, 割り当てのない変数は偽とする
+pub fn is_xor_model(xors: &[(Vec<usize>, bool)], model: &[Option<bool>]) -> bool {
+    xors.iter().all(|(vars, parity)| {
+        vars.iter().fold(false, |acc, &var| acc ^ model.get(var).copied().flatten().unwrap_or(false)) == *parity
+    })
+}
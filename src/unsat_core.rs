@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Solver;
+
+/// 節や割り当てをどう導いたか
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Origin {
+    pub id: u64,            // 証明での節の番号, 元の節は add_clause で加えた順に 1 から
+    pub lits: Clause,       // 理由をたどるときに, 節のリテラルに加えて見るリテラル (根で偽になって除いたリテラルなど)
+    pub used: Vec<usize>,   // 導くのに使った元の節の番号, 0 から
+}
+
+/// 変数の割り当ての理由
+#[derive(Debug, Clone)]
+pub(crate) enum Reason {
+    /// 決定変数 (仮定を含む)
+    Decision,
+    /// clauses[i] による単位伝播, 導き方は origins[i]
+    Clause(usize),
+    /// XOR 制約による単位伝播, 真になったリテラルを先頭に行の他の偽のリテラルを並べた節
+    Xor(Clause),
+    /// clauses にない節による根での割り当て
+    /// 単位節, 学習した単位節, 削除した節による割り当てがこれになる
+    Derived(Origin),
+}
+
+impl Solver {
+    /// UNSAT core か証明のために割り当ての理由を記録するか
+    /// track_core を設定する前に節を加えていた場合は, その節の導き方がないので記録しない
+    pub(crate) fn tracking(&self) -> bool {
+        (self.track_core || self.proof.is_some()) && !self.untracked
+    }
+
+    pub(crate) fn set_reason(&mut self, var: usize, reason: Reason) {
+        if self.reasons.len() <= var {
+            self.reasons.resize(var + 1, Reason::Decision);
+        }
+        self.reasons[var] = reason;
+    }
+
+    /// 現在の割り当てで偽の lits が, 決定変数の下で偽になることを導くのに使った節
+    /// 割り当ての理由を逆にたどり, 途中の節の Origin を集める
+    /// 根で偽になったリテラルや, 仮定の下で偽になった仮定の説明に使う
+    /// # Returns
+    /// * (元の節の番号, 昇順, 証明での節の番号, 単位伝播した順)
+    pub(crate) fn explain(&self, lits: &[Literal]) -> (Vec<usize>, Vec<u64>) {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<usize> = lits.iter().map(|lit| lit.var()).collect();
+        let mut origins: Vec<(usize, &Origin)> = Vec::new();
+        while let Some(var) = stack.pop() {
+            if !seen.insert(var) {
+                continue;
+            }
+            let (clause, origin): (&[Literal], &Origin) = match self.reasons.get(var) {
+                Some(Reason::Clause(i)) => (&self.clauses[*i], &self.origins[*i]),
+                Some(Reason::Derived(origin)) => (&[], origin),
+                // XOR 制約は追跡するときには加えられない
+                Some(Reason::Decision) | Some(Reason::Xor(_)) | None => continue,
+            };
+            stack.extend(clause.iter().chain(origin.lits.iter()).map(|lit| lit.var()).filter(|&v| v != var));
+            origins.push((var, origin));
+        }
+
+        // 証明の検査で順に単位伝播できるように, trail 上の順に並べる
+        let positions: HashMap<usize, usize> = self.searcher.trail[..self.searcher.trail_tail].iter()
+            .enumerate()
+            .map(|(k, lit)| (lit.unwrap().var(), k))
+            .collect();
+        origins.sort_unstable_by_key(|(var, _)| positions[var]);
+        let hints = origins.iter().map(|(_, origin)| origin.id).collect();
+        let mut used: Vec<usize> = origins.iter().flat_map(|(_, origin)| origin.used.iter().copied()).collect();
+        used.sort_unstable();
+        used.dedup();
+        (used, hints)
+    }
+
+    /// 直前の矛盾を導くのに使った節, explain と同じ形
+    pub(crate) fn explain_conflict(&self) -> (Vec<usize>, Vec<u64>) {
+        match self.conflict_clause {
+            Some(i) => {
+                let origin = &self.origins[i];
+                let lits: Clause = self.clauses[i].iter().chain(origin.lits.iter()).copied().collect();
+                let (mut used, mut hints) = self.explain(&lits);
+                used.extend(origin.used.iter().copied());
+                used.sort_unstable();
+                used.dedup();
+                hints.push(origin.id);
+                (used, hints)
+            },
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+
+    /// analyze で学習した節を導くのに使った節, explain と同じ形
+    /// antecedents は理由の節を導出に使った変数で, 根で偽のリテラルの説明, それらの理由の節を trail 上の順, 最後に矛盾した節を並べる
+    /// 学習節の否定を仮定すると, この順に単位伝播して矛盾する
+    pub(crate) fn explain_learnt(&self, antecedents: &[usize]) -> (Vec<usize>, Vec<u64>) {
+        let positions: HashMap<usize, usize> = self.searcher.trail[..self.searcher.trail_tail].iter()
+            .enumerate()
+            .map(|(k, lit)| (lit.unwrap().var(), k))
+            .collect();
+        let mut vars = antecedents.to_vec();
+        vars.sort_unstable_by_key(|var| positions[var]);
+        let mut clauses: Vec<usize> = vars.into_iter()
+            .map(|var| match self.reasons[var] {
+                Reason::Clause(i) => i,
+                _ => unreachable!("antecedent without reason clause"),
+            })
+            .collect();
+        clauses.push(self.conflict_clause.expect("conflict without clause"));
+
+        let roots: Vec<Literal> = clauses.iter()
+            .flat_map(|&i| self.clauses[i].iter().chain(self.origins[i].lits.iter()))
+            .filter(|lit| self.searcher.assigns[lit.var()].is_some() && self.searcher.levels[lit.var()] == 0)
+            .copied()
+            .collect();
+        let (mut used, mut hints) = self.explain(&roots);
+        for &i in &clauses {
+            used.extend(self.origins[i].used.iter().copied());
+            hints.push(self.origins[i].id);
+        }
+        used.sort_unstable();
+        used.dedup();
+        (used, hints)
+    }
+
+    /// hints の節から単位伝播で導ける節 clause を証明に書き出す
+    /// # Returns
+    /// * clause の導き方, 証明を書き出さない場合は番号を 0 とする
+    pub(crate) fn derive(&mut self, clause: Clause, used: Vec<usize>, hints: &[u64]) -> Origin {
+        let id = match &mut self.proof {
+            Some(proof) => proof.add(self.added_clauses as u64, &clause, hints),
+            None => 0,
+        };
+        Origin {
+            id,
+            lits: clause,
+            used,
+        }
+    }
+
+    /// track_core を設定して解き, UNSAT になったときに使った元の節の番号 (UNSAT core), 昇順
+    /// 探索木の各矛盾で使った節を集めたものなので, 極小とは限らない
+    /// 仮定の下で UNSAT になった場合は, failed_assumptions と合わせて UNSAT になる節の集合になる
+    /// # Returns
+    /// * `Some(core)` - 元の節の番号は add_clause で加えた順に 0 から
+    /// * `None` - track_core でない, 直前の探索が UNSAT でない, または track_core を設定する前に節か XOR 制約を加えたため追跡できない
+    pub fn unsat_core(&self) -> Option<Vec<usize>> {
+        if !self.track_core || self.untracked {
+            return None;
+        }
+        self.core.clone()
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_satisfiable(clauses: &[Clause], size_vars: usize) -> bool {
+        (0..1usize << size_vars).any(|bits| {
+            clauses.iter().all(|clause| clause.iter().any(|lit| ((bits >> lit.var()) & 1 == 1) == lit.is_pos()))
+        })
+    }
+
+    #[test]
+    fn core_keeps_original_clause_ids() {
+        // 0: 恒真, 1: x1, 2: x3 ∨ x4, 3: ¬x1 ∨ x2, 4: ¬x2 の core は 1, 3, 4
+        let clauses: Vec<Clause> = [vec![1, -1], vec![1], vec![3, 4], vec![-1, 2], vec![-2]].into_iter()
+            .map(|clause| clause.into_iter().map(Literal::from_dimacs).collect())
+            .collect();
+        let mut solver = Solver::new();
+        solver.track_core = true;
+        for clause in &clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        assert_eq!(solver.solve(), Some(false));
+        assert_eq!(solver.unsat_core(), Some(vec![1, 3, 4]));
+
+        // 探索が必要な場合, x1 ∨ x2 の 4 通りの符号の節と余分な節
+        let clauses: Vec<Clause> = [vec![3, 4], vec![1, 2], vec![1, -2], vec![-1, 2], vec![3, -4], vec![-1, -2]].into_iter()
+            .map(|clause| clause.into_iter().map(Literal::from_dimacs).collect())
+            .collect();
+        let mut solver = Solver::new();
+        solver.track_core = true;
+        for clause in &clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        assert_eq!(solver.solve(), Some(false));
+        let core = solver.unsat_core().unwrap();
+        let subset: Vec<Clause> = core.iter().map(|&i| clauses[i].clone()).collect();
+        assert!(!is_satisfiable(&subset, 4));
+        assert!(core.iter().all(|&i| i != 0 && i != 4));
+    }
+
+    #[test]
+    fn track_core_after_adding_clauses() {
+        // 節を加えた後に track_core を設定しても解けて, 追跡できないので core は None
+        let clauses: Vec<Clause> = [vec![1, 2], vec![1, -2], vec![-1, 2], vec![-1, -2]].into_iter()
+            .map(|clause| clause.into_iter().map(Literal::from_dimacs).collect())
+            .collect();
+        let mut solver = Solver::new();
+        solver.use_preprocess = false;
+        for clause in &clauses {
+            solver.add_clause(&mut clause.clone());
+        }
+        solver.track_core = true;
+        assert_eq!(solver.solve(), Some(false));
+        assert_eq!(solver.unsat_core(), None);
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::clause::Clause;
+use crate::literal::Literal;
+use crate::solver::Clauses;
+
+/// vars の全ての割り当てのうち, 排他的論理和が parity にならないものを禁止する節
+/// 節の数は 2^(vars.len() - 1) になるので, 短い XOR 制約にだけ使う
+pub fn xor_to_clauses(vars: &[usize], parity: bool) -> Clauses {
+    let mut clauses = Vec::new();
+    for bits in 0..(1usize << vars.len()) {
+        if (bits.count_ones() % 2 == 1) == parity {
+            continue;
+        }
+        let clause: Clause = vars.iter().enumerate()
+            .map(|(k, &var)| if (bits >> k) & 1 == 1 { Literal::Neg(var) } else { Literal::Pos(var) })
+            .collect();
+        clauses.push(clause);
+    }
+    clauses
+}
+
+/// 節で符号化された XOR 制約を見つける
+/// 同じ変数の組の節が, 負のリテラルの数の偶奇が同じ 2^(k-1) 通りの符号を全て含んでいれば XOR 制約になる
+/// # Returns
+/// * (見つけた XOR 制約の (変数, 右辺) の一覧, 各節が XOR 制約の一部か)
+pub fn find_xors(clauses: &Clauses, max_len: usize) -> (Vec<(Vec<usize>, bool)>, Vec<bool>) {
+    // 変数の組と負のリテラルの数の偶奇ごとに, 節の符号 (負のリテラルの位置) を集める
+    let mut groups: HashMap<(Vec<usize>, bool), HashMap<usize, usize>> = HashMap::new();
+    for (i, clause) in clauses.iter().enumerate() {
+        if clause.len() < 3 || clause.len() > max_len {
+            continue;
+        }
+        let vars: Vec<usize> = clause.iter().map(|lit| lit.var()).collect();
+        let signs = clause.iter().enumerate()
+            .filter(|(_, lit)| !lit.is_pos())
+            .fold(0, |signs, (k, _)| signs | (1 << k));
+        let odd = clause.iter().filter(|lit| !lit.is_pos()).count() % 2 == 1;
+        groups.entry((vars, odd)).or_default().insert(signs, i);
+    }
+
+    let mut xors = Vec::new();
+    let mut used = vec![false; clauses.len()];
+    for ((vars, odd), patterns) in groups {
+        if patterns.len() < 1 << (vars.len() - 1) {
+            continue;
+        }
+        // 負のリテラルが奇数個の節は, 偶数個の変数が真になる割り当てを禁止する
+        xors.push((vars, !odd));
+        for i in patterns.into_values() {
+            used[i] = true;
+        }
+    }
+    xors.sort_unstable();
+    (xors, used)
+}
+
+/// XOR 制約の1行, bits の変数の排他的論理和が rhs に等しい
+struct XorRow {
+    bits: Vec<u64>,
+    rhs: bool,
+    pivot: usize,       // 基底変数, 他の行には現れない
+}
+
+impl XorRow {
+    fn has(&self, var: usize) -> bool {
+        (self.bits[var / 64] >> (var % 64)) & 1 == 1
+    }
+
+    fn vars(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bits.iter().enumerate().flat_map(|(w, &word)| {
+            (0..64).filter(move |b| (word >> b) & 1 == 1).map(move |b| w * 64 + b)
+        })
+    }
+
+    fn xor_with(&mut self, other: &XorRow) {
+        for (a, b) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *a ^= b;
+        }
+        self.rhs ^= other.rhs;
+    }
+}
+
+/// XOR 制約を加えた結果
+pub(crate) enum XorAdd {
+    /// 他の XOR 制約から導かれる
+    Redundant,
+    /// 他の XOR 制約と矛盾する
+    Conflict,
+    /// 1変数になったので, 単位節として割り当てる
+    Unit(Literal),
+    Added,
+}
+
+/// XOR 制約を Gauss-Jordan 消去した形で持ち, 割り当てに応じて伝播する
+/// 各行の基底変数はなるべく未割り当ての変数にしておき, 基底変数が割り当てられたら
+/// 同じ行の未割り当ての変数に基底を移す (CryptoMiniSat と同じ方針)
+/// すると, 行の基底変数以外が全て割り当てられたときだけ伝播が起き, 未割り当ての変数がなくなった行だけが矛盾しうる
+/// 割り当てを戻しても行列は同値な連立方程式のままなので, バックトラックで元に戻す必要はない
+pub(crate) struct XorEngine {
+    rows: Vec<XorRow>,
+    words: usize,
+}
+
+impl XorEngine {
+    pub fn new() -> Self {
+        XorEngine {
+            rows: Vec::new(),
+            words: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn resize(&mut self, size_vars: usize) {
+        let words = size_vars.div_ceil(64);
+        if self.words < words {
+            self.words = words;
+            for row in self.rows.iter_mut() {
+                row.bits.resize(words, 0);
+            }
+        }
+    }
+
+    /// 根で XOR 制約を加える, vars は未割り当てで重複がないこと
+    pub fn add(&mut self, vars: &[usize], parity: bool) -> XorAdd {
+        if let Some(&max_var) = vars.iter().max() {
+            self.resize(max_var + 1);
+        }
+        let mut row = XorRow {
+            bits: vec![0; self.words],
+            rhs: parity,
+            pivot: 0,
+        };
+        for &var in vars {
+            row.bits[var / 64] ^= 1 << (var % 64);
+        }
+        // 既存の行の基底変数を消去する
+        for other in &self.rows {
+            if row.has(other.pivot) {
+                row.xor_with(other);
+            }
+        }
+
+        let (pivot, second) = {
+            let mut vars = row.vars();
+            (vars.next(), vars.next())
+        };
+        match (pivot, second) {
+            (None, _) => if row.rhs { XorAdd::Conflict } else { XorAdd::Redundant },
+            (Some(var), None) => {
+                XorAdd::Unit(if row.rhs { Literal::Pos(var) } else { Literal::Neg(var) })
+            },
+            (Some(var), Some(_)) => {
+                row.pivot = var;
+                for other in self.rows.iter_mut() {
+                    if other.has(var) {
+                        other.xor_with(&row);
+                    }
+                }
+                self.rows.push(row);
+                XorAdd::Added
+            },
+        }
+    }
+
+    /// var が割り当てられたときに呼ぶ
+    /// 理由の節は真になったリテラルを先頭に, 行の他の変数の偽になったリテラルを並べたもの
+    /// # Returns
+    /// * `Ok(implied)` - 真になったリテラルとその理由の節
+    /// * `Err(clause)` - 矛盾した, clause は全てのリテラルが偽になった節
+    pub fn assign(&mut self, var: usize, assigns: &[Option<bool>]) -> Result<Vec<(Literal, Clause)>, Clause> {
+        if var / 64 >= self.words {
+            return Ok(Vec::new());
+        }
+
+        // var が基底変数なら, 同じ行の未割り当ての変数に基底を移す
+        if let Some(r) = self.rows.iter().position(|row| row.pivot == var) {
+            let next = self.rows[r].vars().find(|&v| assigns[v].is_none());
+            if let Some(next) = next {
+                let (before, rest) = self.rows.split_at_mut(r);
+                let (row, after) = rest.split_first_mut().unwrap();
+                for other in before.iter_mut().chain(after.iter_mut()) {
+                    if other.has(next) {
+                        other.xor_with(row);
+                    }
+                }
+                row.pivot = next;
+            }
+        }
+
+        let mut implied = Vec::new();
+        for row in self.rows.iter().filter(|row| row.has(var)) {
+            if let Some(lit) = XorEngine::check_row(row, assigns)? {
+                implied.push(lit);
+            }
+        }
+        Ok(implied)
+    }
+
+    /// 全ての行を調べる, 行を加えて他の行が変わったときに呼ぶ
+    pub fn check_all(&self, assigns: &[Option<bool>]) -> Result<Vec<(Literal, Clause)>, Clause> {
+        let mut implied = Vec::new();
+        for row in &self.rows {
+            if let Some(lit) = XorEngine::check_row(row, assigns)? {
+                implied.push(lit);
+            }
+        }
+        Ok(implied)
+    }
+
+    /// # Returns
+    /// * `Ok(Some((lit, reason)))` - 未割り当ての変数が1つだけなので lit が真になる
+    /// * `Ok(None)` - 何も分からない, または充足している
+    /// * `Err(clause)` - 全ての変数が割り当てられて偽になった
+    fn check_row(row: &XorRow, assigns: &[Option<bool>]) -> Result<Option<(Literal, Clause)>, Clause> {
+        let mut unassigned = None;
+        let mut count = 0;
+        let mut parity = false;
+        for v in row.vars() {
+            match assigns[v] {
+                Some(value) => parity ^= value,
+                None => {
+                    unassigned = Some(v);
+                    count += 1;
+                    if count > 1 {
+                        return Ok(None);
+                    }
+                },
+            }
+        }
+        // 割り当てられた変数は, 割り当てと逆のリテラルにすると偽になる
+        let falsified = || row.vars()
+            .filter_map(|v| assigns[v].map(|value| if value { Literal::Neg(v) } else { Literal::Pos(v) }));
+        match unassigned {
+            None if parity != row.rhs => Err(falsified().collect()),
+            Some(v) => {
+                let lit = if parity != row.rhs { Literal::Pos(v) } else { Literal::Neg(v) };
+                Ok(Some((lit, std::iter::once(lit).chain(falsified()).collect())))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// XOR 制約に現れる未割り当ての変数
+    pub fn unassigned_vars(&self, assigns: &[Option<bool>]) -> Vec<usize> {
+        let mut vars: Vec<usize> = self.rows.iter()
+            .flat_map(|row| row.vars())
+            .filter(|&v| assigns[v].is_none())
+            .collect();
+        vars.sort_unstable();
+        vars.dedup();
+        vars
+    }
+}
+
+// テストコード
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    /// assigns を更新して engine に伝え, 理由の節を確かめてリテラルだけを返す
+    fn assign(engine: &mut XorEngine, assigns: &mut [Option<bool>], lit: Literal) -> Result<Vec<Literal>, ()> {
+        assigns[lit.var()] = Some(lit.is_pos());
+        match engine.assign(lit.var(), assigns) {
+            Ok(implied) => {
+                for (lit, reason) in &implied {
+                    // 理由の節は lit 以外が全て偽
+                    assert_eq!(reason[0], *lit);
+                    assert!(reason[1..].iter().all(|l| assigns[l.var()] == Some(!l.is_pos())));
+                }
+                Ok(implied.into_iter().map(|(lit, _)| lit).collect())
+            },
+            Err(clause) => {
+                assert!(clause.iter().all(|l| assigns[l.var()] == Some(!l.is_pos())));
+                Err(())
+            },
+        }
+    }
+
+    #[test]
+    fn find_xor_from_clauses() {
+        // x1 ⊕ x2 ⊕ x3 = 1
+        let clauses: Clauses = xor_to_clauses(&[0, 1, 2], true);
+        assert_eq!(clauses.len(), 4);
+        let (xors, used) = find_xors(&clauses, 5);
+        assert_eq!(xors, vec![(vec![0, 1, 2], true)]);
+        assert!(used.iter().all(|&u| u));
+    }
+
+    #[test]
+    fn add_reports_units_and_conflicts() {
+        let mut engine = XorEngine::new();
+        assert!(matches!(engine.add(&[0, 1], true), XorAdd::Added));
+        assert!(matches!(engine.add(&[0, 1], true), XorAdd::Redundant));
+        assert!(matches!(engine.add(&[0, 1], false), XorAdd::Conflict));
+        // x1 ⊕ x2 = 1 と x1 ⊕ x3 = 0 から x2 ⊕ x3 = 1 が導かれる
+        assert!(matches!(engine.add(&[0, 2], false), XorAdd::Added));
+        assert!(matches!(engine.add(&[1, 2], true), XorAdd::Redundant));
+        // 3つ合わせると x3 = 1 になる
+        assert!(matches!(engine.add(&[0, 1, 2], false), XorAdd::Unit(Literal::Pos(2))));
+    }
+
+    #[test]
+    fn propagate_last_unassigned_var() {
+        // x1 ⊕ x2 ⊕ x3 = 1
+        let mut engine = XorEngine::new();
+        let mut assigns = vec![None; 3];
+        assert!(matches!(engine.add(&[0, 1, 2], true), XorAdd::Added));
+        assert_eq!(assign(&mut engine, &mut assigns, Literal::Pos(0)), Ok(vec![]));
+        assert_eq!(assign(&mut engine, &mut assigns, Literal::Pos(1)), Ok(vec![Literal::Pos(2)]));
+        assert_eq!(engine.unassigned_vars(&assigns), vec![2]);
+        // 伝播と逆の値を割り当てると矛盾する
+        assert_eq!(assign(&mut engine, &mut assigns, Literal::Neg(2)), Err(()));
+        assert_eq!(engine.check_all(&assigns), Err(vec![Literal::Neg(0), Literal::Neg(1), Literal::Pos(2)]));
+        assigns[2] = Some(true);
+        assert_eq!(engine.check_all(&assigns), Ok(vec![]));
+    }
+
+    #[test]
+    fn propagate_through_eliminated_rows() {
+        // x1 ⊕ x2 = 0, x2 ⊕ x3 = 1 は, x1 を決めると x2 と x3 が決まる
+        let mut engine = XorEngine::new();
+        let mut assigns = vec![None; 3];
+        assert!(matches!(engine.add(&[0, 1], false), XorAdd::Added));
+        assert!(matches!(engine.add(&[1, 2], true), XorAdd::Added));
+        let mut implied = assign(&mut engine, &mut assigns, Literal::Pos(0)).unwrap();
+        implied.sort_unstable_by_key(|lit| lit.var());
+        assert_eq!(implied, vec![Literal::Pos(1), Literal::Neg(2)]);
+
+        // 割り当てを戻しても同値な連立方程式のまま, x3 からも伝播する
+        let mut assigns = vec![None; 3];
+        let mut implied = assign(&mut engine, &mut assigns, Literal::Pos(2)).unwrap();
+        implied.sort_unstable_by_key(|lit| lit.var());
+        assert_eq!(implied, vec![Literal::Neg(0), Literal::Neg(1)]);
+    }
+
+    #[test]
+    fn propagation_and_conflicts_are_sound() {
+        // 伝播したリテラルは割り当てと XOR 制約から導かれ, 矛盾したら解がなく, 全て割り当てたら必ず判定できる
+        let mut rng = Random::new(5);
+        for _ in 0..300 {
+            let size_vars = 2 + rng.gen_range(7);
+            let mut engine = XorEngine::new();
+            let mut xors: Vec<(Vec<usize>, bool)> = Vec::new();
+            for _ in 0..1 + rng.gen_range(4) {
+                let vars: Vec<usize> = (0..size_vars).filter(|_| rng.gen_bool(0.4)).collect();
+                if vars.len() < 2 {
+                    continue;
+                }
+                let parity = rng.gen_bool(0.5);
+                if let XorAdd::Added = engine.add(&vars, parity) {
+                    xors.push((vars, parity));
+                }
+            }
+            let solutions: Vec<u64> = (0..1u64 << size_vars)
+                .filter(|bits| xors.iter().all(|(vars, parity)| vars.iter().fold(false, |p, &v| p ^ ((bits >> v) & 1 == 1)) == *parity))
+                .collect();
+            let consistent = |assigns: &[Option<bool>], bits: u64| {
+                assigns.iter().enumerate().all(|(v, value)| value.is_none_or(|value| ((bits >> v) & 1 == 1) == value))
+            };
+
+            let mut assigns = vec![None; size_vars];
+            let mut order: Vec<usize> = (0..size_vars).collect();
+            for i in (1..size_vars).rev() {
+                order.swap(i, rng.gen_range(i + 1));
+            }
+            for var in order {
+                if assigns[var].is_some() {
+                    continue;
+                }
+                let result = assign(&mut engine, &mut assigns, if rng.gen_bool(0.5) { Literal::Pos(var) } else { Literal::Neg(var) });
+                let remaining: Vec<u64> = solutions.iter().copied().filter(|&bits| consistent(&assigns, bits)).collect();
+                match result {
+                    Err(()) => {
+                        assert!(remaining.is_empty());
+                        break;
+                    },
+                    Ok(implied) => {
+                        for lit in &implied {
+                            assert!(remaining.iter().all(|&bits| ((bits >> lit.var()) & 1 == 1) == lit.is_pos()));
+                        }
+                        // 伝播したリテラルも割り当てる
+                        let mut conflict = false;
+                        for lit in implied {
+                            if assigns[lit.var()].is_none() && assign(&mut engine, &mut assigns, lit).is_err() {
+                                conflict = true;
+                            }
+                        }
+                        if conflict {
+                            assert!(solutions.iter().all(|&bits| !consistent(&assigns, bits)));
+                            break;
+                        }
+                    },
+                }
+                if assigns.iter().all(Option::is_some) {
+                    assert_eq!(engine.check_all(&assigns).is_ok(), solutions.iter().any(|&bits| consistent(&assigns, bits)));
+                }
+            }
+        }
+    }
+}